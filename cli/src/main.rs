@@ -0,0 +1,493 @@
+//! `astrolabe`: operate smart accounts from the command line.
+
+mod vectors;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use astrolabe_client::accounts::{ProgramConfig, Settings};
+use astrolabe_client::client::SmartAccountClient;
+use astrolabe_client::decode::{TransactionAccount, VersionedSettings};
+use astrolabe_client::instructions::CreateSmartAccountInstructionArgs;
+use astrolabe_client::rpc::{
+    fetch_multiple, fetch_smart_account_state, AccountFetcher, TransactionSender,
+};
+use astrolabe_client::types::{Permissions, SmartAccountSigner, SmartAccountTransactionMessage};
+use astrolabe_client::{create_smart_account, pda, SmartAccount};
+use clap::{Parser, Subcommand, ValueEnum};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_commitment_config::CommitmentConfig;
+use solana_keypair::{read_keypair_file, Keypair};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_signer::Signer;
+
+#[derive(Parser)]
+#[command(
+    name = "astrolabe",
+    version,
+    about = "Operate Astrolabe smart accounts"
+)]
+struct Cli {
+    /// JSON RPC URL of the cluster.
+    #[arg(
+        short,
+        long,
+        global = true,
+        default_value = "https://api.mainnet-beta.solana.com"
+    )]
+    url: String,
+    /// Keypair file that signs and pays. Defaults to the Solana CLI keypair.
+    #[arg(short, long, global = true)]
+    keypair: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a smart account where every signer has all permissions.
+    CreateSmartAccount {
+        #[arg(long, value_delimiter = ',', required = true)]
+        signers: Vec<Pubkey>,
+        #[arg(long)]
+        threshold: u16,
+        #[arg(long, default_value_t = 0)]
+        time_lock: u32,
+        #[arg(long)]
+        settings_authority: Option<Pubkey>,
+        #[arg(long)]
+        rent_collector: Option<Pubkey>,
+    },
+    /// Propose a SOL transfer out of a smart account vault.
+    ProposeTransfer {
+        #[arg(long)]
+        settings: Pubkey,
+        #[arg(long)]
+        to: Pubkey,
+        #[arg(long)]
+        lamports: u64,
+        #[arg(long, default_value_t = 0)]
+        account_index: u8,
+        #[arg(long)]
+        memo: Option<String>,
+    },
+    /// Approve, reject or cancel a proposal.
+    Vote {
+        #[arg(long)]
+        settings: Pubkey,
+        #[arg(long)]
+        index: u64,
+        #[arg(value_enum)]
+        vote: Vote,
+        #[arg(long)]
+        memo: Option<String>,
+    },
+    /// Execute an approved transaction or settings transaction whose time
+    /// lock has passed.
+    Execute {
+        #[arg(long)]
+        settings: Pubkey,
+        #[arg(long)]
+        index: u64,
+    },
+    /// Close finished transactions and reclaim their rent: executed,
+    /// rejected or cancelled ones, and stale ones that were never approved.
+    /// Without `--index`, every such transaction is closed; a failed close
+    /// does not stop the others.
+    CloseStale {
+        #[arg(long)]
+        settings: Pubkey,
+        #[arg(long)]
+        index: Option<u64>,
+    },
+    /// Print a smart account's settings and live transactions.
+    Inspect {
+        #[arg(long)]
+        settings: Pubkey,
+        /// Also list stale transactions that are still open.
+        #[arg(long)]
+        include_stale: bool,
+    },
+    /// Print canonical test vectors as JSON, for checking other SDKs
+    /// against this one.
+    TestVectors {
+        /// Write to this file instead of standard output.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Vote {
+    Approve,
+    Reject,
+    Cancel,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let rpc = RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed());
+    run(&rpc, cli.command, || load_keypair(cli.keypair)).await
+}
+
+/// Runs `command` over `rpc`. Only commands that sign call `payer`.
+async fn run<R>(rpc: &R, command: Command, payer: impl FnOnce() -> Result<Keypair>) -> Result<()>
+where
+    R: AccountFetcher + TransactionSender + Sync,
+{
+    match command {
+        Command::CreateSmartAccount {
+            signers,
+            threshold,
+            time_lock,
+            settings_authority,
+            rent_collector,
+        } => {
+            let payer = payer()?;
+            let address = pda::program_config().0;
+            let account = fetch_multiple(rpc, &[address])
+                .await?
+                .pop()
+                .flatten()
+                .ok_or_else(|| anyhow!("Account not found: {}", address))?;
+            let program_config = ProgramConfig::from_bytes(&account.data)?;
+            let (instruction, smart_account) = create_smart_account(
+                &program_config,
+                payer.pubkey(),
+                CreateSmartAccountInstructionArgs {
+                    settings_authority,
+                    threshold,
+                    signers: signers
+                        .into_iter()
+                        .map(|key| SmartAccountSigner {
+                            key,
+                            permissions: Permissions {
+                                mask: Permissions::INITIATE
+                                    | Permissions::VOTE
+                                    | Permissions::EXECUTE,
+                            },
+                        })
+                        .collect(),
+                    restricted_signers: Vec::new(),
+                    time_lock,
+                    rent_collector,
+                    memo: None,
+                },
+            );
+            let client = SmartAccountClient::new(rpc, smart_account.settings(), payer);
+            print_signature(client.send(&[instruction]).await?);
+            println!("Settings: {}", smart_account.settings());
+            println!("Vault:    {}", smart_account.smart_account_pda(0).0);
+        }
+        Command::ProposeTransfer {
+            settings,
+            to,
+            lamports,
+            account_index,
+            memo,
+        } => {
+            let client = SmartAccountClient::new(rpc, settings, payer()?);
+            let payer = client.signer().pubkey();
+            let smart_account = client.smart_account();
+            let index = fetch_settings(rpc, &settings).await?.transaction_index + 1;
+            let vault = smart_account.smart_account_pda(account_index).0;
+            let message = SmartAccountTransactionMessage::try_compile(
+                &vault,
+                &[solana_system_interface::instruction::transfer(
+                    &vault, &to, lamports,
+                )],
+                &[],
+            )?;
+            let args = smart_account.transaction_args(account_index, 0, &message, memo);
+            let signature = client
+                .send(&[
+                    smart_account.create_transaction(index, payer, payer, args),
+                    smart_account.create_proposal(index, payer, payer, false),
+                ])
+                .await?;
+            print_signature(signature);
+            println!("Proposed transaction {}", index);
+        }
+        Command::Vote {
+            settings,
+            index,
+            vote,
+            memo,
+        } => {
+            let client = SmartAccountClient::new(rpc, settings, payer()?);
+            let payer = client.signer().pubkey();
+            let smart_account = client.smart_account();
+            let instruction = match vote {
+                Vote::Approve => smart_account.approve_proposal(index, payer, memo),
+                Vote::Reject => smart_account.reject_proposal(index, payer, memo),
+                Vote::Cancel => smart_account.cancel_proposal(index, payer, memo),
+            };
+            print_signature(client.send(&[instruction]).await?);
+        }
+        Command::Execute { settings, index } => {
+            // Report why the proposal cannot be executed rather than wait.
+            let client =
+                SmartAccountClient::new(rpc, settings, payer()?).with_wait_timeout(Duration::ZERO);
+            print_signature(client.execute_when_ready(index).await?);
+        }
+        Command::CloseStale { settings, index } => {
+            let client = SmartAccountClient::new(rpc, settings, payer()?);
+            match index {
+                Some(index) => match client.reclaim_rent(index).await? {
+                    Some(signature) => print_signature(signature),
+                    None => println!("Transaction {} cannot be closed", index),
+                },
+                None => {
+                    let reclaimed = client.reclaim_all_rent().await?;
+                    for (index, signature) in &reclaimed.closed {
+                        println!("Closed transaction {}: {}", index, signature);
+                    }
+                    for (index, e) in &reclaimed.failed {
+                        eprintln!("Closing transaction {} failed: {:#}", index, e);
+                    }
+                    if !reclaimed.failed.is_empty() {
+                        let failed: Vec<u64> =
+                            reclaimed.failed.iter().map(|(index, _)| *index).collect();
+                        bail!("failed to close transactions {:?}", failed);
+                    }
+                }
+            }
+        }
+        Command::Inspect {
+            settings,
+            include_stale,
+        } => {
+            let state = fetch_smart_account_state(rpc, &settings, include_stale).await?;
+            let smart_account = SmartAccount::new(settings);
+            println!("Settings:          {}", settings);
+            println!(
+                "Vault:             {}",
+                smart_account.smart_account_pda(0).0
+            );
+            println!("Authority:         {}", state.settings.settings_authority);
+            println!("Threshold:         {}", state.settings.threshold);
+            println!("Time lock:         {}s", state.settings.time_lock);
+            println!("Transaction index: {}", state.settings.transaction_index);
+            println!(
+                "Stale index:       {}",
+                state.settings.stale_transaction_index
+            );
+            println!("Signers:");
+            for signer in &state.settings.signers {
+                println!(
+                    "  {} (permissions {:#05b})",
+                    signer.key, signer.permissions.mask
+                );
+            }
+            println!("Transactions:");
+            for transaction in &state.transactions {
+                let kind = match &transaction.transaction {
+                    Some(TransactionAccount::Transaction(_)) => "transaction",
+                    Some(TransactionAccount::SettingsTransaction(_)) => "settings transaction",
+                    Some(TransactionAccount::Batch(_)) => "batch",
+                    None => "closed",
+                };
+                match &transaction.proposal {
+                    Some(proposal) => println!(
+                        "  #{} {}: {:?}, {} approved, {} rejected",
+                        transaction.transaction_index,
+                        kind,
+                        proposal.status,
+                        proposal.approved.len(),
+                        proposal.rejected.len()
+                    ),
+                    None => println!("  #{} {}: no proposal", transaction.transaction_index, kind),
+                }
+            }
+        }
+        Command::TestVectors { out } => {
+            let vectors = serde_json::to_string_pretty(&vectors::generate()?)?;
+            match out {
+                Some(path) => std::fs::write(&path, vectors + "\n")
+                    .with_context(|| format!("writing {}", path.display()))?,
+                None => println!("{}", vectors),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn load_keypair(path: Option<PathBuf>) -> Result<Keypair> {
+    let path = match path {
+        Some(path) => path,
+        None => dirs::home_dir()
+            .ok_or_else(|| anyhow!("cannot locate home directory"))?
+            .join(".config/solana/id.json"),
+    };
+    if path.to_string_lossy().starts_with("usb://") {
+        bail!("hardware wallet signing is not supported yet; pass a keypair file");
+    }
+    read_keypair_file(&path)
+        .map_err(|e| anyhow!("failed to read keypair {}: {}", path.display(), e))
+}
+
+async fn fetch_settings<R: AccountFetcher>(rpc: &R, settings: &Pubkey) -> Result<Settings> {
+    let account = fetch_multiple(rpc, &[*settings])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| anyhow!("Account not found: {}", settings))?;
+    Ok(VersionedSettings::from_bytes(&account.data)?.into_current())
+}
+
+fn print_signature(signature: Signature) {
+    println!("Signature: {}", signature);
+}
+
+#[cfg(test)]
+mod tests {
+    use astrolabe_client::accounts::{Proposal, SettingsTransaction};
+    use astrolabe_client::decode::{
+        PROPOSAL_DISCRIMINATOR, SETTINGS_DISCRIMINATOR, SETTINGS_TRANSACTION_DISCRIMINATOR,
+    };
+    use astrolabe_client::mock::{MockOutcome, MockRpc};
+    use astrolabe_client::types::ProposalStatus;
+    use futures::executor::block_on;
+
+    use super::*;
+
+    const SETTINGS: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    /// A smart account at `transaction_index` whose only signer is the
+    /// returned payer.
+    fn smart_account(transaction_index: u64) -> (MockRpc, Keypair) {
+        let payer = Keypair::new();
+        let rpc = MockRpc::new();
+        rpc.set_clock(1, 1_000);
+        rpc.set_program_account_with_space(
+            SETTINGS,
+            &Settings {
+                discriminator: SETTINGS_DISCRIMINATOR,
+                seed: 0,
+                settings_authority: Pubkey::default(),
+                threshold: 1,
+                time_lock: 0,
+                transaction_index,
+                stale_transaction_index: 0,
+                archival_authority: None,
+                archivable_after: 0,
+                bump: 255,
+                signers: vec![SmartAccountSigner {
+                    key: payer.pubkey(),
+                    permissions: Permissions {
+                        mask: Permissions::INITIATE | Permissions::VOTE | Permissions::EXECUTE,
+                    },
+                }],
+                restricted_signers: Vec::new(),
+                account_utilization: 0,
+                reserved1: 0,
+                reserved2: 0,
+            },
+            Settings::size(1, 0),
+        );
+        (rpc, payer)
+    }
+
+    fn set_settings_transaction(rpc: &MockRpc, index: u64, status: ProposalStatus) {
+        let smart_account = SmartAccount::new(SETTINGS);
+        rpc.set_program_account(
+            smart_account.transaction_pda(index).0,
+            &SettingsTransaction {
+                discriminator: SETTINGS_TRANSACTION_DISCRIMINATOR,
+                settings: SETTINGS,
+                creator: Pubkey::default(),
+                rent_collector: Pubkey::default(),
+                index,
+                bump: 255,
+                actions: Vec::new(),
+            },
+        );
+        rpc.set_program_account_with_space(
+            smart_account.proposal_pda(index).0,
+            &Proposal {
+                discriminator: PROPOSAL_DISCRIMINATOR,
+                settings: SETTINGS,
+                transaction_index: index,
+                rent_collector: Pubkey::default(),
+                status,
+                bump: 255,
+                approved: Vec::new(),
+                rejected: Vec::new(),
+                cancelled: Vec::new(),
+            },
+            Proposal::size(1),
+        );
+    }
+
+    #[test]
+    fn close_stale_closes_what_the_program_lets_be_closed() {
+        let (rpc, payer) = smart_account(3);
+        set_settings_transaction(&rpc, 1, ProposalStatus::Executed { timestamp: 0 });
+        set_settings_transaction(&rpc, 2, ProposalStatus::Active { timestamp: 0 });
+        set_settings_transaction(&rpc, 3, ProposalStatus::Cancelled { timestamp: 0 });
+        let close = |index| {
+            block_on(run(
+                &rpc,
+                Command::CloseStale {
+                    settings: SETTINGS,
+                    index,
+                },
+                || Ok(payer.insecure_clone()),
+            ))
+        };
+
+        close(Some(2)).unwrap();
+        assert!(rpc.sent_transactions().is_empty());
+
+        // Every attempt at the close of 1 fails in transit; 3 still closes.
+        for _ in 0..4 {
+            rpc.push_outcome(MockOutcome::NetworkError);
+        }
+        assert!(close(None).is_err());
+        assert_eq!(rpc.sent_transactions().len(), 5);
+
+        close(None).unwrap();
+        assert_eq!(rpc.sent_transactions().len(), 7);
+    }
+
+    #[test]
+    fn execute_fails_until_the_proposal_is_approved() {
+        let (rpc, payer) = smart_account(1);
+        let execute = || {
+            block_on(run(
+                &rpc,
+                Command::Execute {
+                    settings: SETTINGS,
+                    index: 1,
+                },
+                || Ok(payer.insecure_clone()),
+            ))
+        };
+
+        set_settings_transaction(&rpc, 1, ProposalStatus::Active { timestamp: 0 });
+        assert!(execute().is_err());
+        assert!(rpc.sent_transactions().is_empty());
+
+        set_settings_transaction(&rpc, 1, ProposalStatus::Approved { timestamp: 0 });
+        execute().unwrap();
+        assert_eq!(rpc.sent_transactions().len(), 1);
+    }
+
+    #[test]
+    fn read_only_commands_do_not_load_a_keypair() {
+        let (rpc, _) = smart_account(1);
+        set_settings_transaction(&rpc, 1, ProposalStatus::Active { timestamp: 0 });
+        block_on(run(
+            &rpc,
+            Command::Inspect {
+                settings: SETTINGS,
+                include_stale: false,
+            },
+            || bail!("no keypair"),
+        ))
+        .unwrap();
+    }
+}
@@ -0,0 +1,570 @@
+//! Canonical test vectors for other SDK implementations.
+//!
+//! Every input is fixed, so the output only changes when an encoding does:
+//! SDKs in other languages can check their PDA derivations, message
+//! serialization and instruction encoding against it byte for byte. Byte
+//! strings are lowercase hex, keys are base58.
+
+use anyhow::Result;
+use astrolabe_client::accounts::{ProgramConfig, SpendingLimit};
+use astrolabe_client::instructions::{
+    AddSpendingLimitAsAuthorityInstructionArgs, CreateSmartAccountInstructionArgs,
+    CreateTransactionBufferInstructionArgs, InitializeProgramConfigInstructionArgs, LogEvent,
+    LogEventInstructionArgs, UseSpendingLimitInstructionArgs,
+};
+use astrolabe_client::offchain::{OffchainAction, OffchainMessage};
+use astrolabe_client::program_config::{
+    initialize_program_config, set_program_config_authority,
+    set_program_config_smart_account_creation_fee, set_program_config_treasury,
+};
+use astrolabe_client::types::{
+    Period, Permissions, SettingsAction, SmartAccountSigner, SmartAccountTransactionMessage,
+};
+use astrolabe_client::{create_smart_account, pda, SmartAccount};
+use serde_json::{json, Value};
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+
+/// Bumped whenever vectors are added, removed or change meaning.
+const VECTORS_VERSION: u32 = 1;
+
+const SETTINGS_SEED: u128 = 1;
+const TRANSACTION_INDEX: u64 = 1;
+const BATCH_TRANSACTION_INDEX: u32 = 1;
+const BUFFER_INDEX: u8 = 0;
+
+/// The fixed key numbered `n`: 32 bytes of `n`.
+fn key(n: u8) -> Pubkey {
+    Pubkey::new_from_array([n; 32])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn generate() -> Result<Value> {
+    let smart_account = SmartAccount::from_seed(SETTINGS_SEED);
+    let settings = smart_account.settings();
+    let vault = smart_account.smart_account_pda(0).0;
+    let message = SmartAccountTransactionMessage::try_compile(
+        &vault,
+        &[solana_system_interface::instruction::transfer(
+            &vault,
+            &key(5),
+            1_000_000,
+        )],
+        &[],
+    )?;
+
+    Ok(json!({
+        "version": VECTORS_VERSION,
+        "program_id": astrolabe_client::ID.to_string(),
+        "keys": (1..=9)
+            .map(|n| json!({ "n": n, "pubkey": key(n).to_string() }))
+            .collect::<Vec<_>>(),
+        "pdas": pdas(&settings),
+        "messages": [{
+            "name": "vault_sol_transfer",
+            "description": "System transfer of 1000000 lamports from vault 0 to key 5",
+            "bytes": hex(&borsh::to_vec(&message)?),
+        }],
+        "instructions": instructions(&smart_account, &message)?
+            .into_iter()
+            .map(|(name, instruction)| instruction_vector(name, &instruction))
+            .collect::<Vec<_>>(),
+        "offchain_messages": [{
+            "name": "approve",
+            "bytes": hex(&OffchainMessage {
+                chain_id: [7; 32],
+                settings,
+                nonce: 1,
+                expires_at: 1_700_000_000,
+                action: OffchainAction::Approve {
+                    transaction_index: TRANSACTION_INDEX,
+                },
+            }
+            .serialize()),
+        }],
+    }))
+}
+
+fn pda_vector(name: &str, seeds: Value, (address, bump): (Pubkey, u8)) -> Value {
+    json!({
+        "name": name,
+        "seeds": seeds,
+        "address": address.to_string(),
+        "bump": bump,
+    })
+}
+
+fn pdas(settings: &Pubkey) -> Vec<Value> {
+    let transaction = pda::transaction(settings, TRANSACTION_INDEX);
+    vec![
+        pda_vector("program_config", json!({}), pda::program_config()),
+        pda_vector(
+            "settings",
+            json!({ "seed": SETTINGS_SEED.to_string() }),
+            pda::settings(SETTINGS_SEED),
+        ),
+        pda_vector(
+            "smart_account",
+            json!({ "settings": settings.to_string(), "account_index": 0 }),
+            pda::smart_account(settings, 0),
+        ),
+        pda_vector(
+            "smart_account",
+            json!({ "settings": settings.to_string(), "account_index": 1 }),
+            pda::smart_account(settings, 1),
+        ),
+        pda_vector(
+            "transaction",
+            json!({ "settings": settings.to_string(), "transaction_index": TRANSACTION_INDEX }),
+            transaction,
+        ),
+        pda_vector(
+            "proposal",
+            json!({ "settings": settings.to_string(), "transaction_index": TRANSACTION_INDEX }),
+            pda::proposal(settings, TRANSACTION_INDEX),
+        ),
+        pda_vector(
+            "batch_transaction",
+            json!({
+                "settings": settings.to_string(),
+                "batch_index": TRANSACTION_INDEX,
+                "transaction_index": BATCH_TRANSACTION_INDEX,
+            }),
+            pda::batch_transaction(settings, TRANSACTION_INDEX, BATCH_TRANSACTION_INDEX),
+        ),
+        pda_vector(
+            "spending_limit",
+            json!({ "settings": settings.to_string(), "seed": key(9).to_string() }),
+            pda::spending_limit(settings, &key(9)),
+        ),
+        pda_vector(
+            "transaction_buffer",
+            json!({
+                "settings": settings.to_string(),
+                "creator": key(2).to_string(),
+                "buffer_index": BUFFER_INDEX,
+            }),
+            pda::transaction_buffer(settings, &key(2), BUFFER_INDEX),
+        ),
+        pda_vector(
+            "ephemeral_signer",
+            json!({ "transaction": transaction.0.to_string(), "ephemeral_signer_index": 0 }),
+            pda::ephemeral_signer(&transaction.0, 0),
+        ),
+    ]
+}
+
+/// One instance of every instruction. Key 1 is the program config
+/// authority, key 2 the creator, signer and payer, key 3 the settings
+/// authority and key 4 the rent collector.
+fn instructions(
+    smart_account: &SmartAccount,
+    message: &SmartAccountTransactionMessage,
+) -> Result<Vec<(&'static str, Instruction)>> {
+    let (authority, signer, settings_authority, rent_collector) = (key(1), key(2), key(3), key(4));
+    let all_permissions = Permissions::INITIATE | Permissions::VOTE | Permissions::EXECUTE;
+    let new_signer = SmartAccountSigner {
+        key: key(6),
+        permissions: Permissions {
+            mask: all_permissions,
+        },
+    };
+    let program_config = ProgramConfig {
+        discriminator: [0; 8],
+        smart_account_index: SETTINGS_SEED - 1,
+        authority,
+        smart_account_creation_fee: 0,
+        treasury: key(8),
+        reserved: [0; 64],
+    };
+    let spending_limit = SpendingLimit {
+        discriminator: [0; 8],
+        settings: smart_account.settings(),
+        seed: key(9),
+        account_index: 0,
+        mint: Pubkey::default(),
+        amount: 1_000_000,
+        period: Period::Day,
+        remaining_amount: 1_000_000,
+        last_reset: 0,
+        bump: 0,
+        signers: vec![signer],
+        destinations: vec![key(5)],
+        expiration: i64::MAX,
+    };
+    let actions = vec![
+        SettingsAction::AddSigner {
+            new_signer: new_signer.clone(),
+        },
+        SettingsAction::ChangeThreshold { new_threshold: 2 },
+    ];
+    let message_accounts = message.execution_accounts(&[])?;
+    let message_bytes = borsh::to_vec(message)?;
+    let args = || smart_account.transaction_args(0, 0, message, Some("vectors".to_string()));
+    let memo = || Some("vectors".to_string());
+
+    Ok(vec![
+        (
+            "initialize_program_config",
+            initialize_program_config(
+                authority,
+                InitializeProgramConfigInstructionArgs {
+                    authority,
+                    smart_account_creation_fee: 0,
+                    treasury: key(8),
+                },
+            ),
+        ),
+        (
+            "set_program_config_authority",
+            set_program_config_authority(authority, key(7)),
+        ),
+        (
+            "set_program_config_smart_account_creation_fee",
+            set_program_config_smart_account_creation_fee(authority, 10_000_000),
+        ),
+        (
+            "set_program_config_treasury",
+            set_program_config_treasury(authority, key(7)),
+        ),
+        (
+            "create_smart_account",
+            create_smart_account(
+                &program_config,
+                signer,
+                CreateSmartAccountInstructionArgs {
+                    settings_authority: None,
+                    threshold: 1,
+                    signers: vec![SmartAccountSigner {
+                        key: signer,
+                        permissions: Permissions {
+                            mask: all_permissions,
+                        },
+                    }],
+                    restricted_signers: Vec::new(),
+                    time_lock: 0,
+                    rent_collector: Some(rent_collector),
+                    memo: memo(),
+                },
+            )
+            .0,
+        ),
+        (
+            "add_signer_as_authority",
+            smart_account.add_signer_as_authority(
+                settings_authority,
+                Some(signer),
+                new_signer.clone(),
+                memo(),
+            ),
+        ),
+        (
+            "remove_signer_as_authority",
+            smart_account.remove_signer_as_authority(settings_authority, None, key(6), memo()),
+        ),
+        (
+            "change_threshold_as_authority",
+            smart_account.change_threshold_as_authority(settings_authority, None, 2, memo()),
+        ),
+        (
+            "set_time_lock_as_authority",
+            smart_account.set_time_lock_as_authority(settings_authority, None, 3600, memo()),
+        ),
+        (
+            "set_new_settings_authority_as_authority",
+            smart_account.set_new_settings_authority_as_authority(
+                settings_authority,
+                None,
+                key(7),
+                memo(),
+            ),
+        ),
+        (
+            "set_archival_authority_as_authority",
+            smart_account.set_archival_authority_as_authority(
+                settings_authority,
+                None,
+                Some(key(7)),
+                memo(),
+            ),
+        ),
+        (
+            "add_spending_limit_as_authority",
+            smart_account.add_spending_limit_as_authority(
+                settings_authority,
+                signer,
+                AddSpendingLimitAsAuthorityInstructionArgs {
+                    seed: key(9),
+                    account_index: 0,
+                    mint: Pubkey::default(),
+                    amount: 1_000_000,
+                    period: Period::Day,
+                    signers: vec![signer],
+                    destinations: vec![key(5)],
+                    expiration: i64::MAX,
+                    memo: memo(),
+                },
+            ),
+        ),
+        (
+            "remove_spending_limit_as_authority",
+            smart_account.remove_spending_limit_as_authority(
+                settings_authority,
+                smart_account.spending_limit_pda(&key(9)).0,
+                rent_collector,
+                memo(),
+            ),
+        ),
+        (
+            "create_settings_transaction",
+            smart_account.create_settings_transaction(
+                TRANSACTION_INDEX,
+                signer,
+                signer,
+                actions.clone(),
+                memo(),
+            ),
+        ),
+        (
+            "execute_settings_transaction",
+            smart_account.execute_settings_transaction(
+                TRANSACTION_INDEX,
+                signer,
+                Some(signer),
+                &actions,
+            ),
+        ),
+        (
+            "execute_settings_transaction_sync",
+            smart_account.execute_settings_transaction_sync(
+                &[signer],
+                Some(signer),
+                actions.clone(),
+                memo(),
+            )?,
+        ),
+        (
+            "close_settings_transaction",
+            smart_account.close_settings_transaction(
+                TRANSACTION_INDEX,
+                rent_collector,
+                rent_collector,
+            ),
+        ),
+        (
+            "create_transaction",
+            smart_account.create_transaction(TRANSACTION_INDEX, signer, signer, args()),
+        ),
+        (
+            "create_transaction_buffer",
+            smart_account.create_transaction_buffer(
+                signer,
+                signer,
+                CreateTransactionBufferInstructionArgs {
+                    buffer_index: BUFFER_INDEX,
+                    account_index: 0,
+                    final_buffer_hash: [0; 32],
+                    final_buffer_size: message_bytes.len() as u16,
+                    buffer: message_bytes[..message_bytes.len() / 2].to_vec(),
+                },
+            ),
+        ),
+        (
+            "extend_transaction_buffer",
+            smart_account.extend_transaction_buffer(
+                signer,
+                BUFFER_INDEX,
+                message_bytes[message_bytes.len() / 2..].to_vec(),
+            ),
+        ),
+        (
+            "create_transaction_from_buffer",
+            smart_account.create_transaction_from_buffer(
+                TRANSACTION_INDEX,
+                signer,
+                signer,
+                BUFFER_INDEX,
+                args(),
+            ),
+        ),
+        (
+            "close_transaction_buffer",
+            smart_account.close_transaction_buffer(signer, BUFFER_INDEX),
+        ),
+        (
+            "create_proposal",
+            smart_account.create_proposal(TRANSACTION_INDEX, signer, signer, false),
+        ),
+        (
+            "activate_proposal",
+            smart_account.activate_proposal(TRANSACTION_INDEX, signer),
+        ),
+        (
+            "approve_proposal",
+            smart_account.approve_proposal(TRANSACTION_INDEX, signer, memo()),
+        ),
+        (
+            "reject_proposal",
+            smart_account.reject_proposal(TRANSACTION_INDEX, signer, memo()),
+        ),
+        (
+            "cancel_proposal",
+            smart_account.cancel_proposal(TRANSACTION_INDEX, signer, memo()),
+        ),
+        (
+            "execute_transaction",
+            smart_account.execute_transaction(TRANSACTION_INDEX, signer, &message_accounts),
+        ),
+        (
+            "execute_transaction_sync",
+            smart_account.execute_transaction_sync(
+                0,
+                &[signer],
+                message_bytes.clone(),
+                &message_accounts,
+            )?,
+        ),
+        (
+            "close_transaction",
+            smart_account.close_transaction(TRANSACTION_INDEX, rent_collector, rent_collector),
+        ),
+        (
+            "create_batch",
+            smart_account.create_batch(TRANSACTION_INDEX, signer, signer, 0, memo()),
+        ),
+        (
+            "add_transaction_to_batch",
+            smart_account.add_transaction_to_batch(
+                TRANSACTION_INDEX,
+                BATCH_TRANSACTION_INDEX,
+                signer,
+                signer,
+                0,
+                message,
+            ),
+        ),
+        (
+            "execute_batch_transaction",
+            smart_account.execute_batch_transaction(
+                TRANSACTION_INDEX,
+                BATCH_TRANSACTION_INDEX,
+                signer,
+                &message_accounts,
+            ),
+        ),
+        (
+            "close_batch_transaction",
+            smart_account.close_batch_transaction(
+                TRANSACTION_INDEX,
+                BATCH_TRANSACTION_INDEX,
+                rent_collector,
+            ),
+        ),
+        (
+            "close_batch",
+            smart_account.close_batch(TRANSACTION_INDEX, rent_collector, rent_collector),
+        ),
+        (
+            "use_spending_limit",
+            smart_account.use_spending_limit(
+                &spending_limit,
+                signer,
+                key(5),
+                None,
+                UseSpendingLimitInstructionArgs {
+                    amount: 1_000,
+                    decimals: 9,
+                    memo: memo(),
+                },
+            ),
+        ),
+        (
+            "log_event",
+            LogEvent {
+                log_authority: smart_account.settings(),
+            }
+            .instruction(LogEventInstructionArgs {
+                account_seeds: vec![b"smart_account".to_vec()],
+                bump: 255,
+                event: vec![1, 2, 3],
+            }),
+        ),
+    ])
+}
+
+fn instruction_vector(name: &str, instruction: &Instruction) -> Value {
+    json!({
+        "name": name,
+        "program_id": instruction.program_id.to_string(),
+        "accounts": instruction
+            .accounts
+            .iter()
+            .map(|meta| json!({
+                "pubkey": meta.pubkey.to_string(),
+                "is_signer": meta.is_signer,
+                "is_writable": meta.is_writable,
+            }))
+            .collect::<Vec<_>>(),
+        "data": hex(&instruction.data),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use borsh::BorshDeserialize;
+
+    use super::*;
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn vectors_are_deterministic() {
+        assert_eq!(generate().unwrap(), generate().unwrap());
+    }
+
+    #[test]
+    fn vectors_decode_back_to_their_inputs() {
+        let vectors = generate().unwrap();
+
+        let names: Vec<&str> = vectors["instructions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|vector| vector["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names.iter().collect::<HashSet<_>>().len(), names.len());
+
+        let settings = SmartAccount::from_seed(SETTINGS_SEED).settings();
+        let vault = pda::smart_account(&settings, 0).0;
+        let message = SmartAccountTransactionMessage::try_from_slice(&unhex(
+            vectors["messages"][0]["bytes"].as_str().unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(message.num_signers, 1);
+        assert_eq!(message.account_keys[..2], [vault, key(5)]);
+
+        let vault_vector = &vectors["pdas"][2];
+        assert_eq!(vault_vector["seeds"]["account_index"], 0);
+        assert_eq!(vault_vector["address"], vault.to_string());
+
+        let approve = vectors["instructions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|vector| vector["name"] == "approve_proposal")
+            .unwrap();
+        assert_eq!(approve["program_id"], astrolabe_client::ID.to_string());
+        assert_eq!(approve["accounts"][1]["pubkey"], key(2).to_string());
+        assert_eq!(approve["accounts"][1]["is_signer"], true);
+    }
+}
@@ -0,0 +1,13 @@
+//! Transaction, settings transaction and batch accounts as fetched from a
+//! cluster: decoding arbitrary account data must never panic.
+
+#![no_main]
+
+use astrolabe_client::decode::TransactionAccount;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(TransactionAccount::Transaction(transaction)) = TransactionAccount::from_bytes(data) {
+        let _ = transaction.message.execution_accounts(&[]);
+    }
+});
@@ -0,0 +1,46 @@
+//! Stored transaction messages are written by proposers, so every byte is
+//! untrusted. Decoding one and resolving its execution accounts must fail
+//! cleanly instead of panicking, and whatever decodes must survive the
+//! conversions to and from a v0 message unchanged.
+
+#![no_main]
+
+use astrolabe_client::types::SmartAccountTransactionMessage;
+use borsh::BorshDeserialize;
+use libfuzzer_sys::fuzz_target;
+use solana_message::{v0, AddressLookupTableAccount};
+use solana_pubkey::Pubkey;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(message) = SmartAccountTransactionMessage::try_from_slice(data) else {
+        return;
+    };
+    assert_eq!(borsh::to_vec(&message).unwrap(), data);
+
+    // Tables short enough that some lookup indexes fall outside them.
+    let tables: Vec<AddressLookupTableAccount> = message
+        .address_table_lookups
+        .iter()
+        .map(|lookup| AddressLookupTableAccount {
+            key: lookup.account_key,
+            addresses: (0..128).map(|i| Pubkey::new_from_array([i; 32])).collect(),
+        })
+        .collect();
+    if let Ok(accounts) = message.execution_accounts(&tables) {
+        let loaded: usize = message
+            .address_table_lookups
+            .iter()
+            .map(|lookup| 1 + lookup.writable_indexes.len() + lookup.readonly_indexes.len())
+            .sum();
+        assert_eq!(accounts.len(), message.account_keys.len() + loaded);
+        assert!(accounts.iter().all(|meta| !meta.is_signer));
+    }
+    if !message.address_table_lookups.is_empty() {
+        assert!(message.execution_accounts(&[]).is_err());
+    }
+
+    if let Ok(v0_message) = v0::Message::try_from(&message) {
+        let round_trip = SmartAccountTransactionMessage::try_from(&v0_message).unwrap();
+        assert_eq!(round_trip, message);
+    }
+});
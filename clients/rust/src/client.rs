@@ -0,0 +1,914 @@
+//! End-to-end flows over a transport.
+//!
+//! [`SmartAccountClient`] chains the instruction builders in
+//! [`crate::smart_account`] into whole operations: proposing, waiting out
+//! votes and the time lock, executing and reclaiming rent. Every transaction
+//! is signed by one signer, which pays fees and rent and votes.
+
+use std::io;
+use std::time::Duration;
+
+use solana_account::Account;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_sdk_ids::sysvar;
+use solana_signature::Signature;
+use solana_signer::Signer;
+use solana_transaction::versioned::VersionedTransaction;
+use thiserror::Error;
+
+use crate::accounts::Settings;
+use crate::decode::{TransactionAccount, VersionedProposal, VersionedSettings};
+use crate::errors::AstrolabeSmartAccountError;
+use crate::lifecycle::{is_closable, ProposalAction, ProposalLifecycle, TransactionKind};
+use crate::lookup_table::{
+    create_lookup_table_instructions, fetch_lookup_table, fetch_slot, lookup_table_addresses,
+};
+use crate::message::MessageError;
+use crate::preflight::{preflight, Preflight, PreflightError, TransactionSimulator};
+use crate::priority_fee::{with_priority_fee, PriorityFeeConfig};
+use crate::rpc::{
+    fetch_address_lookup_tables, fetch_multiple, fetch_transaction_page, index_pages,
+    unix_timestamp, AccountFetcher, TransactionSender,
+};
+use crate::sender::SendError;
+use crate::transaction::{BuildError, TransactionFormat};
+use crate::types::{ProposalStatus, SmartAccountTransactionMessage};
+use crate::SmartAccount;
+
+const DEFAULT_MAX_RETRIES: usize = 3;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error(transparent)]
+    Rpc(#[from] io::Error),
+    #[error(transparent)]
+    Message(#[from] MessageError),
+    #[error("signing failed: {0}")]
+    Signer(String),
+    #[error("proposal {0} does not exist")]
+    MissingProposal(u64),
+    #[error("proposal {0} can no longer be executed: {1:?}")]
+    NotExecutable(u64, ProposalStatus),
+    /// The program would refuse the execution for a reason waiting does
+    /// not change, such as missing permissions or a stale settings
+    /// transaction.
+    #[error("proposal {0} cannot be executed: {1}")]
+    CannotExecute(u64, AstrolabeSmartAccountError),
+    #[error("transaction {0} is a batch, which is not supported")]
+    Batch(u64),
+    #[error(transparent)]
+    Preflight(#[from] PreflightError),
+    #[error(transparent)]
+    Send(#[from] SendError),
+    #[error("proposal {0} is stale and can no longer be approved")]
+    Stale(u64),
+    #[error("gave up waiting for proposal {0} to become executable")]
+    Timeout(u64),
+}
+
+impl ClientError {
+    /// Whether sending again may succeed: the transport failed or the
+    /// transaction expired. A program rejecting a transaction is
+    /// deterministic and is not worth retrying.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Send(SendError::Network(_) | SendError::Expired(_))
+        )
+    }
+}
+
+impl From<BuildError> for ClientError {
+    fn from(error: BuildError) -> Self {
+        match error {
+            BuildError::Message(error) => Self::Message(error),
+            error => Self::Signer(error.to_string()),
+        }
+    }
+}
+
+/// What [`SmartAccountClient::reclaim_all_rent`] closed and failed to close.
+#[derive(Debug, Default)]
+pub struct Reclaimed {
+    /// Closed transaction indexes with the signatures that closed them.
+    pub closed: Vec<(u64, Signature)>,
+    /// Transaction indexes whose close failed, with the error.
+    pub failed: Vec<(u64, ClientError)>,
+}
+
+/// Runs whole smart account flows for one signer.
+pub struct SmartAccountClient<R, S> {
+    rpc: R,
+    smart_account: SmartAccount,
+    signer: S,
+    max_retries: usize,
+    poll_interval: Duration,
+    wait_timeout: Option<Duration>,
+    format: TransactionFormat,
+    priority_fee: Option<PriorityFeeConfig>,
+}
+
+impl<R: AccountFetcher + TransactionSender, S: Signer> SmartAccountClient<R, S> {
+    pub fn new(rpc: R, settings: Pubkey, signer: S) -> Self {
+        Self {
+            rpc,
+            smart_account: SmartAccount::new(settings),
+            signer,
+            max_retries: DEFAULT_MAX_RETRIES,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            wait_timeout: None,
+            format: TransactionFormat::default(),
+            priority_fee: None,
+        }
+    }
+
+    /// How many times a send that failed in transit or expired is retried
+    /// with a fresh blockhash.
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// How long to wait between retries and status polls.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// How long [`Self::execute_when_ready`] waits for a proposal, time
+    /// lock included, before failing with [`ClientError::Timeout`]. Counted
+    /// in the durations slept between polls. Unbounded by default.
+    pub fn with_wait_timeout(mut self, wait_timeout: Duration) -> Self {
+        self.wait_timeout = Some(wait_timeout);
+        self
+    }
+
+    /// The format every transaction is sent in. Defaults to legacy.
+    pub fn with_transaction_format(mut self, format: TransactionFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Adds a priority fee to every transaction, estimated before each
+    /// attempt from fees recently paid for the accounts it writes.
+    pub fn with_priority_fee(mut self, config: PriorityFeeConfig) -> Self {
+        self.priority_fee = Some(config);
+        self
+    }
+
+    pub fn rpc(&self) -> &R {
+        &self.rpc
+    }
+
+    pub fn smart_account(&self) -> &SmartAccount {
+        &self.smart_account
+    }
+
+    pub fn signer(&self) -> &S {
+        &self.signer
+    }
+
+    /// Creates a vault transaction for `message`, opens its proposal and
+    /// approves it in one transaction. Every signer of `message` after the
+    /// smart account is taken to be an ephemeral signer.
+    ///
+    /// After a failed attempt the transaction at the index is looked up.
+    /// If this signer created it, an earlier attempt landed after all and
+    /// its index is returned instead of proposing again. If another
+    /// proposer took the index, the attempt is retried at the next free
+    /// one. Returns the transaction index.
+    pub async fn propose_and_approve(
+        &self,
+        account_index: u8,
+        message: &SmartAccountTransactionMessage,
+        memo: Option<String>,
+    ) -> Result<u64, ClientError> {
+        let signer = self.signer.pubkey();
+        let ephemeral_signers = message.num_signers.saturating_sub(1);
+        let mut index = self.fetch_settings().await?.transaction_index + 1;
+        let mut attempt = 0;
+        loop {
+            let args = self.smart_account.transaction_args(
+                account_index,
+                ephemeral_signers,
+                message,
+                memo.clone(),
+            );
+            let instructions = [
+                self.smart_account
+                    .create_transaction(index, signer, signer, args),
+                self.smart_account
+                    .create_proposal(index, signer, signer, false),
+                self.smart_account.approve_proposal(index, signer, None),
+            ];
+            let error = match self.send_once(&instructions, &self.format).await {
+                Ok(_) => return Ok(index),
+                Err(e) => e,
+            };
+            // All three instructions land together, so a transaction this
+            // signer created is proposed and approved as well.
+            match self.fetch_transaction_account(index).await? {
+                Some(TransactionAccount::Transaction(transaction))
+                    if transaction.creator == signer
+                        && transaction.account_index == account_index =>
+                {
+                    return Ok(index)
+                }
+                Some(_) => index = self.fetch_settings().await?.transaction_index + 1,
+                None if !error.is_transient() => return Err(error),
+                None => {}
+            }
+            if attempt >= self.max_retries {
+                return Err(error);
+            }
+            attempt += 1;
+            self.rpc.sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Waits until the proposal at `transaction_index` is approved and its
+    /// time lock has passed on the cluster clock, then executes it.
+    ///
+    /// Fails if the proposal is rejected, cancelled or already executed, if
+    /// it went stale before it was approved, if the program would refuse
+    /// this signer's execution for good, or once the
+    /// [wait timeout](Self::with_wait_timeout) runs out. Drop the future to
+    /// stop waiting earlier.
+    pub async fn execute_when_ready(
+        &self,
+        transaction_index: u64,
+    ) -> Result<Signature, ClientError> {
+        self.wait_until_executable(transaction_index).await?;
+        let instruction = self.execute_instruction(transaction_index).await?;
+        self.send(&[instruction]).await
+    }
+
+    /// Like [`Self::execute_when_ready`], but loads the accounts of the
+    /// execution from a new lookup table so that large vault transactions
+    /// fit in one v0 transaction. Tables of the client's format are used as
+    /// well.
+    ///
+    /// The table is created and extended by the signer, who remains its
+    /// authority and can deactivate and close it afterwards to reclaim the
+    /// rent. Returns the table address and the execution signature.
+    pub async fn execute_with_lookup_table(
+        &self,
+        transaction_index: u64,
+    ) -> Result<(Pubkey, Signature), ClientError> {
+        self.wait_until_executable(transaction_index).await?;
+        let instruction = self.execute_instruction(transaction_index).await?;
+
+        let signer = self.signer.pubkey();
+        let (table, batches) = create_lookup_table_instructions(
+            signer,
+            signer,
+            fetch_slot(&self.rpc).await?,
+            &lookup_table_addresses(&instruction),
+        );
+        for instructions in &batches {
+            self.send(instructions).await?;
+        }
+        let table = loop {
+            let (state, slot) = fetch_lookup_table(&self.rpc, &table).await?;
+            if state.is_active(slot) {
+                break state.account;
+            }
+            self.rpc.sleep(self.poll_interval).await;
+        };
+
+        let key = table.key;
+        let format = self.format.with_table(table);
+        let signature = self
+            .send_with_format(std::slice::from_ref(&instruction), &format)
+            .await?;
+        Ok((key, signature))
+    }
+
+    /// Closes every transaction and proposal the program allows to be
+    /// closed: those that were executed, rejected or cancelled, and stale
+    /// ones that were never approved. Batches are skipped. A failed close
+    /// does not stop the others; the result lists both.
+    pub async fn reclaim_all_rent(&self) -> Result<Reclaimed, ClientError> {
+        let settings = self.fetch_settings().await?;
+        let mut reclaimed = Reclaimed::default();
+        for page in index_pages(1, settings.transaction_index) {
+            let accounts =
+                fetch_transaction_page(&self.rpc, &self.smart_account.settings(), page).await?;
+            for (index, transaction, proposal) in accounts {
+                let Some(instruction) =
+                    self.close_instruction(&settings, index, transaction, proposal)?
+                else {
+                    continue;
+                };
+                match self.send(&[instruction]).await {
+                    Ok(signature) => reclaimed.closed.push((index, signature)),
+                    Err(e) => reclaimed.failed.push((index, e)),
+                }
+            }
+        }
+        Ok(reclaimed)
+    }
+
+    /// Closes the transaction at `transaction_index` and its proposal if
+    /// [`Self::reclaim_all_rent`] would. Returns `None` if they are already
+    /// closed, cannot be closed yet, or belong to a batch.
+    pub async fn reclaim_rent(
+        &self,
+        transaction_index: u64,
+    ) -> Result<Option<Signature>, ClientError> {
+        let settings = self.fetch_settings().await?;
+        let page = transaction_index..=transaction_index;
+        let Some((index, transaction, proposal)) =
+            fetch_transaction_page(&self.rpc, &self.smart_account.settings(), page)
+                .await?
+                .pop()
+        else {
+            return Ok(None);
+        };
+        match self.close_instruction(&settings, index, transaction, proposal)? {
+            Some(instruction) => Ok(Some(self.send(&[instruction]).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The instruction closing the transaction at `index` and its
+    /// proposal, if the program allows it.
+    fn close_instruction(
+        &self,
+        settings: &Settings,
+        index: u64,
+        transaction: Option<Account>,
+        proposal: Option<Account>,
+    ) -> Result<Option<Instruction>, ClientError> {
+        let Some(transaction) = transaction else {
+            return Ok(None);
+        };
+        let proposal = proposal
+            .map(|account| VersionedProposal::from_bytes(&account.data))
+            .transpose()?
+            .map(VersionedProposal::into_current);
+        let transaction = TransactionAccount::from_bytes(&transaction.data)?;
+        if !is_closable(
+            TransactionKind::from(&transaction),
+            proposal.as_ref().map(|proposal| &proposal.status),
+            index <= settings.stale_transaction_index,
+        ) {
+            return Ok(None);
+        }
+
+        Ok(match transaction {
+            TransactionAccount::Transaction(transaction) => {
+                Some(self.smart_account.close_transaction(
+                    index,
+                    proposal.map_or(transaction.rent_collector, |p| p.rent_collector),
+                    transaction.rent_collector,
+                ))
+            }
+            TransactionAccount::SettingsTransaction(transaction) => {
+                Some(self.smart_account.close_settings_transaction(
+                    index,
+                    proposal.map_or(transaction.rent_collector, |p| p.rent_collector),
+                    transaction.rent_collector,
+                ))
+            }
+            TransactionAccount::Batch(_) => None,
+        })
+    }
+
+    /// Polls until this signer can execute the proposal by
+    /// [`ProposalLifecycle`]'s rules: it is approved and past its time
+    /// lock, the signer may execute, and it is not a stale settings
+    /// transaction.
+    async fn wait_until_executable(&self, transaction_index: u64) -> Result<(), ClientError> {
+        let settings = self.smart_account.settings();
+        let transaction = self.smart_account.transaction_pda(transaction_index).0;
+        let addresses = [
+            settings,
+            transaction,
+            self.smart_account.proposal_pda(transaction_index).0,
+            sysvar::clock::ID,
+        ];
+        let signer = self.signer.pubkey();
+        let mut waited = Duration::ZERO;
+        loop {
+            let accounts = fetch_multiple(&self.rpc, &addresses).await?;
+            let settings_data =
+                VersionedSettings::from_bytes(&expect(&accounts[0], &settings)?.data)?
+                    .into_current();
+            let kind =
+                match TransactionAccount::from_bytes(&expect(&accounts[1], &transaction)?.data)? {
+                    TransactionAccount::Batch(_) => {
+                        return Err(ClientError::Batch(transaction_index))
+                    }
+                    account => TransactionKind::from(&account),
+                };
+            let proposal = accounts[2]
+                .as_ref()
+                .ok_or(ClientError::MissingProposal(transaction_index))?;
+            let proposal = VersionedProposal::from_bytes(&proposal.data)?.into_current();
+            let now = unix_timestamp(&expect(&accounts[3], &sysvar::clock::ID)?.data)?;
+
+            let lifecycle = ProposalLifecycle::new(&proposal, &settings_data, kind);
+            let wait = match lifecycle.why_not(ProposalAction::Execute { signer, now }) {
+                None => return Ok(()),
+                Some(AstrolabeSmartAccountError::TimeLockNotReleased) => {
+                    let ready_at = lifecycle.executable_at().unwrap_or(now);
+                    Duration::from_secs(ready_at.saturating_sub(now) as u64).max(self.poll_interval)
+                }
+                Some(AstrolabeSmartAccountError::InvalidProposalStatus) => match proposal.status {
+                    // A stale proposal cannot be voted on any more.
+                    ProposalStatus::Draft { .. } | ProposalStatus::Active { .. }
+                        if lifecycle.is_stale() =>
+                    {
+                        return Err(ClientError::Stale(transaction_index))
+                    }
+                    ProposalStatus::Draft { .. }
+                    | ProposalStatus::Active { .. }
+                    | ProposalStatus::Executing => self.poll_interval,
+                    status => return Err(ClientError::NotExecutable(transaction_index, status)),
+                },
+                Some(error) => return Err(ClientError::CannotExecute(transaction_index, error)),
+            };
+            if self.wait_timeout.is_some_and(|timeout| waited >= timeout) {
+                return Err(ClientError::Timeout(transaction_index));
+            }
+            waited += wait;
+            self.rpc.sleep(wait).await;
+        }
+    }
+
+    async fn execute_instruction(
+        &self,
+        transaction_index: u64,
+    ) -> Result<Instruction, ClientError> {
+        let address = self.smart_account.transaction_pda(transaction_index).0;
+        let account = self.fetch_transaction_account(transaction_index).await?;
+        let signer = self.signer.pubkey();
+        Ok(match expect(&account, &address)?.clone() {
+            TransactionAccount::Transaction(transaction) => {
+                let tables = fetch_address_lookup_tables(&self.rpc, &transaction.message).await?;
+                let accounts = transaction.message.execution_accounts(&tables)?;
+                self.smart_account
+                    .execute_transaction(transaction_index, signer, &accounts)
+            }
+            TransactionAccount::SettingsTransaction(transaction) => {
+                self.smart_account.execute_settings_transaction(
+                    transaction_index,
+                    signer,
+                    Some(signer),
+                    &transaction.actions,
+                )
+            }
+            TransactionAccount::Batch(_) => return Err(ClientError::Batch(transaction_index)),
+        })
+    }
+
+    /// Signs and sends `instructions`, retrying with a fresh blockhash when
+    /// the send fails in transit or expires.
+    pub async fn send(&self, instructions: &[Instruction]) -> Result<Signature, ClientError> {
+        self.send_with_format(instructions, &self.format).await
+    }
+
+    async fn send_with_format(
+        &self,
+        instructions: &[Instruction],
+        format: &TransactionFormat,
+    ) -> Result<Signature, ClientError> {
+        let mut attempt = 0;
+        loop {
+            match self.send_once(instructions, format).await {
+                Err(e) if e.is_transient() && attempt < self.max_retries => {
+                    attempt += 1;
+                    self.rpc.sleep(self.poll_interval).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_once(
+        &self,
+        instructions: &[Instruction],
+        format: &TransactionFormat,
+    ) -> Result<Signature, ClientError> {
+        let transaction = self.build_transaction(instructions, format).await?;
+        Ok(self.rpc.send_and_confirm_transaction(&transaction).await?)
+    }
+
+    /// The transaction [`Self::send`] would send for `instructions`.
+    async fn build_transaction(
+        &self,
+        instructions: &[Instruction],
+        format: &TransactionFormat,
+    ) -> Result<VersionedTransaction, ClientError> {
+        let instructions = match &self.priority_fee {
+            Some(config) => with_priority_fee(&self.rpc, instructions, config)
+                .await
+                .map_err(SendError::Network)?,
+            None => instructions.to_vec(),
+        };
+        let blockhash = self
+            .rpc
+            .get_latest_blockhash()
+            .await
+            .map_err(SendError::Network)?;
+        Ok(format.sign(
+            &self.signer.pubkey(),
+            &instructions,
+            &[&self.signer],
+            blockhash,
+        )?)
+    }
+
+    async fn fetch_transaction_account(
+        &self,
+        transaction_index: u64,
+    ) -> Result<Option<TransactionAccount>, ClientError> {
+        let address = self.smart_account.transaction_pda(transaction_index).0;
+        Ok(fetch_multiple(&self.rpc, &[address])
+            .await?
+            .pop()
+            .flatten()
+            .map(|account| TransactionAccount::from_bytes(&account.data))
+            .transpose()?)
+    }
+
+    async fn fetch_settings(&self) -> Result<Settings, ClientError> {
+        let settings = self.smart_account.settings();
+        let account = fetch_multiple(&self.rpc, &[settings])
+            .await?
+            .pop()
+            .flatten();
+        Ok(VersionedSettings::from_bytes(&expect(&account, &settings)?.data)?.into_current())
+    }
+}
+
+impl<R, S> SmartAccountClient<R, S>
+where
+    R: AccountFetcher + TransactionSender + TransactionSimulator,
+    S: Signer,
+{
+    /// Builds the execution of the transaction at `transaction_index` the
+    /// way [`Self::execute_when_ready`] would, without waiting, and checks
+    /// it with [`preflight`]. Nothing is sent.
+    pub async fn preflight_execute(
+        &self,
+        transaction_index: u64,
+    ) -> Result<Preflight, ClientError> {
+        let instruction = self.execute_instruction(transaction_index).await?;
+        let transaction = self
+            .build_transaction(std::slice::from_ref(&instruction), &self.format)
+            .await?;
+        Ok(preflight(&self.rpc, &transaction).await?)
+    }
+}
+
+fn expect<'a, T>(account: &'a Option<T>, address: &Pubkey) -> Result<&'a T, io::Error> {
+    account.as_ref().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Account not found: {}", address),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use solana_keypair::Keypair;
+    use solana_transaction_error::TransactionError;
+
+    use super::*;
+    use crate::accounts::{Proposal, SettingsTransaction, Transaction};
+    use crate::decode::{
+        PROPOSAL_DISCRIMINATOR, SETTINGS_DISCRIMINATOR, SETTINGS_TRANSACTION_DISCRIMINATOR,
+        TRANSACTION_DISCRIMINATOR,
+    };
+    use crate::mock::{MockOutcome, MockRpc};
+    use crate::rpc::INDEXES_PER_PAGE;
+    use crate::types::{Permissions, SmartAccountSigner};
+
+    const SETTINGS: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    fn client(
+        transaction_index: u64,
+        stale_transaction_index: u64,
+    ) -> SmartAccountClient<MockRpc, Keypair> {
+        client_with_permissions(
+            transaction_index,
+            stale_transaction_index,
+            Permissions::INITIATE | Permissions::VOTE | Permissions::EXECUTE,
+        )
+    }
+
+    fn client_with_permissions(
+        transaction_index: u64,
+        stale_transaction_index: u64,
+        mask: u8,
+    ) -> SmartAccountClient<MockRpc, Keypair> {
+        let signer = Keypair::new();
+        let rpc = MockRpc::new();
+        rpc.set_clock(1, 1_000);
+        rpc.set_program_account_with_space(
+            SETTINGS,
+            &Settings {
+                discriminator: SETTINGS_DISCRIMINATOR,
+                seed: 0,
+                settings_authority: Pubkey::default(),
+                threshold: 1,
+                time_lock: 0,
+                transaction_index,
+                stale_transaction_index,
+                archival_authority: None,
+                archivable_after: 0,
+                bump: 255,
+                signers: vec![SmartAccountSigner {
+                    key: signer.pubkey(),
+                    permissions: Permissions { mask },
+                }],
+                restricted_signers: Vec::new(),
+                account_utilization: 0,
+                reserved1: 0,
+                reserved2: 0,
+            },
+            Settings::size(1, 0),
+        );
+        SmartAccountClient::new(rpc, SETTINGS, signer).with_max_retries(2)
+    }
+
+    fn set_proposal(
+        client: &SmartAccountClient<MockRpc, Keypair>,
+        index: u64,
+        status: ProposalStatus,
+    ) {
+        client.rpc().set_program_account_with_space(
+            client.smart_account().proposal_pda(index).0,
+            &Proposal {
+                discriminator: PROPOSAL_DISCRIMINATOR,
+                settings: SETTINGS,
+                transaction_index: index,
+                rent_collector: Pubkey::default(),
+                status,
+                bump: 255,
+                approved: Vec::new(),
+                rejected: Vec::new(),
+                cancelled: Vec::new(),
+            },
+            Proposal::size(1),
+        );
+    }
+
+    fn set_transaction(client: &SmartAccountClient<MockRpc, Keypair>, index: u64) {
+        let signer = client.signer.pubkey();
+        client.rpc().set_program_account(
+            client.smart_account().transaction_pda(index).0,
+            &Transaction {
+                discriminator: TRANSACTION_DISCRIMINATOR,
+                settings: SETTINGS,
+                creator: signer,
+                rent_collector: signer,
+                index,
+                bump: 255,
+                account_index: 0,
+                account_bump: 255,
+                ephemeral_signer_bumps: Vec::new(),
+                message: message(),
+            },
+        );
+    }
+
+    fn set_settings_transaction(client: &SmartAccountClient<MockRpc, Keypair>, index: u64) {
+        let signer = client.signer.pubkey();
+        client.rpc().set_program_account(
+            client.smart_account().transaction_pda(index).0,
+            &SettingsTransaction {
+                discriminator: SETTINGS_TRANSACTION_DISCRIMINATOR,
+                settings: SETTINGS,
+                creator: signer,
+                rent_collector: signer,
+                index,
+                bump: 255,
+                actions: Vec::new(),
+            },
+        );
+    }
+
+    fn message() -> SmartAccountTransactionMessage {
+        SmartAccountTransactionMessage {
+            num_signers: 1,
+            num_writable_signers: 1,
+            num_writable_non_signers: 0,
+            account_keys: vec![Pubkey::new_unique()],
+            instructions: Vec::new(),
+            address_table_lookups: Vec::new(),
+        }
+    }
+
+    fn program_error() -> TransactionError {
+        TransactionError::InstructionError(
+            0,
+            solana_instruction::error::InstructionError::Custom(6000),
+        )
+    }
+
+    #[test]
+    fn send_does_not_retry_program_errors() {
+        let client = client(0, 0);
+        client
+            .rpc()
+            .push_outcome(MockOutcome::Failed(program_error()));
+        let result = block_on(client.send(&[]));
+        assert!(matches!(result, Err(ClientError::Send(ref e)) if e.is_program_error()));
+        assert_eq!(client.rpc().sent_transactions().len(), 1);
+    }
+
+    #[test]
+    fn send_retries_expired_transactions() {
+        let client = client(0, 0);
+        client.rpc().push_outcome(MockOutcome::Dropped);
+        client.rpc().push_outcome(MockOutcome::Dropped);
+        assert!(block_on(client.send(&[])).is_ok());
+        assert_eq!(client.rpc().sent_transactions().len(), 3);
+
+        let client = self::client(0, 0);
+        client.rpc().push_outcome(MockOutcome::Dropped);
+        client.rpc().push_outcome(MockOutcome::Dropped);
+        client.rpc().push_outcome(MockOutcome::Dropped);
+        let result = block_on(client.send(&[]));
+        assert!(matches!(
+            result,
+            Err(ClientError::Send(SendError::Expired(_)))
+        ));
+    }
+
+    #[test]
+    fn propose_and_approve_resumes_a_transaction_that_landed() {
+        let client = client(4, 0);
+        let signer = client.signer.pubkey();
+        client.rpc().push_outcome(MockOutcome::Dropped);
+        // The expired attempt landed anyway, as far as the accounts show.
+        client.rpc().set_program_account(
+            client.smart_account().transaction_pda(5).0,
+            &Transaction {
+                discriminator: TRANSACTION_DISCRIMINATOR,
+                settings: SETTINGS,
+                creator: signer,
+                rent_collector: signer,
+                index: 5,
+                bump: 255,
+                account_index: 0,
+                account_bump: 255,
+                ephemeral_signer_bumps: Vec::new(),
+                message: message(),
+            },
+        );
+        assert_eq!(
+            block_on(client.propose_and_approve(0, &message(), None)).unwrap(),
+            5
+        );
+        assert_eq!(client.rpc().sent_transactions().len(), 1);
+    }
+
+    #[test]
+    fn propose_and_approve_returns_program_errors() {
+        let client = client(4, 0);
+        client
+            .rpc()
+            .push_outcome(MockOutcome::Rejected(program_error()));
+        let result = block_on(client.propose_and_approve(0, &message(), None));
+        assert!(matches!(result, Err(ClientError::Send(ref e)) if e.is_program_error()));
+        assert_eq!(client.rpc().sent_transactions().len(), 1);
+    }
+
+    #[test]
+    fn wait_until_executable_stops_on_dead_proposals() {
+        let client = client(3, 2);
+        set_transaction(&client, 2);
+        set_transaction(&client, 3);
+        set_proposal(&client, 2, ProposalStatus::Active { timestamp: 0 });
+        assert!(matches!(
+            block_on(client.wait_until_executable(2)),
+            Err(ClientError::Stale(2))
+        ));
+
+        for status in [
+            ProposalStatus::Rejected { timestamp: 0 },
+            ProposalStatus::Cancelled { timestamp: 0 },
+        ] {
+            set_proposal(&client, 3, status.clone());
+            assert!(matches!(
+                block_on(client.wait_until_executable(3)),
+                Err(ClientError::NotExecutable(3, ref s)) if *s == status
+            ));
+        }
+
+        set_proposal(&client, 3, ProposalStatus::Approved { timestamp: 0 });
+        assert!(block_on(client.wait_until_executable(3)).is_ok());
+    }
+
+    #[test]
+    fn wait_until_executable_gives_up_after_the_timeout() {
+        let client = client(3, 0).with_wait_timeout(Duration::from_secs(10));
+        set_transaction(&client, 3);
+        set_proposal(&client, 3, ProposalStatus::Active { timestamp: 0 });
+        assert!(matches!(
+            block_on(client.wait_until_executable(3)),
+            Err(ClientError::Timeout(3))
+        ));
+    }
+
+    #[test]
+    fn wait_until_executable_stops_when_the_program_would_refuse() {
+        let client = client_with_permissions(3, 0, Permissions::INITIATE | Permissions::VOTE);
+        set_transaction(&client, 3);
+        set_proposal(&client, 3, ProposalStatus::Approved { timestamp: 0 });
+        assert!(matches!(
+            block_on(client.wait_until_executable(3)),
+            Err(ClientError::CannotExecute(
+                3,
+                AstrolabeSmartAccountError::Unauthorized
+            ))
+        ));
+
+        // Stale settings transactions cannot be executed even once approved,
+        // while stale vault transactions can.
+        let client = self::client(3, 3);
+        set_settings_transaction(&client, 3);
+        set_proposal(&client, 3, ProposalStatus::Approved { timestamp: 0 });
+        assert!(matches!(
+            block_on(client.wait_until_executable(3)),
+            Err(ClientError::CannotExecute(
+                3,
+                AstrolabeSmartAccountError::StaleProposal
+            ))
+        ));
+        set_transaction(&client, 3);
+        assert!(block_on(client.wait_until_executable(3)).is_ok());
+        assert!(client.rpc().sent_transactions().is_empty());
+    }
+
+    #[test]
+    fn reclaim_all_rent_reports_failed_closes_and_keeps_going() {
+        let client = client(3, 0);
+        for index in 1..=3 {
+            set_transaction(&client, index);
+            set_proposal(&client, index, ProposalStatus::Executed { timestamp: 0 });
+        }
+        client
+            .rpc()
+            .push_outcome(MockOutcome::Rejected(program_error()));
+        let reclaimed = block_on(client.reclaim_all_rent()).unwrap();
+        assert_eq!(
+            reclaimed
+                .closed
+                .iter()
+                .map(|(index, _)| *index)
+                .collect::<Vec<_>>(),
+            [2, 3]
+        );
+        assert_eq!(reclaimed.failed.len(), 1);
+        assert!(matches!(
+            reclaimed.failed[0],
+            (1, ClientError::Send(ref e)) if e.is_program_error()
+        ));
+    }
+
+    #[test]
+    fn reclaim_all_rent_pages_through_a_long_history() {
+        let last = 2 * INDEXES_PER_PAGE + 1;
+        let client = client(last, 0);
+        let indexes = [1, INDEXES_PER_PAGE, INDEXES_PER_PAGE + 1, last];
+        for index in indexes {
+            set_transaction(&client, index);
+            set_proposal(&client, index, ProposalStatus::Executed { timestamp: 0 });
+        }
+        let reclaimed = block_on(client.reclaim_all_rent()).unwrap();
+        assert_eq!(
+            reclaimed
+                .closed
+                .iter()
+                .map(|(index, _)| *index)
+                .collect::<Vec<_>>(),
+            indexes
+        );
+        assert!(reclaimed.failed.is_empty());
+    }
+
+    #[test]
+    fn reclaim_rent_closes_one_transaction_if_it_can_be_closed() {
+        let client = client(2, 0);
+        for index in 1..=2 {
+            set_settings_transaction(&client, index);
+        }
+        set_proposal(&client, 1, ProposalStatus::Rejected { timestamp: 0 });
+        set_proposal(&client, 2, ProposalStatus::Approved { timestamp: 0 });
+
+        assert!(block_on(client.reclaim_rent(2)).unwrap().is_none());
+        assert!(block_on(client.reclaim_rent(3)).unwrap().is_none());
+        assert!(client.rpc().sent_transactions().is_empty());
+        assert!(block_on(client.reclaim_rent(1)).unwrap().is_some());
+        assert_eq!(client.rpc().sent_transactions().len(), 1);
+    }
+}
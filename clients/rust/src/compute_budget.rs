@@ -0,0 +1,28 @@
+//! Compute budget program instructions.
+//!
+//! Encoded by hand; the layouts are a one-byte tag followed by the
+//! little-endian argument.
+
+use solana_instruction::Instruction;
+use solana_sdk_ids::compute_budget;
+
+/// Most compute units one transaction may request.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Units each instruction gets when a transaction sets no limit.
+pub const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+
+pub(crate) const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+pub(crate) const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    let mut data = vec![SET_COMPUTE_UNIT_LIMIT];
+    data.extend_from_slice(&units.to_le_bytes());
+    Instruction::new_with_bytes(compute_budget::ID, &data, Vec::new())
+}
+
+/// Sets the priority fee, in micro-lamports per requested compute unit.
+pub fn set_compute_unit_price(micro_lamports: u64) -> Instruction {
+    let mut data = vec![SET_COMPUTE_UNIT_PRICE];
+    data.extend_from_slice(&micro_lamports.to_le_bytes());
+    Instruction::new_with_bytes(compute_budget::ID, &data, Vec::new())
+}
@@ -0,0 +1,353 @@
+//! A builder for the actions of a settings transaction.
+//!
+//! [`ConfigBuilder`] collects actions in the order they will execute and
+//! checks them before they are proposed: [`ConfigBuilder::build`] checks
+//! each action on its own, and [`ConfigBuilder::build_for`] also applies
+//! them to the current settings and checks the result against
+//! [`Settings::invariant`], the rules the program enforces after every
+//! settings change. Errors are the ones the program would fail with.
+
+use solana_pubkey::Pubkey;
+
+use crate::accounts::Settings;
+use crate::errors::AstrolabeSmartAccountError;
+use crate::settings_diff::{NewSpendingLimit, SettingsDiff};
+use crate::types::{Permissions, SettingsAction, SmartAccountSigner};
+
+/// Longest time lock the program accepts: 90 days, in seconds.
+pub const MAX_TIME_LOCK: u32 = 90 * 24 * 60 * 60;
+
+const ALL_PERMISSIONS: u8 = Permissions::INITIATE | Permissions::VOTE | Permissions::EXECUTE;
+
+impl Settings {
+    /// Checks the rules the program enforces whenever settings change.
+    pub fn invariant(&self) -> Result<(), AstrolabeSmartAccountError> {
+        use AstrolabeSmartAccountError::*;
+
+        if self.signers.len() > u16::MAX as usize {
+            return Err(TooManySigners);
+        }
+        let mut keys: Vec<Pubkey> = self
+            .signers
+            .iter()
+            .map(|signer| signer.key)
+            .chain(self.restricted_signers.iter().map(|signer| signer.key))
+            .collect();
+        keys.sort_unstable();
+        if keys.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(DuplicateSigner);
+        }
+        if self
+            .signers
+            .iter()
+            .any(|signer| signer.permissions.mask & !ALL_PERMISSIONS != 0)
+        {
+            return Err(UnknownPermission);
+        }
+        let count = |permission| {
+            self.signers
+                .iter()
+                .filter(|signer| signer.permissions.has(permission))
+                .count()
+        };
+        if count(Permissions::INITIATE) == 0 {
+            return Err(NoProposers);
+        }
+        if count(Permissions::EXECUTE) == 0 {
+            return Err(NoExecutors);
+        }
+        let voters = count(Permissions::VOTE);
+        if voters == 0 {
+            return Err(NoVoters);
+        }
+        if self.threshold == 0 || self.threshold as usize > voters {
+            return Err(InvalidThreshold);
+        }
+        if self.stale_transaction_index > self.transaction_index {
+            return Err(InvalidStaleTransactionIndex);
+        }
+        if self.time_lock > MAX_TIME_LOCK {
+            return Err(TimeLockExceedsMaxAllowed);
+        }
+        Ok(())
+    }
+}
+
+/// See the [module documentation](self).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ConfigBuilder {
+    actions: Vec<SettingsAction>,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `permissions` is a mask of [`Permissions::INITIATE`],
+    /// [`Permissions::VOTE`] and [`Permissions::EXECUTE`].
+    pub fn add_signer(mut self, key: Pubkey, permissions: u8) -> Self {
+        self.actions.push(SettingsAction::AddSigner {
+            new_signer: SmartAccountSigner {
+                key,
+                permissions: Permissions { mask: permissions },
+            },
+        });
+        self
+    }
+
+    pub fn remove_signer(mut self, key: Pubkey) -> Self {
+        self.actions
+            .push(SettingsAction::RemoveSigner { old_signer: key });
+        self
+    }
+
+    pub fn set_threshold(mut self, threshold: u16) -> Self {
+        self.actions.push(SettingsAction::ChangeThreshold {
+            new_threshold: threshold,
+        });
+        self
+    }
+
+    /// Seconds between approval and execution.
+    pub fn set_time_lock(mut self, time_lock: u32) -> Self {
+        self.actions.push(SettingsAction::SetTimeLock {
+            new_time_lock: time_lock,
+        });
+        self
+    }
+
+    pub fn add_spending_limit(mut self, limit: NewSpendingLimit) -> Self {
+        self.actions.push(SettingsAction::AddSpendingLimit {
+            seed: limit.seed,
+            account_index: limit.account_index,
+            mint: limit.mint,
+            amount: limit.amount,
+            period: limit.period,
+            signers: limit.signers,
+            destinations: limit.destinations,
+            expiration: limit.expiration,
+        });
+        self
+    }
+
+    /// `spending_limit` is the address of the spending limit account.
+    pub fn remove_spending_limit(mut self, spending_limit: Pubkey) -> Self {
+        self.actions
+            .push(SettingsAction::RemoveSpendingLimit { spending_limit });
+        self
+    }
+
+    pub fn set_archival_authority(mut self, archival_authority: Option<Pubkey>) -> Self {
+        self.actions.push(SettingsAction::SetArchivalAuthority {
+            new_archival_authority: archival_authority,
+        });
+        self
+    }
+
+    /// The actions, if each is valid on its own.
+    pub fn build(self) -> Result<Vec<SettingsAction>, AstrolabeSmartAccountError> {
+        use AstrolabeSmartAccountError::*;
+
+        if self.actions.is_empty() {
+            return Err(NoActions);
+        }
+        for action in &self.actions {
+            match action {
+                SettingsAction::AddSigner { new_signer }
+                    if new_signer.permissions.mask & !ALL_PERMISSIONS != 0 =>
+                {
+                    return Err(UnknownPermission)
+                }
+                SettingsAction::ChangeThreshold { new_threshold: 0 } => {
+                    return Err(InvalidThreshold)
+                }
+                SettingsAction::SetTimeLock { new_time_lock } if *new_time_lock > MAX_TIME_LOCK => {
+                    return Err(TimeLockExceedsMaxAllowed)
+                }
+                SettingsAction::AddSpendingLimit {
+                    amount, signers, ..
+                } => {
+                    if *amount == 0 {
+                        return Err(SpendingLimitInvalidAmount);
+                    }
+                    if signers.is_empty() {
+                        return Err(EmptySigners);
+                    }
+                    let mut signers = signers.clone();
+                    signers.sort_unstable();
+                    if signers.windows(2).any(|pair| pair[0] == pair[1]) {
+                        return Err(DuplicateSigner);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(self.actions)
+    }
+
+    /// The actions, if they are valid and leave `settings` valid. Actions
+    /// are checked in order, the way the program executes them.
+    pub fn build_for(
+        self,
+        settings: &Settings,
+    ) -> Result<Vec<SettingsAction>, AstrolabeSmartAccountError> {
+        use AstrolabeSmartAccountError::*;
+
+        if settings.settings_authority != Pubkey::default() {
+            return Err(NotSupportedForControlled);
+        }
+        let actions = self.build()?;
+        let mut signers: Vec<Pubkey> = settings.signers.iter().map(|signer| signer.key).collect();
+        for action in &actions {
+            match action {
+                SettingsAction::AddSigner { new_signer } => {
+                    if signers.contains(&new_signer.key) {
+                        return Err(DuplicateSigner);
+                    }
+                    signers.push(new_signer.key);
+                }
+                SettingsAction::RemoveSigner { old_signer } => {
+                    if signers.len() == 1 {
+                        return Err(RemoveLastSigner);
+                    }
+                    let index = signers
+                        .iter()
+                        .position(|key| key == old_signer)
+                        .ok_or(NotASigner)?;
+                    signers.remove(index);
+                }
+                _ => {}
+            }
+        }
+        SettingsDiff::new(settings, &actions).after.invariant()?;
+        Ok(actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use AstrolabeSmartAccountError::*;
+
+    const ALICE: Pubkey = Pubkey::new_from_array([1; 32]);
+    const BOB: Pubkey = Pubkey::new_from_array([2; 32]);
+
+    /// Autonomous settings with Alice as the only signer.
+    fn settings() -> Settings {
+        Settings {
+            discriminator: [0; 8],
+            seed: 0,
+            settings_authority: Pubkey::default(),
+            threshold: 1,
+            time_lock: 0,
+            transaction_index: 0,
+            stale_transaction_index: 0,
+            archival_authority: None,
+            archivable_after: 0,
+            bump: 0,
+            signers: vec![SmartAccountSigner {
+                key: ALICE,
+                permissions: Permissions {
+                    mask: ALL_PERMISSIONS,
+                },
+            }],
+            restricted_signers: Vec::new(),
+            account_utilization: 0,
+            reserved1: 0,
+            reserved2: 0,
+        }
+    }
+
+    #[test]
+    fn build_for_accepts_a_valid_change() {
+        let actions = ConfigBuilder::new()
+            .add_signer(BOB, ALL_PERMISSIONS)
+            .set_threshold(2)
+            .build_for(&settings())
+            .unwrap();
+        assert_eq!(actions.len(), 2);
+    }
+
+    #[test]
+    fn build_for_rejects_duplicate_signers() {
+        assert_eq!(
+            ConfigBuilder::new()
+                .add_signer(ALICE, Permissions::VOTE)
+                .build_for(&settings()),
+            Err(DuplicateSigner)
+        );
+        assert_eq!(
+            ConfigBuilder::new()
+                .add_signer(BOB, Permissions::VOTE)
+                .add_signer(BOB, Permissions::VOTE)
+                .build_for(&settings()),
+            Err(DuplicateSigner)
+        );
+    }
+
+    #[test]
+    fn build_for_rejects_removing_the_last_signer() {
+        assert_eq!(
+            ConfigBuilder::new()
+                .remove_signer(ALICE)
+                .build_for(&settings()),
+            Err(RemoveLastSigner)
+        );
+        // Checked in order: adding first makes the removal fine.
+        assert!(ConfigBuilder::new()
+            .add_signer(BOB, ALL_PERMISSIONS)
+            .remove_signer(ALICE)
+            .build_for(&settings())
+            .is_ok());
+        assert_eq!(
+            ConfigBuilder::new()
+                .add_signer(BOB, ALL_PERMISSIONS)
+                .remove_signer(Pubkey::new_from_array([3; 32]))
+                .build_for(&settings()),
+            Err(NotASigner)
+        );
+    }
+
+    #[test]
+    fn build_for_rejects_a_threshold_above_the_voters() {
+        // Bob cannot vote, so there is still one voter.
+        assert_eq!(
+            ConfigBuilder::new()
+                .add_signer(BOB, Permissions::EXECUTE)
+                .set_threshold(2)
+                .build_for(&settings()),
+            Err(InvalidThreshold)
+        );
+        assert_eq!(
+            ConfigBuilder::new().set_threshold(0).build_for(&settings()),
+            Err(InvalidThreshold)
+        );
+    }
+
+    #[test]
+    fn build_for_rejects_a_time_lock_above_the_maximum() {
+        assert!(ConfigBuilder::new()
+            .set_time_lock(MAX_TIME_LOCK)
+            .build_for(&settings())
+            .is_ok());
+        assert_eq!(
+            ConfigBuilder::new()
+                .set_time_lock(MAX_TIME_LOCK + 1)
+                .build_for(&settings()),
+            Err(TimeLockExceedsMaxAllowed)
+        );
+    }
+
+    #[test]
+    fn build_for_rejects_controlled_settings() {
+        let settings = Settings {
+            settings_authority: BOB,
+            ..settings()
+        };
+        assert_eq!(
+            ConfigBuilder::new().set_threshold(1).build_for(&settings),
+            Err(NotSupportedForControlled)
+        );
+    }
+}
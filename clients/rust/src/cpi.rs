@@ -0,0 +1,336 @@
+//! Anchor CPI wrappers for creating proposals and voting on them from
+//! another program.
+//!
+//! Each wrapper takes a [`CpiContext`] over one of the accounts structs
+//! below, the way Anchor's generated `cpi` modules do, and invokes the
+//! program with the context's signer seeds, so a PDA owned by the calling
+//! program can act as a signer of the smart account. Remaining accounts are
+//! passed through with their own signer and writable flags.
+//!
+//! ```ignore
+//! let ctx = CpiContext::new_with_signer(
+//!     ctx.accounts.smart_account_program.to_account_info(),
+//!     astrolabe_client::cpi::VoteOnProposal {
+//!         settings: ctx.accounts.settings.to_account_info(),
+//!         signer: ctx.accounts.voter.to_account_info(),
+//!         proposal: ctx.accounts.proposal.to_account_info(),
+//!         system_program: None,
+//!     },
+//!     signer_seeds,
+//! );
+//! astrolabe_client::cpi::approve_proposal(ctx, VoteOnProposalArgs { memo: None })?;
+//! ```
+
+use anchor_lang::context::CpiContext;
+use anchor_lang::{Result, ToAccountInfos, ToAccountMetas};
+use solana_account_info::AccountInfo;
+use solana_instruction::AccountMeta;
+
+use crate::instructions::{
+    ActivateProposalCpi, ActivateProposalCpiAccounts, ApproveProposalCpi,
+    ApproveProposalCpiAccounts, ApproveProposalInstructionArgs, CancelProposalCpi,
+    CancelProposalCpiAccounts, CancelProposalInstructionArgs, CreateProposalCpi,
+    CreateProposalCpiAccounts, CreateProposalInstructionArgs, RejectProposalCpi,
+    RejectProposalCpiAccounts, RejectProposalInstructionArgs,
+};
+use crate::types::VoteOnProposalArgs;
+
+/// Accounts of `create_proposal`.
+#[derive(Clone)]
+pub struct CreateProposal<'info> {
+    pub settings: AccountInfo<'info>,
+    /// Writable.
+    pub proposal: AccountInfo<'info>,
+    /// Signer with the initiate permission.
+    pub creator: AccountInfo<'info>,
+    /// Writable signer paying the proposal's rent.
+    pub rent_payer: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+}
+
+impl ToAccountMetas for CreateProposal<'_> {
+    fn to_account_metas(&self, _is_signer: Option<bool>) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(*self.settings.key, false),
+            AccountMeta::new(*self.proposal.key, false),
+            AccountMeta::new_readonly(*self.creator.key, true),
+            AccountMeta::new(*self.rent_payer.key, true),
+            AccountMeta::new_readonly(*self.system_program.key, false),
+        ]
+    }
+}
+
+impl<'info> ToAccountInfos<'info> for CreateProposal<'info> {
+    fn to_account_infos(&self) -> Vec<AccountInfo<'info>> {
+        vec![
+            self.settings.clone(),
+            self.proposal.clone(),
+            self.creator.clone(),
+            self.rent_payer.clone(),
+            self.system_program.clone(),
+        ]
+    }
+}
+
+/// Accounts of `activate_proposal`.
+#[derive(Clone)]
+pub struct ActivateProposal<'info> {
+    pub settings: AccountInfo<'info>,
+    /// Writable signer with the initiate permission.
+    pub signer: AccountInfo<'info>,
+    /// Writable.
+    pub proposal: AccountInfo<'info>,
+}
+
+impl ToAccountMetas for ActivateProposal<'_> {
+    fn to_account_metas(&self, _is_signer: Option<bool>) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(*self.settings.key, false),
+            AccountMeta::new(*self.signer.key, true),
+            AccountMeta::new(*self.proposal.key, false),
+        ]
+    }
+}
+
+impl<'info> ToAccountInfos<'info> for ActivateProposal<'info> {
+    fn to_account_infos(&self) -> Vec<AccountInfo<'info>> {
+        vec![
+            self.settings.clone(),
+            self.signer.clone(),
+            self.proposal.clone(),
+        ]
+    }
+}
+
+/// Accounts of `approve_proposal`, `reject_proposal` and `cancel_proposal`.
+#[derive(Clone)]
+pub struct VoteOnProposal<'info> {
+    pub settings: AccountInfo<'info>,
+    /// Writable signer with the vote permission.
+    pub signer: AccountInfo<'info>,
+    /// Writable.
+    pub proposal: AccountInfo<'info>,
+    /// Only needed when the vote makes the proposal grow.
+    pub system_program: Option<AccountInfo<'info>>,
+}
+
+impl ToAccountMetas for VoteOnProposal<'_> {
+    fn to_account_metas(&self, _is_signer: Option<bool>) -> Vec<AccountMeta> {
+        // An absent optional account is passed as the program ID.
+        let system_program = self
+            .system_program
+            .as_ref()
+            .map_or(crate::ID, |system_program| *system_program.key);
+        vec![
+            AccountMeta::new_readonly(*self.settings.key, false),
+            AccountMeta::new(*self.signer.key, true),
+            AccountMeta::new(*self.proposal.key, false),
+            AccountMeta::new_readonly(system_program, false),
+        ]
+    }
+}
+
+impl<'info> ToAccountInfos<'info> for VoteOnProposal<'info> {
+    fn to_account_infos(&self) -> Vec<AccountInfo<'info>> {
+        [&self.settings, &self.signer, &self.proposal]
+            .into_iter()
+            .chain(&self.system_program)
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn create_proposal<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, CreateProposal<'info>>,
+    transaction_index: u64,
+    draft: bool,
+) -> Result<()> {
+    let accounts = &ctx.accounts;
+    CreateProposalCpi::new(
+        &ctx.program,
+        CreateProposalCpiAccounts {
+            settings: &accounts.settings,
+            proposal: &accounts.proposal,
+            creator: &accounts.creator,
+            rent_payer: &accounts.rent_payer,
+            system_program: &accounts.system_program,
+        },
+        CreateProposalInstructionArgs {
+            transaction_index,
+            draft,
+        },
+    )
+    .invoke_signed_with_remaining_accounts(ctx.signer_seeds, &remaining_accounts(&ctx))
+    .map_err(Into::into)
+}
+
+pub fn activate_proposal<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, ActivateProposal<'info>>,
+) -> Result<()> {
+    let accounts = &ctx.accounts;
+    ActivateProposalCpi::new(
+        &ctx.program,
+        ActivateProposalCpiAccounts {
+            settings: &accounts.settings,
+            signer: &accounts.signer,
+            proposal: &accounts.proposal,
+        },
+    )
+    .invoke_signed_with_remaining_accounts(ctx.signer_seeds, &remaining_accounts(&ctx))
+    .map_err(Into::into)
+}
+
+pub fn approve_proposal<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, VoteOnProposal<'info>>,
+    args: VoteOnProposalArgs,
+) -> Result<()> {
+    let accounts = &ctx.accounts;
+    ApproveProposalCpi::new(
+        &ctx.program,
+        ApproveProposalCpiAccounts {
+            settings: &accounts.settings,
+            signer: &accounts.signer,
+            proposal: &accounts.proposal,
+            system_program: accounts.system_program.as_ref(),
+        },
+        ApproveProposalInstructionArgs { args },
+    )
+    .invoke_signed_with_remaining_accounts(ctx.signer_seeds, &remaining_accounts(&ctx))
+    .map_err(Into::into)
+}
+
+pub fn reject_proposal<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, VoteOnProposal<'info>>,
+    args: VoteOnProposalArgs,
+) -> Result<()> {
+    let accounts = &ctx.accounts;
+    RejectProposalCpi::new(
+        &ctx.program,
+        RejectProposalCpiAccounts {
+            settings: &accounts.settings,
+            signer: &accounts.signer,
+            proposal: &accounts.proposal,
+            system_program: accounts.system_program.as_ref(),
+        },
+        RejectProposalInstructionArgs { args },
+    )
+    .invoke_signed_with_remaining_accounts(ctx.signer_seeds, &remaining_accounts(&ctx))
+    .map_err(Into::into)
+}
+
+/// Cancels an approved proposal; the signer needs the vote permission.
+pub fn cancel_proposal<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, VoteOnProposal<'info>>,
+    args: VoteOnProposalArgs,
+) -> Result<()> {
+    let accounts = &ctx.accounts;
+    CancelProposalCpi::new(
+        &ctx.program,
+        CancelProposalCpiAccounts {
+            settings: &accounts.settings,
+            signer: &accounts.signer,
+            proposal: &accounts.proposal,
+            system_program: accounts.system_program.as_ref(),
+        },
+        CancelProposalInstructionArgs { args },
+    )
+    .invoke_signed_with_remaining_accounts(ctx.signer_seeds, &remaining_accounts(&ctx))
+    .map_err(Into::into)
+}
+
+fn remaining_accounts<'c, 'info, T: ToAccountMetas + ToAccountInfos<'info>>(
+    ctx: &'c CpiContext<'_, '_, '_, 'info, T>,
+) -> Vec<(&'c AccountInfo<'info>, bool, bool)> {
+    ctx.remaining_accounts
+        .iter()
+        .map(|account| (account, account.is_signer, account.is_writable))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_pubkey::Pubkey;
+
+    use super::*;
+    use crate::instructions;
+
+    /// Accounts with unique keys, each with its own lamports and data.
+    struct Accounts {
+        keys: Vec<Pubkey>,
+        lamports: Vec<u64>,
+        data: Vec<Vec<u8>>,
+    }
+
+    impl Accounts {
+        fn new(count: usize) -> Self {
+            Self {
+                keys: (0..count).map(|_| Pubkey::new_unique()).collect(),
+                lamports: vec![0; count],
+                data: vec![Vec::new(); count],
+            }
+        }
+
+        fn infos(&mut self) -> Vec<AccountInfo<'_>> {
+            self.keys
+                .iter()
+                .zip(&mut self.lamports)
+                .zip(&mut self.data)
+                .map(|((key, lamports), data)| {
+                    AccountInfo::new(key, false, false, lamports, data, &crate::ID, false, 0)
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn account_metas_match_the_generated_instructions() {
+        let mut accounts = Accounts::new(5);
+        let keys = accounts.keys.clone();
+        let infos = accounts.infos();
+
+        let create = CreateProposal {
+            settings: infos[0].clone(),
+            proposal: infos[1].clone(),
+            creator: infos[2].clone(),
+            rent_payer: infos[3].clone(),
+            system_program: infos[4].clone(),
+        };
+        let expected = instructions::CreateProposal {
+            settings: keys[0],
+            proposal: keys[1],
+            creator: keys[2],
+            rent_payer: keys[3],
+            system_program: keys[4],
+        }
+        .instruction(CreateProposalInstructionArgs {
+            transaction_index: 1,
+            draft: false,
+        });
+        assert_eq!(create.to_account_metas(None), expected.accounts);
+        assert_eq!(create.to_account_infos().len(), 5);
+
+        for system_program in [Some(infos[3].clone()), None] {
+            let vote = VoteOnProposal {
+                settings: infos[0].clone(),
+                signer: infos[1].clone(),
+                proposal: infos[2].clone(),
+                system_program: system_program.clone(),
+            };
+            let expected = instructions::ApproveProposal {
+                settings: keys[0],
+                signer: keys[1],
+                proposal: keys[2],
+                system_program: system_program.map(|info| *info.key),
+            }
+            .instruction(ApproveProposalInstructionArgs {
+                args: VoteOnProposalArgs { memo: None },
+            });
+            assert_eq!(vote.to_account_metas(None), expected.accounts);
+            // Absent accounts are not passed to the invocation.
+            assert_eq!(
+                vote.to_account_infos().len(),
+                3 + vote.system_program.is_some() as usize
+            );
+        }
+    }
+}
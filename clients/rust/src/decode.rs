@@ -0,0 +1,287 @@
+//! Discriminator-based decoding of program accounts.
+//!
+//! The generated account structs deserialize whatever bytes they are given;
+//! these helpers check the Anchor discriminator first so an account of the
+//! wrong kind is rejected instead of decoded into garbage.
+
+use std::io;
+
+use borsh::BorshDeserialize;
+
+use crate::accounts::{Batch, Proposal, Settings, SettingsTransaction, Transaction};
+
+pub const BATCH_DISCRIMINATOR: [u8; 8] = [156, 194, 70, 44, 22, 88, 137, 44];
+pub const BATCH_TRANSACTION_DISCRIMINATOR: [u8; 8] = [92, 20, 61, 146, 155, 62, 112, 72];
+pub const PROGRAM_CONFIG_DISCRIMINATOR: [u8; 8] = [196, 210, 90, 231, 144, 149, 140, 63];
+pub const PROPOSAL_DISCRIMINATOR: [u8; 8] = [26, 94, 189, 187, 116, 136, 53, 33];
+pub const SETTINGS_DISCRIMINATOR: [u8; 8] = [223, 179, 163, 190, 177, 224, 67, 173];
+pub const SETTINGS_TRANSACTION_DISCRIMINATOR: [u8; 8] = [199, 151, 72, 87, 77, 124, 16, 0];
+pub const SPENDING_LIMIT_DISCRIMINATOR: [u8; 8] = [10, 201, 27, 160, 218, 195, 222, 152];
+pub const TRANSACTION_DISCRIMINATOR: [u8; 8] = [11, 24, 174, 129, 203, 117, 242, 23];
+pub const TRANSACTION_BUFFER_DISCRIMINATOR: [u8; 8] = [90, 36, 35, 219, 93, 225, 110, 96];
+
+/// Any account that can live at a transaction index PDA.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransactionAccount {
+    Transaction(Transaction),
+    SettingsTransaction(SettingsTransaction),
+    Batch(Batch),
+}
+
+impl TransactionAccount {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+        match discriminator(data)? {
+            TRANSACTION_DISCRIMINATOR => Transaction::from_bytes(data).map(Self::Transaction),
+            SETTINGS_TRANSACTION_DISCRIMINATOR => {
+                SettingsTransaction::from_bytes(data).map(Self::SettingsTransaction)
+            }
+            BATCH_DISCRIMINATOR => Batch::from_bytes(data).map(Self::Batch),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a transaction, settings transaction or batch account",
+            )),
+        }
+    }
+}
+
+/// The first eight bytes of `data`.
+pub fn discriminator(data: &[u8]) -> Result<[u8; 8], io::Error> {
+    data.get(..8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "account data too short"))
+}
+
+/// A `Settings` account in one of the layouts this client knows.
+///
+/// Only the layout of the IDL the client is generated from is known. A
+/// legacy layout gets its own variant, normalized by [`Self::into_current`],
+/// once the program version that wrote it is pinned down.
+///
+/// The program grows settings and proposals with realloc but never shrinks
+/// them, and leaves whatever follows the serialized account in place, so
+/// an account can be longer than its contents: a removed signer, or an
+/// archival authority set back to `None`, leaves stale bytes at the end.
+/// Those bytes are ignored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VersionedSettings {
+    Current(Settings),
+}
+
+impl VersionedSettings {
+    /// Fails if `data` is shorter than [`Settings::size`] of the signers it
+    /// holds, which the program never allocates.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+        expect_discriminator(data, SETTINGS_DISCRIMINATOR)?;
+        let settings: Settings = deserialize_prefix(data)?;
+        let size = Settings::size(settings.signers.len(), settings.restricted_signers.len());
+        if data.len() < size {
+            return Err(unknown_layout("settings", data.len()));
+        }
+        Ok(Self::Current(settings))
+    }
+
+    pub fn into_current(self) -> Settings {
+        match self {
+            Self::Current(settings) => settings,
+        }
+    }
+}
+
+/// A `Proposal` account in one of the layouts this client knows; see
+/// [`VersionedSettings`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VersionedProposal {
+    Current(Proposal),
+}
+
+impl VersionedProposal {
+    /// Fails if `data` has no room for the longest vote list, which the
+    /// program never allocates.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+        expect_discriminator(data, PROPOSAL_DISCRIMINATOR)?;
+        let proposal: Proposal = deserialize_prefix(data)?;
+        let votes = proposal
+            .approved
+            .len()
+            .max(proposal.rejected.len())
+            .max(proposal.cancelled.len());
+        if data.len() < Proposal::size(votes) {
+            return Err(unknown_layout("proposal", data.len()));
+        }
+        Ok(Self::Current(proposal))
+    }
+
+    pub fn into_current(self) -> Proposal {
+        match self {
+            Self::Current(proposal) => proposal,
+        }
+    }
+}
+
+fn expect_discriminator(data: &[u8], expected: [u8; 8]) -> Result<(), io::Error> {
+    if discriminator(data)? == expected {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unexpected account discriminator",
+        ))
+    }
+}
+
+/// Deserializes `T` from the start of `data`, ignoring what follows.
+fn deserialize_prefix<T: BorshDeserialize>(data: &[u8]) -> Result<T, io::Error> {
+    T::deserialize(&mut &data[..])
+}
+
+fn unknown_layout(account: &str, len: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} account of {} bytes matches no known layout",
+            account, len
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+    use solana_pubkey::Pubkey;
+
+    use super::*;
+    use crate::types::{Permissions, ProposalStatus, SmartAccountSigner};
+
+    fn settings(num_signers: u8) -> Settings {
+        Settings {
+            discriminator: SETTINGS_DISCRIMINATOR,
+            seed: 1,
+            settings_authority: Pubkey::default(),
+            threshold: 1,
+            time_lock: 0,
+            transaction_index: 3,
+            stale_transaction_index: 1,
+            archival_authority: None,
+            archivable_after: 0,
+            bump: 255,
+            signers: (0..num_signers)
+                .map(|n| SmartAccountSigner {
+                    key: Pubkey::new_from_array([n; 32]),
+                    permissions: Permissions {
+                        mask: Permissions::VOTE,
+                    },
+                })
+                .collect(),
+            restricted_signers: Vec::new(),
+            account_utilization: 0,
+            reserved1: 0,
+            reserved2: 0,
+        }
+    }
+
+    fn proposal(approved: Vec<Pubkey>) -> Proposal {
+        Proposal {
+            discriminator: PROPOSAL_DISCRIMINATOR,
+            settings: Pubkey::new_from_array([7; 32]),
+            transaction_index: 3,
+            rent_collector: Pubkey::new_from_array([4; 32]),
+            status: ProposalStatus::Active { timestamp: 10 },
+            bump: 254,
+            approved,
+            rejected: Vec::new(),
+            cancelled: Vec::new(),
+        }
+    }
+
+    /// `account` serialized into an allocation of `size` bytes.
+    fn allocate(account: &impl BorshSerialize, size: usize) -> Vec<u8> {
+        let mut data = borsh::to_vec(account).unwrap();
+        assert!(data.len() <= size);
+        data.resize(size, 0);
+        data
+    }
+
+    #[test]
+    fn settings_of_their_own_size_decode() {
+        let settings = settings(2);
+        let data = allocate(&settings, Settings::size(2, 0));
+        assert_eq!(
+            VersionedSettings::from_bytes(&data).unwrap().into_current(),
+            settings
+        );
+    }
+
+    #[test]
+    fn proposals_decode_with_room_for_every_signer() {
+        let proposal = proposal(vec![Pubkey::new_from_array([1; 32])]);
+        for signers in [1, 3] {
+            let data = allocate(&proposal, Proposal::size(signers));
+            assert_eq!(
+                VersionedProposal::from_bytes(&data).unwrap().into_current(),
+                proposal
+            );
+        }
+    }
+
+    #[test]
+    fn stale_bytes_after_a_shrunk_account_are_ignored() {
+        // A signer was removed: the account keeps its three-signer size and
+        // the removed signer's bytes.
+        let mut data = allocate(&settings(3), Settings::size(3, 0));
+        let shrunk = settings(2);
+        let serialized = borsh::to_vec(&shrunk).unwrap();
+        data[..serialized.len()].copy_from_slice(&serialized);
+        assert_ne!(data[serialized.len()..], [0; 33][..]);
+        assert_eq!(
+            VersionedSettings::from_bytes(&data).unwrap().into_current(),
+            shrunk
+        );
+
+        // The archival authority was set back to `None`, so the account
+        // ends 32 bytes before the allocation does.
+        let archived = Settings {
+            archival_authority: Some(Pubkey::new_from_array([9; 32])),
+            ..settings(1)
+        };
+        let mut data = allocate(&archived, Settings::size(1, 0));
+        let cleared = settings(1);
+        let serialized = borsh::to_vec(&cleared).unwrap();
+        data[..serialized.len()].copy_from_slice(&serialized);
+        assert_eq!(
+            VersionedSettings::from_bytes(&data).unwrap().into_current(),
+            cleared
+        );
+
+        let proposal = proposal(vec![Pubkey::new_from_array([1; 32])]);
+        let mut data = allocate(&proposal, Proposal::size(2));
+        *data.last_mut().unwrap() = 1;
+        assert_eq!(
+            VersionedProposal::from_bytes(&data).unwrap().into_current(),
+            proposal
+        );
+    }
+
+    #[test]
+    fn accounts_without_room_for_their_contents_are_rejected() {
+        // Serialized as is, without the space always allocated for the
+        // archival authority.
+        let data = borsh::to_vec(&settings(2)).unwrap();
+        assert_eq!(
+            VersionedSettings::from_bytes(&data).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let proposal = proposal(vec![
+            Pubkey::new_from_array([1; 32]),
+            Pubkey::new_from_array([2; 32]),
+        ]);
+        let data = allocate(&proposal, Proposal::size(1));
+        assert_eq!(
+            VersionedProposal::from_bytes(&data).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Program activity parsed from transaction logs and inner instructions.
+//!
+//! The program reports activity two ways: log lines written while it is
+//! executing, and `log_event` self-invocations whose arguments carry a
+//! serialized event. [`parse_logs`] and [`parse_inner_instructions`] turn
+//! both into [`ProgramEvent`]s; [`Event`] attaches the slot and signature
+//! so indexers can order and deduplicate them.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use borsh::BorshDeserialize;
+use solana_message::compiled_instruction::CompiledInstruction;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+
+use crate::instructions::{LogEventInstructionArgs, LogEventInstructionData};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProgramEvent {
+    /// An instruction started, named by its `Instruction:` log.
+    Instruction(String),
+    /// Any other `Program log:` message.
+    Log(String),
+    /// A `Program data:` payload.
+    Data(Vec<u8>),
+    /// A `log_event` self-invocation.
+    LogEvent(LogEventInstructionArgs),
+    /// An invocation of the program failed with the given reason.
+    Failed(String),
+}
+
+/// A [`ProgramEvent`] with the transaction it came from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Event {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub signature: Signature,
+    pub slot: u64,
+    pub event: ProgramEvent,
+}
+
+/// Parses the log lines the program wrote while it was the innermost
+/// running program. Output of programs it invokes is skipped.
+pub fn parse_logs<S: AsRef<str>>(logs: &[S]) -> Vec<ProgramEvent> {
+    let program_id = crate::ID.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+
+    for line in logs {
+        let line = line.as_ref();
+        if let Some(rest) = line.strip_prefix("Program ") {
+            if let Some((program, _)) = rest.split_once(" invoke [") {
+                stack.push(program);
+                continue;
+            }
+            if let Some(program) = rest.strip_suffix(" success") {
+                if stack.last() == Some(&program) {
+                    stack.pop();
+                }
+                continue;
+            }
+            if let Some((program, reason)) = rest.split_once(" failed: ") {
+                if stack.last() == Some(&program) {
+                    stack.pop();
+                }
+                if program == program_id {
+                    events.push(ProgramEvent::Failed(reason.to_string()));
+                }
+                continue;
+            }
+        }
+        if stack.last() != Some(&program_id.as_str()) {
+            continue;
+        }
+        if let Some(message) = line.strip_prefix("Program log: ") {
+            events.push(match message.strip_prefix("Instruction: ") {
+                Some(name) => ProgramEvent::Instruction(name.to_string()),
+                None => ProgramEvent::Log(message.to_string()),
+            });
+        } else if let Some(data) = line.strip_prefix("Program data: ") {
+            events.extend(
+                data.split_whitespace()
+                    .filter_map(|chunk| STANDARD.decode(chunk).ok())
+                    .map(ProgramEvent::Data),
+            );
+        }
+    }
+    events
+}
+
+/// Decodes the `log_event` invocations among a transaction's inner
+/// instructions. `account_keys` are the transaction's keys, including any
+/// loaded from lookup tables, in message order.
+pub fn parse_inner_instructions(
+    account_keys: &[Pubkey],
+    instructions: &[CompiledInstruction],
+) -> Vec<ProgramEvent> {
+    let discriminator = borsh::to_vec(&LogEventInstructionData::new()).unwrap();
+    instructions
+        .iter()
+        .filter(|instruction| {
+            account_keys.get(instruction.program_id_index as usize) == Some(&crate::ID)
+        })
+        .filter_map(|instruction| instruction.data.strip_prefix(discriminator.as_slice()))
+        .filter_map(|mut args| LogEventInstructionArgs::deserialize(&mut args).ok())
+        .map(ProgramEvent::LogEvent)
+        .collect()
+}
+
+/// Parses both sources and tags each event with `signature` and `slot`.
+pub fn parse_transaction<S: AsRef<str>>(
+    signature: Signature,
+    slot: u64,
+    logs: &[S],
+    account_keys: &[Pubkey],
+    inner_instructions: &[CompiledInstruction],
+) -> Vec<Event> {
+    parse_logs(logs)
+        .into_iter()
+        .chain(parse_inner_instructions(account_keys, inner_instructions))
+        .map(|event| Event {
+            signature,
+            slot,
+            event,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logs_of_invoked_programs_are_skipped() {
+        let program = crate::ID.to_string();
+        let other = Pubkey::new_unique().to_string();
+        let logs = [
+            format!("Program {} invoke [1]", program),
+            "Program log: Instruction: ExecuteTransaction".to_string(),
+            format!("Program {} invoke [2]", other),
+            "Program log: Instruction: Transfer".to_string(),
+            format!("Program {} success", other),
+            "Program log: executed".to_string(),
+            format!(
+                "Program data: {} {}",
+                STANDARD.encode([1, 2]),
+                STANDARD.encode([3])
+            ),
+            format!("Program {} failed: custom program error: 0x1771", program),
+        ];
+        assert_eq!(
+            parse_logs(&logs),
+            [
+                ProgramEvent::Instruction("ExecuteTransaction".to_string()),
+                ProgramEvent::Log("executed".to_string()),
+                ProgramEvent::Data(vec![1, 2]),
+                ProgramEvent::Data(vec![3]),
+                ProgramEvent::Failed("custom program error: 0x1771".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn log_event_invocations_are_decoded_with_their_transaction() {
+        let args = LogEventInstructionArgs {
+            account_seeds: vec![b"smart_account".to_vec()],
+            bump: 254,
+            event: vec![9, 9],
+        };
+        let mut data = borsh::to_vec(&LogEventInstructionData::new()).unwrap();
+        data.extend(borsh::to_vec(&args).unwrap());
+        let account_keys = [Pubkey::new_unique(), crate::ID, Pubkey::new_unique()];
+        let inner = [
+            CompiledInstruction::new_from_raw_parts(1, data.clone(), vec![0]),
+            // The same bytes sent to another program are not an event.
+            CompiledInstruction::new_from_raw_parts(2, data, vec![0]),
+            CompiledInstruction::new_from_raw_parts(1, vec![1, 2, 3], vec![0]),
+        ];
+
+        let signature = Signature::from([4; 64]);
+        let no_logs: [&str; 0] = [];
+        assert_eq!(
+            parse_transaction(signature, 42, &no_logs, &account_keys, &inner),
+            [Event {
+                signature,
+                slot: 42,
+                event: ProgramEvent::LogEvent(args),
+            }]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Unsigned transactions for wallets and signing services.
+//!
+//! Builds a transaction from any instructions with empty signature slots,
+//! optionally signed by some keys already, and encodes it the way external
+//! signers expect: base58 or base64 blobs, the raw bytes a wallet-standard
+//! `signTransaction` call takes, or a Solana Pay transaction request
+//! response. The wallet fills in the remaining signatures.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use solana_hash::Hash;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_signer::Signer;
+use solana_transaction::versioned::VersionedTransaction;
+use thiserror::Error;
+
+use crate::transaction::{BuildError, TransactionFormat};
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum ExportError {
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error("invalid transaction encoding: {0}")]
+    Encoding(String),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Encoding {
+    Base58,
+    Base64,
+}
+
+/// The body of a Solana Pay transaction request's POST response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransactionRequestResponse {
+    /// The base64 transaction.
+    pub transaction: String,
+    /// Shown to the user by the wallet.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub message: Option<String>,
+}
+
+/// Builds `instructions` paid for by `payer` and signs with those of
+/// `signers` the message requires, leaving the other signatures empty.
+pub fn partially_signed_transaction(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    format: &TransactionFormat,
+    signers: &[&dyn Signer],
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction, BuildError> {
+    let message = format.compile(payer, instructions, recent_blockhash)?;
+    let num_required_signatures = message.header().num_required_signatures as usize;
+    let message_data = message.serialize();
+    let signatures = message
+        .static_account_keys()
+        .iter()
+        .take(num_required_signatures)
+        .map(|key| {
+            let Some(signer) = signers.iter().find(|signer| signer.pubkey() == *key) else {
+                return Ok(Signature::default());
+            };
+            signer
+                .try_sign_message(&message_data)
+                .map_err(|e| BuildError::Signer(e.to_string()))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(VersionedTransaction {
+        signatures,
+        message,
+    })
+}
+
+/// The wire bytes of `transaction`.
+pub fn serialize_transaction(transaction: &VersionedTransaction) -> Vec<u8> {
+    bincode::serialize(transaction).expect("in-memory serialization cannot fail")
+}
+
+pub fn encode_transaction(transaction: &VersionedTransaction, encoding: Encoding) -> String {
+    let bytes = serialize_transaction(transaction);
+    match encoding {
+        Encoding::Base58 => bs58::encode(bytes).into_string(),
+        Encoding::Base64 => STANDARD.encode(bytes),
+    }
+}
+
+/// Decodes a transaction returned by a wallet or signing service.
+pub fn decode_transaction(
+    encoded: &str,
+    encoding: Encoding,
+) -> Result<VersionedTransaction, ExportError> {
+    let encoded = encoded.trim();
+    let bytes = match encoding {
+        Encoding::Base58 => bs58::decode(encoded)
+            .into_vec()
+            .map_err(|e| ExportError::Encoding(e.to_string()))?,
+        Encoding::Base64 => STANDARD
+            .decode(encoded)
+            .map_err(|e| ExportError::Encoding(e.to_string()))?,
+    };
+    bincode::deserialize(&bytes).map_err(|e| ExportError::Encoding(e.to_string()))
+}
+
+/// A Solana Pay transaction request response for `account`, the wallet
+/// that made the request, which pays and signs. `signers` are the other
+/// keys the instructions need, such as the server's.
+pub fn transaction_request_response(
+    account: &Pubkey,
+    instructions: &[Instruction],
+    format: &TransactionFormat,
+    signers: &[&dyn Signer],
+    recent_blockhash: Hash,
+    message: Option<String>,
+) -> Result<TransactionRequestResponse, ExportError> {
+    let transaction =
+        partially_signed_transaction(account, instructions, format, signers, recent_blockhash)?;
+    Ok(TransactionRequestResponse {
+        transaction: encode_transaction(&transaction, Encoding::Base64),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::AccountMeta;
+    use solana_keypair::Keypair;
+
+    use super::*;
+
+    #[test]
+    fn only_the_given_signers_sign_and_the_rest_is_left_to_the_wallet() {
+        let (wallet, server) = (Pubkey::new_unique(), Keypair::new());
+        let instruction = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(wallet, true),
+                AccountMeta::new_readonly(server.pubkey(), true),
+            ],
+            data: vec![1],
+        };
+        let response = transaction_request_response(
+            &wallet,
+            &[instruction],
+            &TransactionFormat::Legacy,
+            &[&server],
+            Hash::new_from_array([1; 32]),
+            Some("Approve the payout".to_string()),
+        )
+        .unwrap();
+        assert_eq!(response.message.as_deref(), Some("Approve the payout"));
+
+        let transaction = decode_transaction(&response.transaction, Encoding::Base64).unwrap();
+        let keys = transaction.message.static_account_keys();
+        assert_eq!(keys[..2], [wallet, server.pubkey()]);
+        assert_eq!(transaction.signatures[0], Signature::default());
+        assert!(transaction.signatures[1]
+            .verify(server.pubkey().as_ref(), &transaction.message.serialize()));
+
+        let base58 = encode_transaction(&transaction, Encoding::Base58);
+        assert_eq!(
+            decode_transaction(&base58, Encoding::Base58).unwrap(),
+            transaction
+        );
+        assert!(matches!(
+            decode_transaction(&base58, Encoding::Base64),
+            Err(ExportError::Encoding(_))
+        ));
+    }
+}
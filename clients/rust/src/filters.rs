@@ -0,0 +1,251 @@
+//! `getProgramAccounts` filters for common queries.
+//!
+//! Each function returns the full filter list for one query, starting with
+//! the account discriminator. Offsets follow the account layouts in
+//! `crate::accounts`. With the `fetch` feature a `Filter` converts into an
+//! `RpcFilterType`.
+
+use solana_pubkey::Pubkey;
+
+use crate::decode::{
+    BATCH_DISCRIMINATOR, PROPOSAL_DISCRIMINATOR, SETTINGS_DISCRIMINATOR,
+    SETTINGS_TRANSACTION_DISCRIMINATOR, SPENDING_LIMIT_DISCRIMINATOR,
+    TRANSACTION_BUFFER_DISCRIMINATOR, TRANSACTION_DISCRIMINATOR,
+};
+
+/// Every account starts with its discriminator followed by the settings key,
+/// except `Settings` itself.
+const SETTINGS_OFFSET: usize = 8;
+/// `creator` in `Transaction`, `SettingsTransaction`, `Batch` and `TransactionBuffer`.
+const CREATOR_OFFSET: usize = 40;
+/// `Settings::settings_authority`, after the `u128` seed.
+const SETTINGS_AUTHORITY_OFFSET: usize = 24;
+/// `Proposal::status`, after settings, transaction_index and rent_collector.
+const PROPOSAL_STATUS_OFFSET: usize = 80;
+/// `ProposalStatus::Active` variant index.
+const PROPOSAL_STATUS_ACTIVE: u8 = 1;
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Filter {
+    DataSize(u64),
+    Memcmp { offset: usize, bytes: Vec<u8> },
+}
+
+impl Filter {
+    pub fn memcmp(offset: usize, bytes: &[u8]) -> Self {
+        Self::Memcmp {
+            offset,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    pub fn discriminator(discriminator: [u8; 8]) -> Self {
+        Self::memcmp(0, &discriminator)
+    }
+
+    /// Whether account `data` passes the filter, as the RPC node decides.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            Self::DataSize(size) => data.len() as u64 == *size,
+            Self::Memcmp { offset, bytes } => {
+                offset
+                    .checked_add(bytes.len())
+                    .and_then(|end| data.get(*offset..end))
+                    == Some(bytes.as_slice())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl From<Filter> for solana_client::rpc_filter::RpcFilterType {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::DataSize(size) => Self::DataSize(size),
+            Filter::Memcmp { offset, bytes } => Self::Memcmp(
+                solana_client::rpc_filter::Memcmp::new_raw_bytes(offset, bytes),
+            ),
+        }
+    }
+}
+
+/// Smart accounts controlled by `settings_authority`.
+pub fn settings_by_authority(settings_authority: &Pubkey) -> Vec<Filter> {
+    vec![
+        Filter::discriminator(SETTINGS_DISCRIMINATOR),
+        Filter::memcmp(SETTINGS_AUTHORITY_OFFSET, settings_authority.as_ref()),
+    ]
+}
+
+/// All proposals of `settings`, in any status.
+pub fn proposals(settings: &Pubkey) -> Vec<Filter> {
+    vec![
+        Filter::discriminator(PROPOSAL_DISCRIMINATOR),
+        Filter::memcmp(SETTINGS_OFFSET, settings.as_ref()),
+    ]
+}
+
+/// Proposals of `settings` that are open for voting.
+pub fn active_proposals(settings: &Pubkey) -> Vec<Filter> {
+    let mut filters = proposals(settings);
+    filters.push(Filter::memcmp(
+        PROPOSAL_STATUS_OFFSET,
+        &[PROPOSAL_STATUS_ACTIVE],
+    ));
+    filters
+}
+
+/// Vault transactions of `settings`.
+pub fn transactions(settings: &Pubkey) -> Vec<Filter> {
+    vec![
+        Filter::discriminator(TRANSACTION_DISCRIMINATOR),
+        Filter::memcmp(SETTINGS_OFFSET, settings.as_ref()),
+    ]
+}
+
+/// Vault transactions created by `creator` across all smart accounts.
+pub fn transactions_by_creator(creator: &Pubkey) -> Vec<Filter> {
+    vec![
+        Filter::discriminator(TRANSACTION_DISCRIMINATOR),
+        Filter::memcmp(CREATOR_OFFSET, creator.as_ref()),
+    ]
+}
+
+/// Settings transactions created by `creator` across all smart accounts.
+pub fn settings_transactions_by_creator(creator: &Pubkey) -> Vec<Filter> {
+    vec![
+        Filter::discriminator(SETTINGS_TRANSACTION_DISCRIMINATOR),
+        Filter::memcmp(CREATOR_OFFSET, creator.as_ref()),
+    ]
+}
+
+/// Batches created by `creator` across all smart accounts.
+pub fn batches_by_creator(creator: &Pubkey) -> Vec<Filter> {
+    vec![
+        Filter::discriminator(BATCH_DISCRIMINATOR),
+        Filter::memcmp(CREATOR_OFFSET, creator.as_ref()),
+    ]
+}
+
+/// Transaction buffers of `settings` opened by `creator`.
+pub fn transaction_buffers_by_creator(settings: &Pubkey, creator: &Pubkey) -> Vec<Filter> {
+    vec![
+        Filter::discriminator(TRANSACTION_BUFFER_DISCRIMINATOR),
+        Filter::memcmp(SETTINGS_OFFSET, settings.as_ref()),
+        Filter::memcmp(CREATOR_OFFSET, creator.as_ref()),
+    ]
+}
+
+/// Every program account that stores `settings`, of any kind. Batch
+/// transactions do not store it and are not included.
+pub fn settings_accounts(settings: &Pubkey) -> Vec<Filter> {
+    vec![Filter::memcmp(SETTINGS_OFFSET, settings.as_ref())]
+}
+
+/// Spending limits of `settings`.
+pub fn spending_limits(settings: &Pubkey) -> Vec<Filter> {
+    vec![
+        Filter::discriminator(SPENDING_LIMIT_DISCRIMINATOR),
+        Filter::memcmp(SETTINGS_OFFSET, settings.as_ref()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::{Proposal, Settings, Transaction};
+    use crate::types::{ProposalStatus, SmartAccountTransactionMessage};
+
+    fn matches(filters: &[Filter], data: &[u8]) -> bool {
+        filters.iter().all(|filter| filter.matches(data))
+    }
+
+    fn proposal(settings: Pubkey, status: ProposalStatus) -> Vec<u8> {
+        borsh::to_vec(&Proposal {
+            discriminator: PROPOSAL_DISCRIMINATOR,
+            settings,
+            transaction_index: 1,
+            rent_collector: Pubkey::new_unique(),
+            status,
+            bump: 255,
+            approved: Vec::new(),
+            rejected: Vec::new(),
+            cancelled: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn offsets_match_the_account_layouts() {
+        let (settings, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let active = proposal(settings, ProposalStatus::Active { timestamp: 0 });
+        let approved = proposal(settings, ProposalStatus::Approved { timestamp: 0 });
+        assert!(matches(&active_proposals(&settings), &active));
+        assert!(!matches(&active_proposals(&settings), &approved));
+        assert!(matches(&proposals(&settings), &approved));
+        assert!(!matches(&proposals(&Pubkey::new_unique()), &approved));
+        assert!(matches(&settings_accounts(&settings), &approved));
+
+        let transaction = borsh::to_vec(&Transaction {
+            discriminator: TRANSACTION_DISCRIMINATOR,
+            settings,
+            creator,
+            rent_collector: Pubkey::new_unique(),
+            index: 1,
+            bump: 255,
+            account_index: 0,
+            account_bump: 255,
+            ephemeral_signer_bumps: Vec::new(),
+            message: SmartAccountTransactionMessage {
+                num_signers: 0,
+                num_writable_signers: 0,
+                num_writable_non_signers: 0,
+                account_keys: Vec::new(),
+                instructions: Vec::new(),
+                address_table_lookups: Vec::new(),
+            },
+        })
+        .unwrap();
+        assert!(matches(&transactions(&settings), &transaction));
+        assert!(matches(&transactions_by_creator(&creator), &transaction));
+        assert!(!matches(&transactions_by_creator(&settings), &transaction));
+        assert!(!matches(
+            &settings_transactions_by_creator(&creator),
+            &transaction
+        ));
+
+        let authority = Pubkey::new_unique();
+        let settings_account = borsh::to_vec(&Settings {
+            discriminator: SETTINGS_DISCRIMINATOR,
+            seed: 0,
+            settings_authority: authority,
+            threshold: 1,
+            time_lock: 0,
+            transaction_index: 0,
+            stale_transaction_index: 0,
+            archival_authority: None,
+            archivable_after: 0,
+            bump: 255,
+            signers: Vec::new(),
+            restricted_signers: Vec::new(),
+            account_utilization: 0,
+            reserved1: 0,
+            reserved2: 0,
+        })
+        .unwrap();
+        assert!(matches(
+            &settings_by_authority(&authority),
+            &settings_account
+        ));
+    }
+
+    #[test]
+    fn filters_past_the_end_of_the_data_do_not_match() {
+        assert!(!Filter::memcmp(4, &[1, 2]).matches(&[0, 0, 0, 0, 1]));
+        assert!(Filter::memcmp(4, &[1, 2]).matches(&[0, 0, 0, 0, 1, 2]));
+        assert!(!Filter::memcmp(usize::MAX, &[1]).matches(&[1]));
+        assert!(Filter::DataSize(3).matches(&[0; 3]));
+        assert!(!Filter::DataSize(3).matches(&[0; 4]));
+    }
+}
@@ -22,7 +22,7 @@ pub struct CreateTransactionFromBuffer {
 
     
               
-          pub creator: solana_pubkey::Pubkey,
+          pub transaction_creator: solana_pubkey::Pubkey,
                 /// The payer for the transaction account rent.
 
     
@@ -56,7 +56,7 @@ impl CreateTransactionFromBuffer {
             false
           ));
                                           accounts.push(solana_instruction::AccountMeta::new_readonly(
-            self.creator,
+            self.transaction_creator,
             true
           ));
                                           accounts.push(solana_instruction::AccountMeta::new(
@@ -121,7 +121,7 @@ impl Default for CreateTransactionFromBufferInstructionData {
 ///
                 ///   0. `[writable]` settings
                 ///   1. `[writable]` transaction
-                ///   2. `[signer]` creator
+                ///   2. `[signer]` transaction_creator
                       ///   3. `[writable, signer]` rent_payer
                 ///   4. `[optional]` system_program (default to `11111111111111111111111111111111`)
                 ///   5. `[writable]` transaction_buffer
@@ -130,7 +130,7 @@ impl Default for CreateTransactionFromBufferInstructionData {
 pub struct CreateTransactionFromBufferBuilder {
             settings: Option<solana_pubkey::Pubkey>,
                 transaction: Option<solana_pubkey::Pubkey>,
-                creator: Option<solana_pubkey::Pubkey>,
+                transaction_creator: Option<solana_pubkey::Pubkey>,
                 rent_payer: Option<solana_pubkey::Pubkey>,
                 system_program: Option<solana_pubkey::Pubkey>,
                 transaction_buffer: Option<solana_pubkey::Pubkey>,
@@ -155,8 +155,8 @@ impl CreateTransactionFromBufferBuilder {
     }
             /// The member of the multisig that is creating the transaction.
 #[inline(always)]
-    pub fn creator(&mut self, creator: solana_pubkey::Pubkey) -> &mut Self {
-                        self.creator = Some(creator);
+    pub fn transaction_creator(&mut self, transaction_creator: solana_pubkey::Pubkey) -> &mut Self {
+                        self.transaction_creator = Some(transaction_creator);
                     self
     }
             /// The payer for the transaction account rent.
@@ -203,7 +203,7 @@ impl CreateTransactionFromBufferBuilder {
     let accounts = CreateTransactionFromBuffer {
                               settings: self.settings.expect("settings is not set"),
                                         transaction: self.transaction.expect("transaction is not set"),
-                                        creator: self.creator.expect("creator is not set"),
+                                        transaction_creator: self.transaction_creator.expect("transaction_creator is not set"),
                                         rent_payer: self.rent_payer.expect("rent_payer is not set"),
                                         system_program: self.system_program.unwrap_or(solana_pubkey::pubkey!("11111111111111111111111111111111")),
                                         transaction_buffer: self.transaction_buffer.expect("transaction_buffer is not set"),
@@ -229,7 +229,7 @@ impl CreateTransactionFromBufferBuilder {
 
       
                     
-              pub creator: &'b solana_account_info::AccountInfo<'a>,
+              pub transaction_creator: &'b solana_account_info::AccountInfo<'a>,
                         /// The payer for the transaction account rent.
 
       
@@ -260,7 +260,7 @@ pub struct CreateTransactionFromBufferCpi<'a, 'b> {
 
     
               
-          pub creator: &'b solana_account_info::AccountInfo<'a>,
+          pub transaction_creator: &'b solana_account_info::AccountInfo<'a>,
                 /// The payer for the transaction account rent.
 
     
@@ -289,7 +289,7 @@ impl<'a, 'b> CreateTransactionFromBufferCpi<'a, 'b> {
       __program: program,
               settings: accounts.settings,
               transaction: accounts.transaction,
-              creator: accounts.creator,
+              transaction_creator: accounts.transaction_creator,
               rent_payer: accounts.rent_payer,
               system_program: accounts.system_program,
               transaction_buffer: accounts.transaction_buffer,
@@ -327,7 +327,7 @@ impl<'a, 'b> CreateTransactionFromBufferCpi<'a, 'b> {
             false
           ));
                                           accounts.push(solana_instruction::AccountMeta::new_readonly(
-            *self.creator.key,
+            *self.transaction_creator.key,
             true
           ));
                                           accounts.push(solana_instruction::AccountMeta::new(
@@ -366,7 +366,7 @@ impl<'a, 'b> CreateTransactionFromBufferCpi<'a, 'b> {
     account_infos.push(self.__program.clone());
                   account_infos.push(self.settings.clone());
                         account_infos.push(self.transaction.clone());
-                        account_infos.push(self.creator.clone());
+                        account_infos.push(self.transaction_creator.clone());
                         account_infos.push(self.rent_payer.clone());
                         account_infos.push(self.system_program.clone());
                         account_infos.push(self.transaction_buffer.clone());
@@ -387,7 +387,7 @@ impl<'a, 'b> CreateTransactionFromBufferCpi<'a, 'b> {
 ///
                 ///   0. `[writable]` settings
                 ///   1. `[writable]` transaction
-                ///   2. `[signer]` creator
+                ///   2. `[signer]` transaction_creator
                       ///   3. `[writable, signer]` rent_payer
           ///   4. `[]` system_program
                 ///   5. `[writable]` transaction_buffer
@@ -403,7 +403,7 @@ impl<'a, 'b> CreateTransactionFromBufferCpiBuilder<'a, 'b> {
       __program: program,
               settings: None,
               transaction: None,
-              creator: None,
+              transaction_creator: None,
               rent_payer: None,
               system_program: None,
               transaction_buffer: None,
@@ -425,8 +425,8 @@ impl<'a, 'b> CreateTransactionFromBufferCpiBuilder<'a, 'b> {
     }
       /// The member of the multisig that is creating the transaction.
 #[inline(always)]
-    pub fn creator(&mut self, creator: &'b solana_account_info::AccountInfo<'a>) -> &mut Self {
-                        self.instruction.creator = Some(creator);
+    pub fn transaction_creator(&mut self, transaction_creator: &'b solana_account_info::AccountInfo<'a>) -> &mut Self {
+                        self.instruction.transaction_creator = Some(transaction_creator);
                     self
     }
       /// The payer for the transaction account rent.
@@ -487,7 +487,7 @@ impl<'a, 'b> CreateTransactionFromBufferCpiBuilder<'a, 'b> {
                   
           transaction: self.instruction.transaction.expect("transaction is not set"),
                   
-          creator: self.instruction.creator.expect("creator is not set"),
+          transaction_creator: self.instruction.transaction_creator.expect("transaction_creator is not set"),
                   
           rent_payer: self.instruction.rent_payer.expect("rent_payer is not set"),
                   
@@ -507,7 +507,7 @@ struct CreateTransactionFromBufferCpiBuilderInstruction<'a, 'b> {
   __program: &'b solana_account_info::AccountInfo<'a>,
             settings: Option<&'b solana_account_info::AccountInfo<'a>>,
                 transaction: Option<&'b solana_account_info::AccountInfo<'a>>,
-                creator: Option<&'b solana_account_info::AccountInfo<'a>>,
+                transaction_creator: Option<&'b solana_account_info::AccountInfo<'a>>,
                 rent_payer: Option<&'b solana_account_info::AccountInfo<'a>>,
                 system_program: Option<&'b solana_account_info::AccountInfo<'a>>,
                 transaction_buffer: Option<&'b solana_account_info::AccountInfo<'a>>,
@@ -0,0 +1,321 @@
+//! Voting in spl-governance realms with tokens held by a vault.
+//!
+//! [`VaultVoter`] builds the spl-governance instructions a vault needs to
+//! take part in another DAO: depositing its governing tokens into the realm,
+//! voting on proposals, relinquishing votes and withdrawing the tokens again.
+//! The vault is the governing token owner, the governance authority and the
+//! payer, and the only signer, so the instructions go into a vault
+//! transaction as they are.
+//!
+//! Realms with a voter weight plugin need the plugin's own accounts and
+//! instructions, which are not covered here. Instruction layouts are those
+//! of spl-governance 3.1.
+
+use borsh::BorshSerialize;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::{pubkey, Pubkey};
+use solana_sdk_ids::system_program;
+
+use crate::pda;
+
+/// The spl-governance program most realms use. DAOs can deploy their own
+/// instance, see [`VaultVoter::with_program_id`].
+pub const SPL_GOVERNANCE_PROGRAM_ID: Pubkey =
+    pubkey!("GovER5Lthms3bLBqWub97yVrMmEogzX7xNjdXpPPCVZw");
+
+const GOVERNANCE_SEED: &[u8] = b"governance";
+const REALM_CONFIG_SEED: &[u8] = b"realm-config";
+
+/// Indexes into spl-governance's instruction enum.
+const DEPOSIT_GOVERNING_TOKENS: u8 = 1;
+const WITHDRAW_GOVERNING_TOKENS: u8 = 2;
+const CAST_VOTE: u8 = 13;
+const RELINQUISH_VOTE: u8 = 15;
+
+/// A vote, as spl-governance encodes it.
+#[derive(BorshSerialize, Clone, Debug, Eq, PartialEq)]
+pub enum Vote {
+    /// One choice per option of the proposal; a single-choice proposal
+    /// takes [`Vote::approve`].
+    Approve(Vec<VoteChoice>),
+    Deny,
+    Abstain,
+    /// Cast with the other governing token of the realm.
+    Veto,
+}
+
+#[derive(BorshSerialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VoteChoice {
+    pub rank: u8,
+    pub weight_percentage: u8,
+}
+
+impl Vote {
+    /// Approval of a proposal with a single option.
+    pub fn approve() -> Self {
+        Self::Approve(vec![VoteChoice {
+            rank: 0,
+            weight_percentage: 100,
+        }])
+    }
+}
+
+/// See the [module documentation](self).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VaultVoter {
+    program_id: Pubkey,
+    realm: Pubkey,
+    governing_token_mint: Pubkey,
+    vault: Pubkey,
+}
+
+impl VaultVoter {
+    /// The vault of `settings` at `account_index`, voting in `realm` with
+    /// `governing_token_mint`, the realm's community or council mint.
+    pub fn new(
+        settings: &Pubkey,
+        account_index: u8,
+        realm: Pubkey,
+        governing_token_mint: Pubkey,
+    ) -> Self {
+        Self {
+            program_id: SPL_GOVERNANCE_PROGRAM_ID,
+            realm,
+            governing_token_mint,
+            vault: pda::smart_account(settings, account_index).0,
+        }
+    }
+
+    /// For realms on their own spl-governance deployment.
+    pub fn with_program_id(mut self, program_id: Pubkey) -> Self {
+        self.program_id = program_id;
+        self
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        self.vault
+    }
+
+    /// The vault's token owner record, which holds its deposit and votes.
+    pub fn token_owner_record(&self) -> Pubkey {
+        self.find_address(&[
+            GOVERNANCE_SEED,
+            self.realm.as_ref(),
+            self.governing_token_mint.as_ref(),
+            self.vault.as_ref(),
+        ])
+    }
+
+    /// The record of the vault's vote on `proposal`.
+    pub fn vote_record(&self, proposal: &Pubkey) -> Pubkey {
+        self.find_address(&[
+            GOVERNANCE_SEED,
+            proposal.as_ref(),
+            self.token_owner_record().as_ref(),
+        ])
+    }
+
+    /// Deposits `amount` governing tokens from `source`, a token account of
+    /// the vault, creating the token owner record on first use.
+    pub fn deposit(&self, source: &Pubkey, token_program: &Pubkey, amount: u64) -> Instruction {
+        self.instruction(
+            vec![
+                AccountMeta::new_readonly(self.realm, false),
+                AccountMeta::new(self.holding_account(), false),
+                AccountMeta::new(*source, false),
+                AccountMeta::new_readonly(self.vault, true),
+                AccountMeta::new_readonly(self.vault, true),
+                AccountMeta::new(self.token_owner_record(), false),
+                AccountMeta::new(self.vault, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(*token_program, false),
+                AccountMeta::new_readonly(self.realm_config(), false),
+            ],
+            DEPOSIT_GOVERNING_TOKENS,
+            &amount,
+        )
+    }
+
+    /// Withdraws the whole deposit to `destination`. Fails while the vault
+    /// has votes on proposals that are still being voted on.
+    pub fn withdraw(&self, destination: &Pubkey, token_program: &Pubkey) -> Instruction {
+        self.instruction(
+            vec![
+                AccountMeta::new_readonly(self.realm, false),
+                AccountMeta::new(self.holding_account(), false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(self.vault, true),
+                AccountMeta::new(self.token_owner_record(), false),
+                AccountMeta::new_readonly(*token_program, false),
+                AccountMeta::new_readonly(self.realm_config(), false),
+            ],
+            WITHDRAW_GOVERNING_TOKENS,
+            &(),
+        )
+    }
+
+    /// Votes on `proposal` of `governance`. `proposal_owner_record` is the
+    /// token owner record of the proposal's creator. Vetoes need a voter for
+    /// the other governing token mint of the realm.
+    pub fn cast_vote(
+        &self,
+        governance: &Pubkey,
+        proposal: &Pubkey,
+        proposal_owner_record: &Pubkey,
+        vote: &Vote,
+    ) -> Instruction {
+        self.instruction(
+            vec![
+                AccountMeta::new_readonly(self.realm, false),
+                AccountMeta::new(*governance, false),
+                AccountMeta::new(*proposal, false),
+                AccountMeta::new(*proposal_owner_record, false),
+                AccountMeta::new(self.token_owner_record(), false),
+                AccountMeta::new_readonly(self.vault, true),
+                AccountMeta::new(self.vote_record(proposal), false),
+                AccountMeta::new_readonly(self.governing_token_mint, false),
+                AccountMeta::new(self.vault, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(self.realm_config(), false),
+            ],
+            CAST_VOTE,
+            vote,
+        )
+    }
+
+    /// Withdraws the vault's vote while `proposal` is being voted on, or
+    /// releases it afterwards so the deposit can be withdrawn. The vote
+    /// record's rent goes back to the vault.
+    pub fn relinquish_vote(&self, governance: &Pubkey, proposal: &Pubkey) -> Instruction {
+        self.instruction(
+            vec![
+                AccountMeta::new_readonly(self.realm, false),
+                AccountMeta::new_readonly(*governance, false),
+                AccountMeta::new(*proposal, false),
+                AccountMeta::new(self.token_owner_record(), false),
+                AccountMeta::new(self.vote_record(proposal), false),
+                AccountMeta::new_readonly(self.governing_token_mint, false),
+                AccountMeta::new_readonly(self.vault, true),
+                AccountMeta::new(self.vault, false),
+            ],
+            RELINQUISH_VOTE,
+            &(),
+        )
+    }
+
+    fn holding_account(&self) -> Pubkey {
+        self.find_address(&[
+            GOVERNANCE_SEED,
+            self.realm.as_ref(),
+            self.governing_token_mint.as_ref(),
+        ])
+    }
+
+    fn realm_config(&self) -> Pubkey {
+        self.find_address(&[REALM_CONFIG_SEED, self.realm.as_ref()])
+    }
+
+    fn find_address(&self, seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &self.program_id).0
+    }
+
+    fn instruction(
+        &self,
+        accounts: Vec<AccountMeta>,
+        index: u8,
+        args: &impl BorshSerialize,
+    ) -> Instruction {
+        let mut data = vec![index];
+        args.serialize(&mut data)
+            .expect("in-memory serialization cannot fail");
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voter() -> VaultVoter {
+        VaultVoter::new(
+            &Pubkey::new_from_array([7; 32]),
+            0,
+            Pubkey::new_from_array([1; 32]),
+            Pubkey::new_from_array([2; 32]),
+        )
+    }
+
+    #[test]
+    fn instruction_data_follows_spl_governance() {
+        let voter = voter();
+        let (governance, proposal, owner_record) = (
+            Pubkey::new_from_array([3; 32]),
+            Pubkey::new_from_array([4; 32]),
+            Pubkey::new_from_array([5; 32]),
+        );
+        let vote = |vote| {
+            voter
+                .cast_vote(&governance, &proposal, &owner_record, &vote)
+                .data
+        };
+        assert_eq!(vote(Vote::approve()), [13, 0, 1, 0, 0, 0, 0, 100]);
+        assert_eq!(vote(Vote::Deny), [13, 1]);
+        assert_eq!(vote(Vote::Abstain), [13, 2]);
+        assert_eq!(vote(Vote::Veto), [13, 3]);
+        assert_eq!(
+            voter
+                .deposit(&Pubkey::default(), &Pubkey::default(), 5)
+                .data,
+            [1, 5, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            voter.withdraw(&Pubkey::default(), &Pubkey::default()).data,
+            [2]
+        );
+        assert_eq!(voter.relinquish_vote(&governance, &proposal).data, [15]);
+    }
+
+    #[test]
+    fn the_vault_is_the_only_signer() {
+        let voter = voter();
+        let (governance, proposal) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let instructions = [
+            voter.deposit(&Pubkey::new_unique(), &Pubkey::new_unique(), 1),
+            voter.cast_vote(&governance, &proposal, &Pubkey::new_unique(), &Vote::Deny),
+            voter.relinquish_vote(&governance, &proposal),
+            voter.withdraw(&Pubkey::new_unique(), &Pubkey::new_unique()),
+        ];
+        for instruction in &instructions {
+            assert!(instruction
+                .accounts
+                .iter()
+                .all(|meta| !meta.is_signer || meta.pubkey == voter.vault()));
+        }
+        assert_eq!(
+            instructions[1].accounts[6].pubkey,
+            voter.vote_record(&proposal)
+        );
+    }
+
+    #[test]
+    fn addresses_follow_the_program_id() {
+        let voter = voter();
+        let other = voter.with_program_id(Pubkey::new_unique());
+        assert_ne!(voter.token_owner_record(), other.token_owner_record());
+        assert_eq!(
+            other
+                .cast_vote(
+                    &Pubkey::default(),
+                    &Pubkey::default(),
+                    &Pubkey::default(),
+                    &Vote::Abstain
+                )
+                .program_id,
+            other.program_id
+        );
+    }
+}
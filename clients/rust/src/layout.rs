@@ -0,0 +1,272 @@
+//! Account sizes, as the program allocates them.
+//!
+//! Each variable-size account gets a `size` function of the lengths that
+//! vary, matching the space the program requests on creation and realloc.
+//! Proposals reserve room for every signer on each vote list. The
+//! functions take lengths rather than values, so the space of an account
+//! can be computed before its contents are known; [`crate::rent`] turns
+//! sizes into lamports.
+
+use crate::accounts::{
+    BatchTransaction, Proposal, Settings, SettingsTransaction, SpendingLimit, Transaction,
+    TransactionBuffer,
+};
+use crate::types::{SettingsAction, SmartAccountTransactionMessage};
+
+/// A signer or restricted signer: key and permission mask.
+const SIGNER_SIZE: usize = 32 + 1;
+/// Length prefix of a borsh `Vec`.
+const VEC_PREFIX_SIZE: usize = 4;
+/// Discriminator, settings, creator, rent collector, index and three bumps.
+const TRANSACTION_HEADER_SIZE: usize = 8 + 32 + 32 + 32 + 8 + 1 + 1 + 1;
+
+impl Settings {
+    pub fn size(num_signers: usize, num_restricted_signers: usize) -> usize {
+        // Discriminator, seed, settings authority, threshold, time lock,
+        // transaction index, stale transaction index, archival authority
+        // (always allocated), archivable after and bump.
+        8 + 16 + 32 + 2 + 4 + 8 + 8 + (1 + 32) + 8 + 1
+            + VEC_PREFIX_SIZE
+            + num_signers * SIGNER_SIZE
+            + VEC_PREFIX_SIZE
+            + num_restricted_signers * SIGNER_SIZE
+            // Account utilization and two reserved bytes.
+            + 3
+    }
+}
+
+impl Proposal {
+    /// Size of a proposal on a smart account with `num_signers` signers.
+    pub fn size(num_signers: usize) -> usize {
+        // Discriminator, settings, index, rent collector, status, bump, and
+        // three vote lists.
+        8 + 32 + 8 + 32 + (1 + 8) + 1 + 3 * (VEC_PREFIX_SIZE + num_signers * 32)
+    }
+}
+
+impl Transaction {
+    /// `message_len` is the serialized length of the message, see
+    /// [`SmartAccountTransactionMessage::size`].
+    pub fn size(ephemeral_signers: u8, message_len: usize) -> usize {
+        TRANSACTION_HEADER_SIZE + VEC_PREFIX_SIZE + ephemeral_signers as usize + message_len
+    }
+}
+
+impl SettingsTransaction {
+    pub fn size(actions: &[SettingsAction]) -> usize {
+        // Discriminator, settings, creator, rent collector, index and bump.
+        8 + 32 + 32 + 32 + 8 + 1 + borsh::to_vec(actions).unwrap().len()
+    }
+}
+
+impl BatchTransaction {
+    pub fn size(ephemeral_signers: u8, message_len: usize) -> usize {
+        // Discriminator, bump and rent collector.
+        8 + 1 + 32 + VEC_PREFIX_SIZE + ephemeral_signers as usize + message_len
+    }
+}
+
+impl SpendingLimit {
+    pub fn size(num_signers: usize, num_destinations: usize) -> usize {
+        // Discriminator, settings, seed, account index, mint, amount,
+        // period, remaining amount, last reset and bump.
+        8 + 32 + 32 + 1 + 32 + 8 + 1 + 8 + 8 + 1
+            + VEC_PREFIX_SIZE
+            + num_signers * 32
+            + VEC_PREFIX_SIZE
+            + num_destinations * 32
+            // Expiration.
+            + 8
+    }
+}
+
+impl TransactionBuffer {
+    /// Size of a buffer that will hold a message of `final_buffer_size`
+    /// bytes; the program allocates all of it up front.
+    pub fn size(final_buffer_size: u16) -> usize {
+        // Discriminator, settings, creator, buffer index, account index,
+        // final buffer hash and final buffer size.
+        8 + 32 + 32 + 1 + 1 + 32 + 2 + VEC_PREFIX_SIZE + final_buffer_size as usize
+    }
+}
+
+impl SmartAccountTransactionMessage {
+    /// Serialized length, as stored in transaction accounts.
+    pub fn size(&self) -> usize {
+        borsh::to_vec(self).unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+    use solana_pubkey::Pubkey;
+
+    use super::*;
+    use crate::types::{
+        Period, Permissions, ProposalStatus, RestrictedPermissions, RestrictedSmartAccountSigner,
+        SmartAccountSigner,
+    };
+
+    fn key(n: usize) -> Pubkey {
+        Pubkey::new_from_array([n as u8; 32])
+    }
+
+    fn len(account: &impl BorshSerialize) -> usize {
+        borsh::to_vec(account).unwrap().len()
+    }
+
+    fn message() -> SmartAccountTransactionMessage {
+        SmartAccountTransactionMessage {
+            num_signers: 1,
+            num_writable_signers: 1,
+            num_writable_non_signers: 1,
+            account_keys: vec![key(1), key(2)],
+            instructions: Vec::new(),
+            address_table_lookups: Vec::new(),
+        }
+    }
+
+    /// Settings with every optional field set, the largest they serialize.
+    fn settings(num_signers: usize, num_restricted_signers: usize) -> Settings {
+        Settings {
+            discriminator: [0; 8],
+            seed: 0,
+            settings_authority: key(0),
+            threshold: 1,
+            time_lock: 0,
+            transaction_index: 0,
+            stale_transaction_index: 0,
+            archival_authority: Some(key(0)),
+            archivable_after: 0,
+            bump: 0,
+            signers: (0..num_signers)
+                .map(|n| SmartAccountSigner {
+                    key: key(n),
+                    permissions: Permissions { mask: 7 },
+                })
+                .collect(),
+            restricted_signers: (0..num_restricted_signers)
+                .map(|n| RestrictedSmartAccountSigner {
+                    key: key(n),
+                    restricted_permissions: RestrictedPermissions { mask: 1 },
+                })
+                .collect(),
+            account_utilization: 0,
+            reserved1: 0,
+            reserved2: 0,
+        }
+    }
+
+    /// A proposal every signer voted on in every way, with the largest
+    /// status.
+    fn proposal(num_signers: usize) -> Proposal {
+        let votes: Vec<Pubkey> = (0..num_signers).map(key).collect();
+        Proposal {
+            discriminator: [0; 8],
+            settings: key(0),
+            transaction_index: 0,
+            rent_collector: key(0),
+            status: ProposalStatus::Approved { timestamp: 0 },
+            bump: 0,
+            approved: votes.clone(),
+            rejected: votes.clone(),
+            cancelled: votes,
+        }
+    }
+
+    #[test]
+    fn settings_and_proposal_sizes_fit_their_largest_contents() {
+        for (signers, restricted) in [(0, 0), (1, 0), (3, 2), (10, 1)] {
+            let settings = settings(signers, restricted);
+            assert_eq!(len(&settings), Settings::size(signers, restricted));
+        }
+        for signers in [0, 1, 5] {
+            let proposal = proposal(signers);
+            assert_eq!(len(&proposal), Proposal::size(signers));
+        }
+    }
+
+    #[test]
+    fn transaction_sizes_match_their_serialization() {
+        let message = message();
+        let transaction = Transaction {
+            discriminator: [0; 8],
+            settings: key(0),
+            creator: key(0),
+            rent_collector: key(0),
+            index: 0,
+            bump: 0,
+            account_index: 0,
+            account_bump: 0,
+            ephemeral_signer_bumps: vec![0; 2],
+            message: message.clone(),
+        };
+        assert_eq!(len(&transaction), Transaction::size(2, message.size()));
+
+        let batch_transaction = BatchTransaction {
+            discriminator: [0; 8],
+            bump: 0,
+            rent_collector: key(0),
+            ephemeral_signer_bumps: vec![0; 3],
+            message: message.clone(),
+        };
+        assert_eq!(
+            len(&batch_transaction),
+            BatchTransaction::size(3, message.size())
+        );
+
+        let actions = vec![
+            SettingsAction::ChangeThreshold { new_threshold: 2 },
+            SettingsAction::SetArchivalAuthority {
+                new_archival_authority: Some(key(3)),
+            },
+        ];
+        let settings_transaction = SettingsTransaction {
+            discriminator: [0; 8],
+            settings: key(0),
+            creator: key(0),
+            rent_collector: key(0),
+            index: 0,
+            bump: 0,
+            actions: actions.clone(),
+        };
+        assert_eq!(
+            len(&settings_transaction),
+            SettingsTransaction::size(&actions)
+        );
+    }
+
+    #[test]
+    fn spending_limit_and_buffer_sizes_match_their_serialization() {
+        let spending_limit = SpendingLimit {
+            discriminator: [0; 8],
+            settings: key(0),
+            seed: key(0),
+            account_index: 0,
+            mint: key(0),
+            amount: 0,
+            period: Period::Day,
+            remaining_amount: 0,
+            last_reset: 0,
+            bump: 0,
+            signers: vec![key(1), key(2)],
+            destinations: vec![key(3)],
+            expiration: 0,
+        };
+        assert_eq!(len(&spending_limit), SpendingLimit::size(2, 1));
+
+        // The buffer is allocated for the whole message up front.
+        let buffer = TransactionBuffer {
+            discriminator: [0; 8],
+            settings: key(0),
+            creator: key(0),
+            buffer_index: 0,
+            account_index: 0,
+            final_buffer_hash: [0; 32],
+            final_buffer_size: 700,
+            buffer: vec![0; 700],
+        };
+        assert_eq!(len(&buffer), TransactionBuffer::size(700));
+    }
+}
@@ -0,0 +1,91 @@
+//! Rust client for the Astrolabe smart account program.
+//!
+//! `generated` is produced by codama from the program IDL and re-exported
+//! as-is; the remaining modules are hand-written helpers built on top of it.
+//!
+//! Modules that build, sign or send transactions need the default `client`
+//! feature. Without it the crate has no RPC or transaction dependencies and
+//! can be used from on-chain programs; the `cpi` feature adds Anchor
+//! `CpiContext` wrappers for invoking the program from another one.
+//!
+//! There is no adapter for `anchor_client::Program` yet: the only
+//! anchor-client available to build against is 0.29, which is built on the
+//! 1.x Solana SDK types and cannot share them with this crate's 2.x ones.
+//! The builders return plain `solana_instruction::Instruction`s, which
+//! anchor-client 0.31 accepts in `RequestBuilder::instruction`, and with
+//! the `fetch` feature the nonblocking `RpcClient` it wraps implements the
+//! transports of [`client::SmartAccountClient`].
+
+#[allow(deprecated, clippy::io_other_error)]
+mod generated;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod compute_budget;
+pub mod config_builder;
+#[cfg(feature = "cpi")]
+pub mod cpi;
+pub mod decode;
+#[cfg(feature = "client")]
+pub mod events;
+#[cfg(feature = "client")]
+pub mod export;
+pub mod filters;
+pub mod governance;
+pub mod layout;
+pub mod lifecycle;
+#[cfg(feature = "client")]
+pub mod lookup_table;
+#[cfg(feature = "client")]
+pub mod message;
+#[cfg(feature = "client")]
+pub mod mint;
+#[cfg(feature = "client")]
+pub mod mock;
+#[cfg(feature = "client")]
+pub mod nonce;
+pub mod offchain;
+#[cfg(feature = "client")]
+pub mod offline;
+#[cfg(feature = "client")]
+pub mod packing;
+pub mod pda;
+pub mod pnft;
+#[cfg(feature = "client")]
+pub mod preflight;
+#[cfg(feature = "client")]
+pub mod priority_fee;
+pub mod program_config;
+pub mod program_error;
+pub mod rent;
+#[cfg(feature = "client")]
+pub mod rpc;
+#[cfg(feature = "client")]
+pub mod scan;
+#[cfg(feature = "client")]
+pub mod sender;
+pub mod settings_diff;
+pub mod smart_account;
+#[cfg(feature = "client")]
+pub mod stake;
+#[cfg(feature = "client")]
+pub mod stake_pool;
+#[cfg(all(feature = "client", feature = "serde"))]
+pub mod state;
+#[cfg(feature = "client")]
+pub mod summary;
+#[cfg(feature = "client")]
+pub mod token;
+#[cfg(feature = "client")]
+pub mod transaction;
+#[cfg(feature = "client")]
+pub mod treasury;
+#[cfg(feature = "client")]
+pub mod upgrade;
+#[cfg(feature = "client")]
+pub mod vote;
+#[cfg(feature = "client")]
+pub mod watch;
+
+pub use generated::programs::ASTROLABE_SMART_ACCOUNT_ID as ID;
+pub use generated::*;
+pub use smart_account::{create_smart_account, SmartAccount};
@@ -0,0 +1,477 @@
+//! What can happen to a proposal next, by the program's rules.
+//!
+//! [`ProposalLifecycle`] answers the same questions the program asks before
+//! voting, executing or closing, and [`ProposalLifecycle::why_not`] returns
+//! the error the program would fail with. UIs can grey out buttons and bots
+//! can skip transactions that are bound to fail. Only regular signers take
+//! part in proposals; restricted signers are ignored.
+
+use solana_pubkey::Pubkey;
+
+use crate::accounts::{Proposal, Settings};
+use crate::decode::TransactionAccount;
+use crate::errors::AstrolabeSmartAccountError;
+use crate::types::{Permissions, ProposalStatus};
+
+impl Permissions {
+    pub const INITIATE: u8 = 1 << 0;
+    pub const VOTE: u8 = 1 << 1;
+    pub const EXECUTE: u8 = 1 << 2;
+
+    pub fn has(&self, permission: u8) -> bool {
+        self.mask & permission == permission
+    }
+}
+
+/// The kind of transaction a proposal belongs to, which decides whether a
+/// stale approved proposal can still be executed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TransactionKind {
+    Transaction,
+    SettingsTransaction,
+    Batch,
+}
+
+impl From<&TransactionAccount> for TransactionKind {
+    fn from(account: &TransactionAccount) -> Self {
+        match account {
+            TransactionAccount::Transaction(_) => Self::Transaction,
+            TransactionAccount::SettingsTransaction(_) => Self::SettingsTransaction,
+            TransactionAccount::Batch(_) => Self::Batch,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProposalAction {
+    Approve(Pubkey),
+    Reject(Pubkey),
+    Cancel(Pubkey),
+    /// Execute as `signer` at unix timestamp `now` on the cluster clock.
+    Execute {
+        signer: Pubkey,
+        now: i64,
+    },
+    Close,
+}
+
+/// Whether the program lets a transaction of `kind` be closed together
+/// with its proposal, which is `None` if it was never created.
+///
+/// Executed, rejected and cancelled proposals can be closed, and so can
+/// stale ones that were never approved. Stale vault transactions and
+/// batches stay executable once approved, so only stale approved settings
+/// transactions can be closed. A proposal that is executing never can.
+pub fn is_closable(kind: TransactionKind, status: Option<&ProposalStatus>, is_stale: bool) -> bool {
+    match status {
+        Some(
+            ProposalStatus::Executed { .. }
+            | ProposalStatus::Rejected { .. }
+            | ProposalStatus::Cancelled { .. },
+        ) => true,
+        Some(ProposalStatus::Approved { .. }) => {
+            is_stale && kind == TransactionKind::SettingsTransaction
+        }
+        Some(ProposalStatus::Executing) => false,
+        Some(ProposalStatus::Draft { .. } | ProposalStatus::Active { .. }) | None => is_stale,
+    }
+}
+
+pub struct ProposalLifecycle<'a> {
+    proposal: &'a Proposal,
+    settings: &'a Settings,
+    kind: TransactionKind,
+}
+
+impl<'a> ProposalLifecycle<'a> {
+    pub fn new(proposal: &'a Proposal, settings: &'a Settings, kind: TransactionKind) -> Self {
+        Self {
+            proposal,
+            settings,
+            kind,
+        }
+    }
+
+    /// Whether `signer` can approve or reject.
+    pub fn can_vote(&self, signer: &Pubkey) -> bool {
+        self.why_not(ProposalAction::Approve(*signer)).is_none()
+            || self.why_not(ProposalAction::Reject(*signer)).is_none()
+    }
+
+    pub fn can_cancel(&self, signer: &Pubkey) -> bool {
+        self.why_not(ProposalAction::Cancel(*signer)).is_none()
+    }
+
+    pub fn can_execute(&self, signer: &Pubkey, now: i64) -> bool {
+        self.why_not(ProposalAction::Execute {
+            signer: *signer,
+            now,
+        })
+        .is_none()
+    }
+
+    pub fn can_close(&self) -> bool {
+        self.why_not(ProposalAction::Close).is_none()
+    }
+
+    /// Unix timestamp from which the proposal can be executed, once it is
+    /// approved.
+    pub fn executable_at(&self) -> Option<i64> {
+        match self.proposal.status {
+            ProposalStatus::Approved { timestamp } => {
+                Some(timestamp.saturating_add(self.settings.time_lock as i64))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a settings change made this proposal stale.
+    pub fn is_stale(&self) -> bool {
+        self.proposal.transaction_index <= self.settings.stale_transaction_index
+    }
+
+    /// The error the program fails `action` with, or `None` if it succeeds.
+    pub fn why_not(&self, action: ProposalAction) -> Option<AstrolabeSmartAccountError> {
+        self.check(action).err()
+    }
+
+    fn check(&self, action: ProposalAction) -> Result<(), AstrolabeSmartAccountError> {
+        use AstrolabeSmartAccountError::*;
+
+        match action {
+            ProposalAction::Approve(signer) | ProposalAction::Reject(signer) => {
+                self.check_permission(&signer, Permissions::VOTE)?;
+                if !matches!(self.proposal.status, ProposalStatus::Active { .. }) {
+                    return Err(InvalidProposalStatus);
+                }
+                if self.is_stale() {
+                    return Err(StaleProposal);
+                }
+                match action {
+                    ProposalAction::Approve(_) if self.proposal.approved.contains(&signer) => {
+                        Err(AlreadyApproved)
+                    }
+                    ProposalAction::Reject(_) if self.proposal.rejected.contains(&signer) => {
+                        Err(AlreadyRejected)
+                    }
+                    _ => Ok(()),
+                }
+            }
+            ProposalAction::Cancel(signer) => {
+                self.check_permission(&signer, Permissions::VOTE)?;
+                if !matches!(self.proposal.status, ProposalStatus::Approved { .. }) {
+                    return Err(InvalidProposalStatus);
+                }
+                if self.proposal.cancelled.contains(&signer) {
+                    return Err(AlreadyCancelled);
+                }
+                Ok(())
+            }
+            ProposalAction::Execute { signer, now } => {
+                self.check_permission(&signer, Permissions::EXECUTE)?;
+                let executable_at = self.executable_at().ok_or(InvalidProposalStatus)?;
+                if self.is_stale() && self.kind == TransactionKind::SettingsTransaction {
+                    return Err(StaleProposal);
+                }
+                if now < executable_at {
+                    return Err(TimeLockNotReleased);
+                }
+                Ok(())
+            }
+            ProposalAction::Close => {
+                if !is_closable(self.kind, Some(&self.proposal.status), self.is_stale()) {
+                    return Err(InvalidProposalStatus);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_permission(
+        &self,
+        signer: &Pubkey,
+        permission: u8,
+    ) -> Result<(), AstrolabeSmartAccountError> {
+        let signer = self
+            .settings
+            .signers
+            .iter()
+            .find(|s| s.key == *signer)
+            .ok_or(AstrolabeSmartAccountError::NotASigner)?;
+        if !signer.permissions.has(permission) {
+            return Err(AstrolabeSmartAccountError::Unauthorized);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SmartAccountSigner;
+
+    const KINDS: [TransactionKind; 3] = [
+        TransactionKind::Transaction,
+        TransactionKind::SettingsTransaction,
+        TransactionKind::Batch,
+    ];
+
+    fn settings(stale_transaction_index: u64) -> Settings {
+        Settings {
+            discriminator: [0; 8],
+            seed: 0,
+            settings_authority: Pubkey::default(),
+            threshold: 1,
+            time_lock: 0,
+            transaction_index: 2,
+            stale_transaction_index,
+            archival_authority: None,
+            archivable_after: 0,
+            bump: 0,
+            signers: Vec::new(),
+            restricted_signers: Vec::new(),
+            account_utilization: 0,
+            reserved1: 0,
+            reserved2: 0,
+        }
+    }
+
+    fn proposal(status: ProposalStatus) -> Proposal {
+        Proposal {
+            discriminator: [0; 8],
+            settings: Pubkey::default(),
+            transaction_index: 2,
+            rent_collector: Pubkey::default(),
+            status,
+            bump: 0,
+            approved: Vec::new(),
+            rejected: Vec::new(),
+            cancelled: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn close_rule_covers_every_status_staleness_and_kind() {
+        // Closable when fresh, then when stale, per entry of `KINDS`.
+        let table: [(Option<ProposalStatus>, [bool; 3], [bool; 3]); 8] = [
+            (None, [false; 3], [true; 3]),
+            (
+                Some(ProposalStatus::Draft { timestamp: 0 }),
+                [false; 3],
+                [true; 3],
+            ),
+            (
+                Some(ProposalStatus::Active { timestamp: 0 }),
+                [false; 3],
+                [true; 3],
+            ),
+            (
+                Some(ProposalStatus::Rejected { timestamp: 0 }),
+                [true; 3],
+                [true; 3],
+            ),
+            (
+                Some(ProposalStatus::Approved { timestamp: 0 }),
+                [false; 3],
+                [false, true, false],
+            ),
+            (Some(ProposalStatus::Executing), [false; 3], [false; 3]),
+            (
+                Some(ProposalStatus::Executed { timestamp: 0 }),
+                [true; 3],
+                [true; 3],
+            ),
+            (
+                Some(ProposalStatus::Cancelled { timestamp: 0 }),
+                [true; 3],
+                [true; 3],
+            ),
+        ];
+        for (status, fresh, stale) in table {
+            for (i, kind) in KINDS.into_iter().enumerate() {
+                for (is_stale, expected) in [(false, fresh[i]), (true, stale[i])] {
+                    assert_eq!(
+                        is_closable(kind, status.as_ref(), is_stale),
+                        expected,
+                        "{:?} {:?} stale: {}",
+                        status,
+                        kind,
+                        is_stale
+                    );
+                    if let Some(status) = &status {
+                        let proposal = proposal(status.clone());
+                        let settings = settings(if is_stale { 2 } else { 1 });
+                        let lifecycle = ProposalLifecycle::new(&proposal, &settings, kind);
+                        assert_eq!(lifecycle.can_close(), expected);
+                    }
+                }
+            }
+        }
+    }
+
+    const VOTER: Pubkey = Pubkey::new_from_array([1; 32]);
+    const EXECUTOR: Pubkey = Pubkey::new_from_array([2; 32]);
+    const OUTSIDER: Pubkey = Pubkey::new_from_array([3; 32]);
+
+    /// Settings with a voter and an executor that cannot vote, and a time
+    /// lock of 100 seconds.
+    fn signed_settings(stale_transaction_index: u64) -> Settings {
+        Settings {
+            signers: vec![
+                SmartAccountSigner {
+                    key: VOTER,
+                    permissions: Permissions {
+                        mask: Permissions::INITIATE | Permissions::VOTE,
+                    },
+                },
+                SmartAccountSigner {
+                    key: EXECUTOR,
+                    permissions: Permissions {
+                        mask: Permissions::EXECUTE,
+                    },
+                },
+            ],
+            time_lock: 100,
+            ..settings(stale_transaction_index)
+        }
+    }
+
+    #[test]
+    fn votes_need_an_active_fresh_proposal_and_a_voter() {
+        use AstrolabeSmartAccountError::*;
+
+        let settings = signed_settings(1);
+        let active = proposal(ProposalStatus::Active { timestamp: 0 });
+        let lifecycle = ProposalLifecycle::new(&active, &settings, TransactionKind::Transaction);
+        assert_eq!(lifecycle.why_not(ProposalAction::Approve(VOTER)), None);
+        assert_eq!(lifecycle.why_not(ProposalAction::Reject(VOTER)), None);
+        assert!(lifecycle.can_vote(&VOTER));
+        assert_eq!(
+            lifecycle.why_not(ProposalAction::Approve(OUTSIDER)),
+            Some(NotASigner)
+        );
+        assert_eq!(
+            lifecycle.why_not(ProposalAction::Approve(EXECUTOR)),
+            Some(Unauthorized)
+        );
+
+        let voted = Proposal {
+            approved: vec![VOTER],
+            ..active.clone()
+        };
+        let lifecycle = ProposalLifecycle::new(&voted, &settings, TransactionKind::Transaction);
+        assert_eq!(
+            lifecycle.why_not(ProposalAction::Approve(VOTER)),
+            Some(AlreadyApproved)
+        );
+        // Changing a vote is allowed.
+        assert_eq!(lifecycle.why_not(ProposalAction::Reject(VOTER)), None);
+        let voted = Proposal {
+            rejected: vec![VOTER],
+            ..active.clone()
+        };
+        let lifecycle = ProposalLifecycle::new(&voted, &settings, TransactionKind::Transaction);
+        assert_eq!(
+            lifecycle.why_not(ProposalAction::Reject(VOTER)),
+            Some(AlreadyRejected)
+        );
+
+        let stale = signed_settings(2);
+        let lifecycle = ProposalLifecycle::new(&active, &stale, TransactionKind::Transaction);
+        assert_eq!(
+            lifecycle.why_not(ProposalAction::Approve(VOTER)),
+            Some(StaleProposal)
+        );
+        assert!(!lifecycle.can_vote(&VOTER));
+
+        for status in [
+            ProposalStatus::Draft { timestamp: 0 },
+            ProposalStatus::Approved { timestamp: 0 },
+            ProposalStatus::Rejected { timestamp: 0 },
+            ProposalStatus::Executing,
+            ProposalStatus::Executed { timestamp: 0 },
+            ProposalStatus::Cancelled { timestamp: 0 },
+        ] {
+            let proposal = proposal(status);
+            let lifecycle =
+                ProposalLifecycle::new(&proposal, &settings, TransactionKind::Transaction);
+            assert_eq!(
+                lifecycle.why_not(ProposalAction::Approve(VOTER)),
+                Some(InvalidProposalStatus)
+            );
+        }
+    }
+
+    #[test]
+    fn only_approved_proposals_can_be_cancelled() {
+        use AstrolabeSmartAccountError::*;
+
+        let settings = signed_settings(1);
+        let approved = proposal(ProposalStatus::Approved { timestamp: 0 });
+        let lifecycle = ProposalLifecycle::new(&approved, &settings, TransactionKind::Transaction);
+        assert!(lifecycle.can_cancel(&VOTER));
+        assert_eq!(
+            lifecycle.why_not(ProposalAction::Cancel(EXECUTOR)),
+            Some(Unauthorized)
+        );
+
+        let cancelled = Proposal {
+            cancelled: vec![VOTER],
+            ..approved
+        };
+        let lifecycle = ProposalLifecycle::new(&cancelled, &settings, TransactionKind::Transaction);
+        assert_eq!(
+            lifecycle.why_not(ProposalAction::Cancel(VOTER)),
+            Some(AlreadyCancelled)
+        );
+
+        let active = proposal(ProposalStatus::Active { timestamp: 0 });
+        let lifecycle = ProposalLifecycle::new(&active, &settings, TransactionKind::Transaction);
+        assert_eq!(
+            lifecycle.why_not(ProposalAction::Cancel(VOTER)),
+            Some(InvalidProposalStatus)
+        );
+    }
+
+    #[test]
+    fn execution_waits_for_approval_and_the_time_lock() {
+        use AstrolabeSmartAccountError::*;
+
+        let execute = |now| ProposalAction::Execute {
+            signer: EXECUTOR,
+            now,
+        };
+        let settings = signed_settings(1);
+        let approved = proposal(ProposalStatus::Approved { timestamp: 1_000 });
+        let lifecycle = ProposalLifecycle::new(&approved, &settings, TransactionKind::Transaction);
+        assert_eq!(lifecycle.executable_at(), Some(1_100));
+        assert_eq!(lifecycle.why_not(execute(1_099)), Some(TimeLockNotReleased));
+        assert!(lifecycle.can_execute(&EXECUTOR, 1_100));
+        assert_eq!(
+            lifecycle.why_not(ProposalAction::Execute {
+                signer: VOTER,
+                now: 1_100,
+            }),
+            Some(Unauthorized)
+        );
+
+        let active = proposal(ProposalStatus::Active { timestamp: 0 });
+        let lifecycle = ProposalLifecycle::new(&active, &settings, TransactionKind::Transaction);
+        assert_eq!(lifecycle.executable_at(), None);
+        assert_eq!(
+            lifecycle.why_not(execute(1_100)),
+            Some(InvalidProposalStatus)
+        );
+
+        // Staleness only stops settings transactions.
+        let stale = signed_settings(2);
+        for (kind, expected) in [
+            (TransactionKind::Transaction, None),
+            (TransactionKind::SettingsTransaction, Some(StaleProposal)),
+            (TransactionKind::Batch, None),
+        ] {
+            let lifecycle = ProposalLifecycle::new(&approved, &stale, kind);
+            assert_eq!(lifecycle.why_not(execute(1_100)), expected, "{:?}", kind);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Address lookup tables for executions too large for a legacy transaction.
+//!
+//! An `execute_transaction` instruction carries every account the stored
+//! message touches, which quickly outgrows the packet size. Loading those
+//! accounts from a lookup table owned by the executor keeps the outer v0
+//! transaction small. See [`crate::client::SmartAccountClient::execute_with_lookup_table`]
+//! for the whole workflow; transactions that load from a table are built
+//! with [`TransactionFormat::V0`](crate::transaction::TransactionFormat::V0).
+
+use std::io;
+
+use solana_address_lookup_table_interface::instruction::{
+    create_lookup_table, extend_lookup_table,
+};
+use solana_address_lookup_table_interface::state::AddressLookupTable;
+use solana_instruction::Instruction;
+use solana_message::AddressLookupTableAccount;
+use solana_pubkey::Pubkey;
+use solana_sdk_ids::sysvar;
+
+use crate::rpc::{fetch_multiple, AccountFetcher};
+
+/// Addresses per `extend_lookup_table`, small enough that the extension
+/// fits in one transaction alongside the table creation.
+pub const MAX_ADDRESSES_PER_EXTEND: usize = 20;
+
+/// A fetched lookup table with the slot it was last extended in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LookupTableState {
+    pub account: AddressLookupTableAccount,
+    pub last_extended_slot: u64,
+}
+
+impl LookupTableState {
+    /// Addresses are usable from the slot after they were added.
+    pub fn is_active(&self, current_slot: u64) -> bool {
+        current_slot > self.last_extended_slot
+    }
+}
+
+/// Accounts of `instruction` that can be loaded from a table: all but the
+/// signers, which must stay in the static keys.
+pub fn lookup_table_addresses(instruction: &Instruction) -> Vec<Pubkey> {
+    let mut addresses: Vec<Pubkey> = Vec::new();
+    for meta in &instruction.accounts {
+        if !meta.is_signer && !addresses.contains(&meta.pubkey) {
+            addresses.push(meta.pubkey);
+        }
+    }
+    addresses
+}
+
+/// Instruction batches that create a table controlled by `authority` and
+/// fill it with `addresses`, one transaction each, in order. The first
+/// batch creates the table. Returns the table address and the batches.
+pub fn create_lookup_table_instructions(
+    authority: Pubkey,
+    payer: Pubkey,
+    recent_slot: u64,
+    addresses: &[Pubkey],
+) -> (Pubkey, Vec<Vec<Instruction>>) {
+    let (create, table) = create_lookup_table(authority, payer, recent_slot);
+    let mut batches = vec![vec![create]];
+    for (i, chunk) in addresses.chunks(MAX_ADDRESSES_PER_EXTEND).enumerate() {
+        let extend = extend_lookup_table(table, authority, Some(payer), chunk.to_vec());
+        if i == 0 {
+            batches[0].push(extend);
+        } else {
+            batches.push(vec![extend]);
+        }
+    }
+    (table, batches)
+}
+
+/// Fetches the table at `address` together with the current slot.
+pub async fn fetch_lookup_table<R: AccountFetcher>(
+    rpc: &R,
+    address: &Pubkey,
+) -> Result<(LookupTableState, u64), io::Error> {
+    let mut accounts = fetch_multiple(rpc, &[*address, sysvar::clock::ID])
+        .await?
+        .into_iter();
+    let table = accounts.next().flatten().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Account not found: {}", address),
+        )
+    })?;
+    let clock = accounts
+        .next()
+        .flatten()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "clock sysvar not found"))?;
+    let table = AddressLookupTable::deserialize(&table.data).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not an address lookup table", address),
+        )
+    })?;
+    let state = LookupTableState {
+        account: AddressLookupTableAccount {
+            key: *address,
+            addresses: table.addresses.to_vec(),
+        },
+        last_extended_slot: table.meta.last_extended_slot,
+    };
+    Ok((state, clock_slot(&clock.data)?))
+}
+
+/// Current slot, read from the clock sysvar.
+pub async fn fetch_slot<R: AccountFetcher>(rpc: &R) -> Result<u64, io::Error> {
+    let clock = fetch_multiple(rpc, &[sysvar::clock::ID])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "clock sysvar not found"))?;
+    clock_slot(&clock.data)
+}
+
+fn clock_slot(clock: &[u8]) -> Result<u64, io::Error> {
+    clock
+        .get(..8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed clock sysvar"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use futures::executor::block_on;
+    use solana_address_lookup_table_interface::state::LookupTableMeta;
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+    use crate::mock::MockRpc;
+
+    #[test]
+    fn tables_are_created_and_filled_in_transaction_sized_batches() {
+        let (authority, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let addresses: Vec<Pubkey> = (0..45).map(|_| Pubkey::new_unique()).collect();
+        let (table, batches) = create_lookup_table_instructions(authority, payer, 9, &addresses);
+
+        assert_eq!(table, create_lookup_table(authority, payer, 9).1);
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1, 1]);
+        assert_eq!(batches[0][0], create_lookup_table(authority, payer, 9).0);
+        assert_eq!(
+            batches[2][0],
+            extend_lookup_table(table, authority, Some(payer), addresses[40..].to_vec())
+        );
+    }
+
+    #[test]
+    fn signers_stay_out_of_the_table() {
+        let (signer, account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let instruction = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(signer, true),
+                AccountMeta::new(account, false),
+                AccountMeta::new_readonly(account, false),
+            ],
+            data: Vec::new(),
+        };
+        assert_eq!(lookup_table_addresses(&instruction), [account]);
+    }
+
+    #[test]
+    fn fetched_tables_are_active_after_the_slot_they_were_extended_in() {
+        let rpc = MockRpc::new();
+        rpc.set_clock(100, 0);
+        let (address, entry) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let data = AddressLookupTable {
+            meta: LookupTableMeta {
+                last_extended_slot: 100,
+                ..LookupTableMeta::new(Pubkey::new_unique())
+            },
+            addresses: Cow::Owned(vec![entry]),
+        }
+        .serialize_for_tests()
+        .unwrap();
+        rpc.set_account(
+            address,
+            solana_account::Account {
+                lamports: 1,
+                data,
+                owner: solana_sdk_ids::address_lookup_table::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let (state, slot) = block_on(fetch_lookup_table(&rpc, &address)).unwrap();
+        assert_eq!(slot, 100);
+        assert_eq!(state.account.addresses, [entry]);
+        assert!(!state.is_active(slot));
+        assert!(state.is_active(101));
+        assert!(block_on(fetch_lookup_table(&rpc, &Pubkey::new_unique())).is_err());
+    }
+}
@@ -0,0 +1,438 @@
+//! Conversions between `SmartAccountTransactionMessage` and the solana-sdk
+//! message types.
+//!
+//! Messages can be built with the usual `solana_message` tooling and turned
+//! into the format stored in a transaction account, or decoded back for
+//! inspection. The smart account message has no blockhash, so conversions
+//! into a solana message leave `recent_blockhash` as `Hash::default()`.
+//!
+//! `execution_accounts` resolves the remaining accounts that
+//! `execute_transaction` needs for a stored message.
+
+use solana_hash::Hash;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_message::compiled_instruction::CompiledInstruction;
+use solana_message::v0::{self, MessageAddressTableLookup};
+use solana_message::{AddressLookupTableAccount, CompileError, Message, MessageHeader};
+use solana_pubkey::Pubkey;
+use thiserror::Error;
+
+use crate::types::{
+    SmartAccountCompiledInstruction, SmartAccountMessageAddressTableLookup,
+    SmartAccountTransactionMessage,
+};
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum MessageError {
+    #[error("message header does not match its account keys")]
+    InvalidHeader,
+    #[error("legacy messages cannot use address table lookups")]
+    AddressTableLookups,
+    #[error("address lookup table {0} was not provided")]
+    MissingAddressLookupTable(Pubkey),
+    #[error("address lookup table {0} has no address at index {1}")]
+    InvalidLookupIndex(Pubkey, u8),
+    #[error("instruction references account index {0}, which the message does not have")]
+    InvalidAccountIndex(u8),
+    #[error(transparent)]
+    Compile(#[from] CompileError),
+}
+
+impl SmartAccountTransactionMessage {
+    /// Compiles `instructions` into a message executed by `smart_account`,
+    /// which takes the fee payer slot and signs via the program.
+    pub fn try_compile(
+        smart_account: &Pubkey,
+        instructions: &[Instruction],
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+    ) -> Result<Self, MessageError> {
+        let message = v0::Message::try_compile(
+            smart_account,
+            instructions,
+            address_lookup_table_accounts,
+            Hash::default(),
+        )?;
+        Self::try_from(&message)
+    }
+
+    /// Remaining accounts for executing this message: the lookup tables
+    /// followed by [`Self::account_metas`].
+    pub fn execution_accounts(
+        &self,
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+    ) -> Result<Vec<AccountMeta>, MessageError> {
+        let mut accounts: Vec<AccountMeta> = self
+            .address_table_lookups
+            .iter()
+            .map(|lookup| AccountMeta::new_readonly(lookup.account_key, false))
+            .collect();
+        accounts.extend(self.account_metas(address_lookup_table_accounts)?);
+        Ok(accounts)
+    }
+
+    /// Every account the message references, in the order its instructions
+    /// index them: the static keys, then every writable and every readonly
+    /// address loaded from the tables. The smart account and ephemeral
+    /// signers are non-signers because the program signs for them.
+    pub fn account_metas(
+        &self,
+        address_lookup_table_accounts: &[AddressLookupTableAccount],
+    ) -> Result<Vec<AccountMeta>, MessageError> {
+        let tables = self
+            .address_table_lookups
+            .iter()
+            .map(|lookup| {
+                address_lookup_table_accounts
+                    .iter()
+                    .find(|table| table.key == lookup.account_key)
+                    .ok_or(MessageError::MissingAddressLookupTable(lookup.account_key))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut accounts: Vec<AccountMeta> = self
+            .account_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                if self.is_static_writable_index(i) {
+                    AccountMeta::new(*key, false)
+                } else {
+                    AccountMeta::new_readonly(*key, false)
+                }
+            })
+            .collect();
+        for (lookup, table) in self.address_table_lookups.iter().zip(&tables) {
+            for &index in &lookup.writable_indexes {
+                accounts.push(AccountMeta::new(lookup_address(table, index)?, false));
+            }
+        }
+        for (lookup, table) in self.address_table_lookups.iter().zip(&tables) {
+            for &index in &lookup.readonly_indexes {
+                accounts.push(AccountMeta::new_readonly(
+                    lookup_address(table, index)?,
+                    false,
+                ));
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn is_static_writable_index(&self, index: usize) -> bool {
+        let num_signers = self.num_signers as usize;
+        if index < num_signers {
+            index < self.num_writable_signers as usize
+        } else {
+            index - num_signers < self.num_writable_non_signers as usize
+        }
+    }
+}
+
+impl TryFrom<&Message> for SmartAccountTransactionMessage {
+    type Error = MessageError;
+
+    fn try_from(message: &Message) -> Result<Self, Self::Error> {
+        from_parts(
+            &message.header,
+            &message.account_keys,
+            &message.instructions,
+            &[],
+        )
+    }
+}
+
+impl TryFrom<&v0::Message> for SmartAccountTransactionMessage {
+    type Error = MessageError;
+
+    fn try_from(message: &v0::Message) -> Result<Self, Self::Error> {
+        from_parts(
+            &message.header,
+            &message.account_keys,
+            &message.instructions,
+            &message.address_table_lookups,
+        )
+    }
+}
+
+impl TryFrom<&SmartAccountTransactionMessage> for Message {
+    type Error = MessageError;
+
+    fn try_from(message: &SmartAccountTransactionMessage) -> Result<Self, Self::Error> {
+        if !message.address_table_lookups.is_empty() {
+            return Err(MessageError::AddressTableLookups);
+        }
+        Ok(Message {
+            header: header(message)?,
+            account_keys: message.account_keys.clone(),
+            recent_blockhash: Hash::default(),
+            instructions: compiled_instructions(message),
+        })
+    }
+}
+
+impl TryFrom<&SmartAccountTransactionMessage> for v0::Message {
+    type Error = MessageError;
+
+    fn try_from(message: &SmartAccountTransactionMessage) -> Result<Self, Self::Error> {
+        Ok(v0::Message {
+            header: header(message)?,
+            account_keys: message.account_keys.clone(),
+            recent_blockhash: Hash::default(),
+            instructions: compiled_instructions(message),
+            address_table_lookups: message
+                .address_table_lookups
+                .iter()
+                .map(|lookup| MessageAddressTableLookup {
+                    account_key: lookup.account_key,
+                    writable_indexes: lookup.writable_indexes.clone(),
+                    readonly_indexes: lookup.readonly_indexes.clone(),
+                })
+                .collect(),
+        })
+    }
+}
+
+fn lookup_address(table: &AddressLookupTableAccount, index: u8) -> Result<Pubkey, MessageError> {
+    table
+        .addresses
+        .get(index as usize)
+        .copied()
+        .ok_or(MessageError::InvalidLookupIndex(table.key, index))
+}
+
+fn from_parts(
+    header: &MessageHeader,
+    account_keys: &[Pubkey],
+    instructions: &[CompiledInstruction],
+    address_table_lookups: &[MessageAddressTableLookup],
+) -> Result<SmartAccountTransactionMessage, MessageError> {
+    let num_non_signers = account_keys
+        .len()
+        .checked_sub(header.num_required_signatures as usize)
+        .ok_or(MessageError::InvalidHeader)?;
+    let num_writable_signers = header
+        .num_required_signatures
+        .checked_sub(header.num_readonly_signed_accounts)
+        .ok_or(MessageError::InvalidHeader)?;
+    let num_writable_non_signers = num_non_signers
+        .checked_sub(header.num_readonly_unsigned_accounts as usize)
+        .and_then(|n| u8::try_from(n).ok())
+        .ok_or(MessageError::InvalidHeader)?;
+
+    Ok(SmartAccountTransactionMessage {
+        num_signers: header.num_required_signatures,
+        num_writable_signers,
+        num_writable_non_signers,
+        account_keys: account_keys.to_vec(),
+        instructions: instructions
+            .iter()
+            .map(|ix| SmartAccountCompiledInstruction {
+                program_id_index: ix.program_id_index,
+                account_indexes: ix.accounts.clone(),
+                data: ix.data.clone(),
+            })
+            .collect(),
+        address_table_lookups: address_table_lookups
+            .iter()
+            .map(|lookup| SmartAccountMessageAddressTableLookup {
+                account_key: lookup.account_key,
+                writable_indexes: lookup.writable_indexes.clone(),
+                readonly_indexes: lookup.readonly_indexes.clone(),
+            })
+            .collect(),
+    })
+}
+
+fn header(message: &SmartAccountTransactionMessage) -> Result<MessageHeader, MessageError> {
+    let num_readonly_signed_accounts = message
+        .num_signers
+        .checked_sub(message.num_writable_signers)
+        .ok_or(MessageError::InvalidHeader)?;
+    let num_readonly_unsigned_accounts = message
+        .account_keys
+        .len()
+        .checked_sub(message.num_signers as usize + message.num_writable_non_signers as usize)
+        .and_then(|n| u8::try_from(n).ok())
+        .ok_or(MessageError::InvalidHeader)?;
+
+    Ok(MessageHeader {
+        num_required_signatures: message.num_signers,
+        num_readonly_signed_accounts,
+        num_readonly_unsigned_accounts,
+    })
+}
+
+fn compiled_instructions(message: &SmartAccountTransactionMessage) -> Vec<CompiledInstruction> {
+    message
+        .instructions
+        .iter()
+        .map(|ix| CompiledInstruction {
+            program_id_index: ix.program_id_index,
+            accounts: ix.account_indexes.clone(),
+            data: ix.data.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instructions(vault: &Pubkey, loaded: &[Pubkey]) -> Vec<Instruction> {
+        let program = Pubkey::new_unique();
+        let mut accounts = vec![
+            AccountMeta::new(*vault, true),
+            AccountMeta::new(Pubkey::new_unique(), false),
+            AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        ];
+        // Alternately writable and readonly.
+        accounts.extend(loaded.iter().enumerate().map(|(i, key)| {
+            if i % 2 == 0 {
+                AccountMeta::new(*key, false)
+            } else {
+                AccountMeta::new_readonly(*key, false)
+            }
+        }));
+        vec![
+            Instruction::new_with_bytes(program, &[1, 2], accounts),
+            Instruction::new_with_bytes(program, &[3], vec![AccountMeta::new(*vault, true)]),
+        ]
+    }
+
+    #[test]
+    fn legacy_messages_round_trip() {
+        let vault = Pubkey::new_unique();
+        let legacy = Message::new(&instructions(&vault, &[]), Some(&vault));
+        let message = SmartAccountTransactionMessage::try_from(&legacy).unwrap();
+        assert_eq!(message.account_keys[0], vault);
+        assert_eq!(message.num_signers, 1);
+        assert_eq!(message.num_writable_signers, 1);
+        assert_eq!(message.num_writable_non_signers, 1);
+        assert_eq!(Message::try_from(&message).unwrap(), legacy);
+        assert_eq!(
+            SmartAccountTransactionMessage::try_compile(&vault, &instructions(&vault, &[]), &[])
+                .unwrap()
+                .address_table_lookups,
+            []
+        );
+    }
+
+    #[test]
+    fn v0_messages_with_lookup_tables_round_trip() {
+        let vault = Pubkey::new_unique();
+        let loaded = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique(), loaded[1], loaded[0]],
+        };
+        let instructions = instructions(&vault, &loaded);
+        let v0 = v0::Message::try_compile(
+            &vault,
+            &instructions,
+            std::slice::from_ref(&table),
+            Hash::default(),
+        )
+        .unwrap();
+        let message = SmartAccountTransactionMessage::try_from(&v0).unwrap();
+        assert_eq!(
+            message,
+            SmartAccountTransactionMessage::try_compile(
+                &vault,
+                &instructions,
+                std::slice::from_ref(&table)
+            )
+            .unwrap()
+        );
+        assert_eq!(message.address_table_lookups.len(), 1);
+        assert_eq!(message.address_table_lookups[0].writable_indexes, [2]);
+        assert_eq!(message.address_table_lookups[0].readonly_indexes, [1]);
+        assert_eq!(v0::Message::try_from(&message).unwrap(), v0);
+
+        assert_eq!(
+            Message::try_from(&message),
+            Err(MessageError::AddressTableLookups)
+        );
+    }
+
+    #[test]
+    fn inconsistent_headers_are_rejected() {
+        let mut legacy = Message::new(&instructions(&Pubkey::new_unique(), &[]), None);
+        legacy.header.num_required_signatures = legacy.account_keys.len() as u8 + 1;
+        assert_eq!(
+            SmartAccountTransactionMessage::try_from(&legacy),
+            Err(MessageError::InvalidHeader)
+        );
+
+        let mut message = SmartAccountTransactionMessage::try_from(&Message::new(
+            &instructions(&Pubkey::new_unique(), &[]),
+            None,
+        ))
+        .unwrap();
+        message.num_writable_signers = message.num_signers + 1;
+        assert_eq!(
+            Message::try_from(&message),
+            Err(MessageError::InvalidHeader)
+        );
+    }
+
+    #[test]
+    fn execution_accounts_list_tables_static_keys_then_lookups() {
+        let [vault, ephemeral, destination, program] = [(); 4].map(|_| Pubkey::new_unique());
+        let table_a = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: (0..2).map(|_| Pubkey::new_unique()).collect(),
+        };
+        let table_b = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: (0..3).map(|_| Pubkey::new_unique()).collect(),
+        };
+        let message = SmartAccountTransactionMessage {
+            num_signers: 2,
+            num_writable_signers: 1,
+            num_writable_non_signers: 1,
+            account_keys: vec![vault, ephemeral, destination, program],
+            instructions: Vec::new(),
+            address_table_lookups: vec![
+                SmartAccountMessageAddressTableLookup {
+                    account_key: table_a.key,
+                    writable_indexes: vec![1],
+                    readonly_indexes: vec![0],
+                },
+                SmartAccountMessageAddressTableLookup {
+                    account_key: table_b.key,
+                    writable_indexes: vec![0],
+                    readonly_indexes: vec![2],
+                },
+            ],
+        };
+        let tables = [table_b.clone(), table_a.clone()];
+
+        assert_eq!(
+            message.execution_accounts(&tables).unwrap(),
+            [
+                AccountMeta::new_readonly(table_a.key, false),
+                AccountMeta::new_readonly(table_b.key, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(ephemeral, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new_readonly(program, false),
+                AccountMeta::new(table_a.addresses[1], false),
+                AccountMeta::new(table_b.addresses[0], false),
+                AccountMeta::new_readonly(table_a.addresses[0], false),
+                AccountMeta::new_readonly(table_b.addresses[2], false),
+            ]
+        );
+
+        assert_eq!(
+            message.execution_accounts(&tables[..1]),
+            Err(MessageError::MissingAddressLookupTable(table_a.key))
+        );
+        let short_b = AddressLookupTableAccount {
+            addresses: table_b.addresses[..2].to_vec(),
+            ..table_b.clone()
+        };
+        assert_eq!(
+            message.execution_accounts(&[table_a, short_b]),
+            Err(MessageError::InvalidLookupIndex(table_b.key, 2))
+        );
+    }
+}
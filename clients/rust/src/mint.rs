@@ -0,0 +1,320 @@
+//! Issuance of mints whose mint authority is a vault.
+//!
+//! [`VaultMintAuthority`] builds the SPL Token instructions that mint
+//! tokens and hand over or drop the mint and freeze authorities. The vault
+//! is the only signer of each instruction, so they go into a vault
+//! transaction as they are. Tokens are minted with `MintToChecked`, which
+//! carries the decimals, so [`crate::summary`] shows approvers the amount in
+//! whole tokens.
+//!
+//! Caps and schedules are checked when a proposal is built, by
+//! [`VaultMintAuthority::mint_to_capped`] and [`EmissionSchedule`]. The
+//! program executes whatever was approved, so approvers should check a
+//! mint proposal against the cap or schedule the project announced, and a
+//! time lock on the smart account gives them the time to.
+
+use std::io;
+
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+use thiserror::Error;
+
+use crate::pda;
+use crate::smart_account::TOKEN_PROGRAM_ID;
+use crate::token::{set_authority, AuthorityType};
+
+/// Size of a mint without Token-2022 extensions.
+const MINT_SIZE: usize = 82;
+/// `MintToChecked` in the token program's instruction enum.
+const MINT_TO_CHECKED: u8 = 14;
+
+/// The state of a mint, from its account data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Mint {
+    pub mint_authority: Option<Pubkey>,
+    pub supply: u64,
+    pub decimals: u8,
+    pub freeze_authority: Option<Pubkey>,
+}
+
+impl Mint {
+    /// Reads a mint of the token program or Token-2022, whose extensions
+    /// follow the same 82 bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not an initialized mint");
+        let data = data.get(..MINT_SIZE).ok_or_else(invalid)?;
+        if data[45] != 1 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            mint_authority: option_pubkey(&data[..36]).ok_or_else(invalid)?,
+            supply: u64::from_le_bytes(data[36..44].try_into().unwrap()),
+            decimals: data[44],
+            freeze_authority: option_pubkey(&data[46..82]).ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// A `COption<Pubkey>` as the token program stores it: a four byte tag,
+/// then the key, zeroed if there is none.
+fn option_pubkey(data: &[u8]) -> Option<Option<Pubkey>> {
+    match data[..4] {
+        [0, 0, 0, 0] => Some(None),
+        [1, 0, 0, 0] => Some(Some(Pubkey::try_from(&data[4..36]).unwrap())),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum MintError {
+    #[error("the mint authority is {0:?}, not the vault")]
+    NotMintAuthority(Option<Pubkey>),
+    #[error("minting {amount} onto a supply of {supply} exceeds the cap of {cap}")]
+    CapExceeded { supply: u64, amount: u64, cap: u64 },
+}
+
+/// See the [module documentation](self).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VaultMintAuthority {
+    vault: Pubkey,
+    mint: Pubkey,
+    decimals: u8,
+    token_program: Pubkey,
+}
+
+impl VaultMintAuthority {
+    /// `mint`, with `decimals`, under the vault of `settings` at
+    /// `account_index` and the token program.
+    pub fn new(settings: &Pubkey, account_index: u8, mint: Pubkey, decimals: u8) -> Self {
+        Self {
+            vault: pda::smart_account(settings, account_index).0,
+            mint,
+            decimals,
+            token_program: TOKEN_PROGRAM_ID,
+        }
+    }
+
+    /// For mints of another token program, e.g. Token-2022.
+    pub fn with_token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        self.vault
+    }
+
+    /// Mints `amount` base units to `destination`, a token account of the
+    /// mint.
+    pub fn mint_to(&self, destination: &Pubkey, amount: u64) -> Instruction {
+        let mut data = vec![MINT_TO_CHECKED];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.push(self.decimals);
+        Instruction {
+            program_id: self.token_program,
+            accounts: vec![
+                AccountMeta::new(self.mint, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(self.vault, true),
+            ],
+            data,
+        }
+    }
+
+    /// [`Self::mint_to`], unless the vault is not `mint`'s authority or the
+    /// supply would end up above `cap`. `mint` is the current state of the
+    /// mint, e.g. fetched just before the proposal is created.
+    pub fn mint_to_capped(
+        &self,
+        destination: &Pubkey,
+        amount: u64,
+        mint: &Mint,
+        cap: u64,
+    ) -> Result<Instruction, MintError> {
+        if mint.mint_authority != Some(self.vault) {
+            return Err(MintError::NotMintAuthority(mint.mint_authority));
+        }
+        if mint
+            .supply
+            .checked_add(amount)
+            .is_none_or(|supply| supply > cap)
+        {
+            return Err(MintError::CapExceeded {
+                supply: mint.supply,
+                amount,
+                cap,
+            });
+        }
+        Ok(self.mint_to(destination, amount))
+    }
+
+    /// Hands minting to `new_authority`, or fixes the supply for good with
+    /// `None`.
+    pub fn set_mint_authority(&self, new_authority: Option<&Pubkey>) -> Instruction {
+        set_authority(
+            &self.token_program,
+            &self.mint,
+            &self.vault,
+            AuthorityType::MintTokens,
+            new_authority,
+        )
+    }
+
+    /// Hands the freeze authority to `new_authority`, or drops it with
+    /// `None`. The vault must hold it.
+    pub fn set_freeze_authority(&self, new_authority: Option<&Pubkey>) -> Instruction {
+        set_authority(
+            &self.token_program,
+            &self.mint,
+            &self.vault,
+            AuthorityType::FreezeAccount,
+            new_authority,
+        )
+    }
+}
+
+/// A fixed amount released every period, starting at `start`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EmissionSchedule {
+    /// Unix timestamp of the first release.
+    pub start: i64,
+    /// Seconds between releases.
+    pub period: i64,
+    /// Base units released each period.
+    pub amount: u64,
+    pub periods: u32,
+}
+
+impl EmissionSchedule {
+    /// Base units released up to unix timestamp `now`, on the cluster clock.
+    pub fn released(&self, now: i64) -> u64 {
+        if now < self.start || self.period <= 0 {
+            return 0;
+        }
+        let elapsed = (now.saturating_sub(self.start) / self.period).saturating_add(1);
+        let periods = elapsed.min(self.periods.into()) as u64;
+        periods.saturating_mul(self.amount)
+    }
+
+    /// Base units released up to `now` that are not among the `minted` so
+    /// far under the schedule.
+    pub fn due(&self, now: i64, minted: u64) -> u64 {
+        self.released(now).saturating_sub(minted)
+    }
+
+    /// The supply once every period is released, on top of `initial_supply`;
+    /// a cap for [`VaultMintAuthority::mint_to_capped`].
+    pub fn cap(&self, initial_supply: u64) -> u64 {
+        initial_supply.saturating_add(self.amount.saturating_mul(self.periods.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::summarize;
+    use crate::types::SmartAccountTransactionMessage;
+
+    fn mint_data(mint_authority: Option<Pubkey>, supply: u64) -> Vec<u8> {
+        let mut data = vec![0; MINT_SIZE];
+        if let Some(authority) = mint_authority {
+            data[..4].copy_from_slice(&[1, 0, 0, 0]);
+            data[4..36].copy_from_slice(authority.as_ref());
+        }
+        data[36..44].copy_from_slice(&supply.to_le_bytes());
+        data[44] = 6;
+        data[45] = 1;
+        data
+    }
+
+    #[test]
+    fn mint_to_capped_checks_the_authority_and_the_cap() {
+        let authority = VaultMintAuthority::new(&Pubkey::new_unique(), 0, Pubkey::new_unique(), 6);
+        let destination = Pubkey::new_unique();
+        let mint = Mint::from_bytes(&mint_data(Some(authority.vault()), 900)).unwrap();
+        assert_eq!(
+            mint,
+            Mint {
+                mint_authority: Some(authority.vault()),
+                supply: 900,
+                decimals: 6,
+                freeze_authority: None,
+            }
+        );
+
+        assert_eq!(
+            authority.mint_to_capped(&destination, 100, &mint, 1_000),
+            Ok(authority.mint_to(&destination, 100))
+        );
+        assert_eq!(
+            authority.mint_to_capped(&destination, 101, &mint, 1_000),
+            Err(MintError::CapExceeded {
+                supply: 900,
+                amount: 101,
+                cap: 1_000
+            })
+        );
+        assert!(authority
+            .mint_to_capped(&destination, u64::MAX, &mint, u64::MAX)
+            .is_err());
+        let other = Mint::from_bytes(&mint_data(None, 0)).unwrap();
+        assert_eq!(
+            authority.mint_to_capped(&destination, 1, &other, 1_000),
+            Err(MintError::NotMintAuthority(None))
+        );
+
+        assert!(Mint::from_bytes(&[0; MINT_SIZE]).is_err());
+        assert!(Mint::from_bytes(&mint_data(None, 0)[..81]).is_err());
+    }
+
+    #[test]
+    fn emission_schedule_releases_once_per_period() {
+        let schedule = EmissionSchedule {
+            start: 1_000,
+            period: 100,
+            amount: 5,
+            periods: 3,
+        };
+        assert_eq!(schedule.released(999), 0);
+        assert_eq!(schedule.released(1_000), 5);
+        assert_eq!(schedule.released(1_099), 5);
+        assert_eq!(schedule.released(1_100), 10);
+        assert_eq!(schedule.released(i64::MAX), 15);
+        assert_eq!(schedule.due(1_150, 5), 5);
+        assert_eq!(schedule.due(1_150, 10), 0);
+        assert_eq!(schedule.cap(100), 115);
+    }
+
+    #[test]
+    fn approvers_see_what_is_minted() {
+        let authority = VaultMintAuthority::new(&Pubkey::new_unique(), 1, Pubkey::new_unique(), 6);
+        let destination = Pubkey::new_unique();
+        let instructions = [
+            authority.mint_to(&destination, 2_500_000),
+            authority.set_mint_authority(None),
+            authority.set_freeze_authority(Some(&destination)),
+        ];
+        assert_eq!(instructions[0].data, [14, 160, 37, 38, 0, 0, 0, 0, 0, 6]);
+        let message =
+            SmartAccountTransactionMessage::try_compile(&authority.vault(), &instructions, &[])
+                .unwrap();
+        assert_eq!(message.num_signers, 1);
+
+        let summaries: Vec<String> = summarize(&message, &[])
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                format!("Mint 2.5 of {} to {}", authority.mint, destination),
+                format!("Remove the mint authority of {}", authority.mint),
+                format!(
+                    "Set the freeze authority of {} to {}",
+                    authority.mint, destination
+                ),
+            ]
+        );
+    }
+}
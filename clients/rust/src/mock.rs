@@ -0,0 +1,359 @@
+//! An in-memory transport for testing code built on this crate.
+//!
+//! [`MockRpc`] implements [`AccountFetcher`], [`TransactionSender`],
+//! [`TransactionTransport`], [`ProgramAccountScanner`] and
+//! [`TransactionSimulator`] over accounts and outcomes set up in advance,
+//! so governance flows can be unit-tested without a validator. Sending
+//! never changes accounts; tests update them between steps to model the
+//! program. Every call is synchronous and `sleep` returns at once, so any
+//! executor can drive it.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use borsh::BorshSerialize;
+use solana_account::Account;
+use solana_hash::Hash;
+use solana_pubkey::Pubkey;
+use solana_sdk_ids::sysvar;
+use solana_signature::Signature;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction_error::TransactionError;
+
+use crate::filters::Filter;
+use crate::preflight::{Simulation, TransactionSimulator};
+use crate::rpc::{AccountFetcher, TransactionSender};
+use crate::scan::ProgramAccountScanner;
+use crate::sender::{Commitment, SendError, SendOptions, SignatureStatus, TransactionTransport};
+
+/// Blocks a mock blockhash stays valid for.
+const BLOCKHASH_VALIDITY: u64 = 150;
+
+/// What happens to the next transaction sent.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MockOutcome {
+    /// Lands and succeeds.
+    Success,
+    /// Lands and fails with the error.
+    Failed(TransactionError),
+    /// Fails preflight with the error and never lands.
+    Rejected(TransactionError),
+    /// Is accepted but never lands, so it eventually expires.
+    Dropped,
+    /// Never reaches the cluster: the send fails with a network error.
+    /// Sending the same transaction again takes the next outcome.
+    NetworkError,
+}
+
+#[derive(Default)]
+struct State {
+    accounts: HashMap<Pubkey, Account>,
+    outcomes: VecDeque<MockOutcome>,
+    statuses: HashMap<Signature, Option<TransactionError>>,
+    dropped: HashSet<Signature>,
+    sent: Vec<VersionedTransaction>,
+    prioritization_fees: Vec<u64>,
+    units_consumed: Option<u64>,
+    slot: u64,
+    block_height: u64,
+}
+
+/// See the [module documentation](self).
+#[derive(Default)]
+pub struct MockRpc {
+    state: Mutex<State>,
+}
+
+impl MockRpc {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_account(&self, address: Pubkey, account: Account) {
+        self.state().accounts.insert(address, account);
+    }
+
+    /// Stores `value` as an account owned by the program, serialized the way
+    /// the program writes it. Generated account structs include their
+    /// discriminator, so they can be passed as they are.
+    pub fn set_program_account<T: BorshSerialize>(&self, address: Pubkey, value: &T) {
+        let data = borsh::to_vec(value).expect("in-memory serialization cannot fail");
+        self.set_program_data(address, data);
+    }
+
+    /// Like [`Self::set_program_account`], zero-padded to `space` bytes the
+    /// way the program allocates settings and proposals, see
+    /// [`crate::layout`].
+    pub fn set_program_account_with_space<T: BorshSerialize>(
+        &self,
+        address: Pubkey,
+        value: &T,
+        space: usize,
+    ) {
+        let mut data = borsh::to_vec(value).expect("in-memory serialization cannot fail");
+        assert!(
+            data.len() <= space,
+            "account does not fit in {} bytes",
+            space
+        );
+        data.resize(space, 0);
+        self.set_program_data(address, data);
+    }
+
+    fn set_program_data(&self, address: Pubkey, data: Vec<u8>) {
+        self.set_account(
+            address,
+            Account {
+                lamports: crate::rent::rent(data.len()),
+                data,
+                owner: crate::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    pub fn remove_account(&self, address: &Pubkey) -> Option<Account> {
+        self.state().accounts.remove(address)
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Option<Account> {
+        self.state().accounts.get(address).cloned()
+    }
+
+    /// Sets the clock sysvar, which flows read the slot and time from.
+    pub fn set_clock(&self, slot: u64, unix_timestamp: i64) {
+        let mut data = Vec::with_capacity(40);
+        data.extend_from_slice(&slot.to_le_bytes());
+        // epoch_start_timestamp, epoch and leader_schedule_epoch.
+        data.extend_from_slice(&[0; 24]);
+        data.extend_from_slice(&unix_timestamp.to_le_bytes());
+        self.state().slot = slot;
+        self.set_account(
+            sysvar::clock::ID,
+            Account {
+                lamports: 1,
+                data,
+                owner: sysvar::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    pub fn set_block_height(&self, block_height: u64) {
+        self.state().block_height = block_height;
+    }
+
+    pub fn block_height(&self) -> u64 {
+        self.state().block_height
+    }
+
+    /// Queues the outcome of the next transaction sent. Transactions sent
+    /// with nothing queued succeed.
+    pub fn push_outcome(&self, outcome: MockOutcome) {
+        self.state().outcomes.push_back(outcome);
+    }
+
+    /// Recent prioritization fees, reported for any accounts.
+    pub fn set_prioritization_fees(&self, fees: Vec<u64>) {
+        self.state().prioritization_fees = fees;
+    }
+
+    /// Compute units simulations report as used.
+    pub fn set_units_consumed(&self, units_consumed: Option<u64>) {
+        self.state().units_consumed = units_consumed;
+    }
+
+    /// Every transaction submitted, rebroadcasts included, in order.
+    pub fn sent_transactions(&self) -> Vec<VersionedTransaction> {
+        self.state().sent.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The blockhash of the current block height, so that a new height gives
+    /// a new blockhash and re-signed transactions get new signatures.
+    fn blockhash(block_height: u64) -> Hash {
+        let mut bytes = [0; 32];
+        bytes[..8].copy_from_slice(&block_height.to_le_bytes());
+        Hash::new_from_array(bytes)
+    }
+
+    /// Records `transaction` and applies the next outcome, unless it was
+    /// sent before, in which case it is a rebroadcast and keeps its outcome.
+    fn submit(&self, transaction: &VersionedTransaction) -> Result<Signature, SendError> {
+        let mut state = self.state();
+        state.sent.push(transaction.clone());
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+        if state.statuses.contains_key(&signature) || state.dropped.contains(&signature) {
+            return Ok(signature);
+        }
+        match state.outcomes.pop_front().unwrap_or(MockOutcome::Success) {
+            MockOutcome::Success => {
+                state.statuses.insert(signature, None);
+            }
+            MockOutcome::Failed(error) => {
+                state.statuses.insert(signature, Some(error));
+            }
+            MockOutcome::Rejected(error) => {
+                return Err(SendError::Transaction {
+                    signature: None,
+                    error,
+                })
+            }
+            MockOutcome::Dropped => {
+                state.dropped.insert(signature);
+            }
+            MockOutcome::NetworkError => {
+                return Err(SendError::Network(io::Error::new(
+                    io::ErrorKind::ConnectionReset,
+                    "mock network error",
+                )))
+            }
+        }
+        Ok(signature)
+    }
+}
+
+impl AccountFetcher for MockRpc {
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<Option<Account>>, io::Error> {
+        let state = self.state();
+        Ok(addresses
+            .iter()
+            .map(|address| state.accounts.get(address).cloned())
+            .collect())
+    }
+}
+
+/// Scans see the accounts owned by the program, at the clock's slot.
+impl ProgramAccountScanner for MockRpc {
+    async fn get_program_accounts(
+        &self,
+        filters: &[Filter],
+    ) -> Result<(u64, Vec<(Pubkey, Account)>), io::Error> {
+        let state = self.state();
+        let accounts = state
+            .accounts
+            .iter()
+            .filter(|(_, account)| {
+                account.owner == crate::ID
+                    && filters.iter().all(|filter| filter.matches(&account.data))
+            })
+            .map(|(address, account)| (*address, account.clone()))
+            .collect();
+        Ok((state.slot, accounts))
+    }
+}
+
+/// Simulations fail with the error of the next queued outcome, if it fails,
+/// without taking it from the queue.
+impl TransactionSimulator for MockRpc {
+    async fn simulate_transaction(
+        &self,
+        _transaction: &VersionedTransaction,
+    ) -> Result<Simulation, io::Error> {
+        let state = self.state();
+        let error = match state.outcomes.front() {
+            Some(MockOutcome::Failed(error) | MockOutcome::Rejected(error)) => Some(error.clone()),
+            _ => None,
+        };
+        Ok(Simulation {
+            error,
+            units_consumed: state.units_consumed,
+            logs: Vec::new(),
+        })
+    }
+}
+
+/// Dropped transactions fail at once with an expiry error instead of
+/// waiting out the blockhash. `sleep` advances the block height by one, so
+/// a transaction signed again after it gets a new blockhash.
+impl TransactionSender for MockRpc {
+    async fn get_latest_blockhash(&self) -> Result<Hash, io::Error> {
+        Ok(Self::blockhash(self.block_height()))
+    }
+
+    async fn send_and_confirm_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<Signature, SendError> {
+        let signature = self.submit(transaction)?;
+        match self.state().statuses.get(&signature) {
+            Some(None) => Ok(signature),
+            Some(Some(error)) => Err(SendError::Transaction {
+                signature: Some(signature),
+                error: error.clone(),
+            }),
+            None => Err(SendError::Expired(signature)),
+        }
+    }
+
+    async fn sleep(&self, _duration: Duration) {
+        self.state().block_height += 1;
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        _writable_accounts: &[Pubkey],
+    ) -> Result<Vec<u64>, io::Error> {
+        Ok(self.state().prioritization_fees.clone())
+    }
+}
+
+/// Every landed transaction is finalized. `sleep` advances the block height
+/// by one, so dropped transactions expire after 150 sleeps.
+impl TransactionTransport for MockRpc {
+    async fn get_latest_blockhash(
+        &self,
+        _commitment: Commitment,
+    ) -> Result<(Hash, u64), io::Error> {
+        let block_height = self.block_height();
+        Ok((
+            Self::blockhash(block_height),
+            block_height + BLOCKHASH_VALIDITY,
+        ))
+    }
+
+    async fn get_block_height(&self, _commitment: Commitment) -> Result<u64, io::Error> {
+        Ok(self.block_height())
+    }
+
+    async fn send_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        _options: SendOptions,
+    ) -> Result<Signature, SendError> {
+        self.submit(transaction)
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<SignatureStatus>, io::Error> {
+        let state = self.state();
+        Ok(state.statuses.get(signature).map(|err| SignatureStatus {
+            slot: state.slot,
+            commitment: Some(Commitment::Finalized),
+            err: err.clone(),
+        }))
+    }
+
+    async fn sleep(&self, _duration: Duration) {
+        self.state().block_height += 1;
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        _writable_accounts: &[Pubkey],
+    ) -> Result<Vec<u64>, io::Error> {
+        Ok(self.state().prioritization_fees.clone())
+    }
+}
@@ -0,0 +1,232 @@
+//! Durable nonce accounts for signing ceremonies that outlast a blockhash.
+//!
+//! A vote or execute transaction built on a recent blockhash expires within
+//! minutes, too soon for signers spread across time zones. Built on a
+//! durable nonce with [`NonceAccount::approve`] or
+//! [`NonceAccount::execute`], it stays valid until the nonce is advanced,
+//! and can go through [`crate::offline`] signing at each signer's pace.
+//!
+//! The nonce authority must sign every such transaction, so it has to be a
+//! key. Once a ceremony is over, [`authorize_to_vault`] hands the nonce
+//! account to a vault of the smart account; from then on it can only be
+//! advanced, withdrawn from or handed back by a smart account transaction.
+
+use std::io;
+
+use solana_hash::Hash;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_nonce::state::State;
+use solana_nonce::versions::Versions;
+use solana_pubkey::Pubkey;
+use solana_system_interface::instruction::{authorize_nonce_account, create_nonce_account};
+
+use crate::offline::UnsignedTransaction;
+use crate::pda;
+use crate::rpc::{fetch_multiple, AccountFetcher};
+use crate::smart_account::SmartAccount;
+
+/// Size of a nonce account.
+pub const NONCE_ACCOUNT_SIZE: usize = State::size();
+
+/// Rent-exempt minimum of a nonce account, by [`crate::rent::rent`].
+pub fn nonce_account_rent() -> u64 {
+    crate::rent::rent(NONCE_ACCOUNT_SIZE)
+}
+
+/// Instructions that create a nonce account at `nonce_account`, which must
+/// sign, controlled by `authority`. `lamports` must keep it rent exempt,
+/// e.g. the cluster's minimum balance for [`NONCE_ACCOUNT_SIZE`] or
+/// [`nonce_account_rent`].
+pub fn create_nonce_account_instructions(
+    payer: &Pubkey,
+    nonce_account: &Pubkey,
+    authority: &Pubkey,
+    lamports: u64,
+) -> Vec<Instruction> {
+    create_nonce_account(payer, nonce_account, authority, lamports)
+}
+
+/// Hands `nonce_account` from `authority` to the vault of `settings` at
+/// `account_index`.
+pub fn authorize_to_vault(
+    nonce_account: &Pubkey,
+    authority: &Pubkey,
+    settings: &Pubkey,
+    account_index: u8,
+) -> Instruction {
+    let vault = pda::smart_account(settings, account_index).0;
+    authorize_nonce_account(nonce_account, authority, &vault)
+}
+
+/// An initialized nonce account.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NonceAccount {
+    pub address: Pubkey,
+    pub authority: Pubkey,
+    /// The value that stands in for the recent blockhash.
+    pub nonce: Hash,
+}
+
+impl NonceAccount {
+    pub fn from_bytes(address: Pubkey, data: &[u8]) -> Result<Self, io::Error> {
+        let versions: Versions = bincode::deserialize(data).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a nonce account: {}", address, e),
+            )
+        })?;
+        match versions.state() {
+            State::Initialized(data) => Ok(Self {
+                address,
+                authority: data.authority,
+                nonce: data.blockhash(),
+            }),
+            State::Uninitialized => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("nonce account {} is not initialized", address),
+            )),
+        }
+    }
+
+    /// Whether the authority is a vault of `settings`, so the nonce is no
+    /// longer usable for ceremonies.
+    pub fn is_vault_authority(&self, settings: &Pubkey, account_index: u8) -> bool {
+        self.authority == pda::smart_account(settings, account_index).0
+    }
+
+    /// `instructions` against this nonce. The authority signs alongside
+    /// `payer`.
+    pub fn transaction(&self, instructions: &[Instruction], payer: &Pubkey) -> UnsignedTransaction {
+        UnsignedTransaction::with_nonce(
+            instructions,
+            payer,
+            &self.address,
+            &self.authority,
+            self.nonce,
+        )
+    }
+
+    pub fn approve(
+        &self,
+        smart_account: &SmartAccount,
+        transaction_index: u64,
+        signer: Pubkey,
+        payer: &Pubkey,
+    ) -> UnsignedTransaction {
+        self.transaction(
+            &[smart_account.approve_proposal(transaction_index, signer, None)],
+            payer,
+        )
+    }
+
+    /// `message_accounts` as for [`SmartAccount::execute_transaction`].
+    pub fn execute(
+        &self,
+        smart_account: &SmartAccount,
+        transaction_index: u64,
+        signer: Pubkey,
+        message_accounts: &[AccountMeta],
+        payer: &Pubkey,
+    ) -> UnsignedTransaction {
+        self.transaction(
+            &[smart_account.execute_transaction(transaction_index, signer, message_accounts)],
+            payer,
+        )
+    }
+}
+
+/// Fetches the nonce account at `address`.
+pub async fn fetch_nonce_account<R: AccountFetcher>(
+    rpc: &R,
+    address: &Pubkey,
+) -> Result<NonceAccount, io::Error> {
+    let account = fetch_multiple(rpc, &[*address])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Account not found: {}", address),
+            )
+        })?;
+    NonceAccount::from_bytes(*address, &account.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_nonce::state::DurableNonce;
+    use solana_system_interface::instruction::SystemInstruction;
+
+    use super::*;
+
+    fn nonce_data(state: State) -> Vec<u8> {
+        bincode::serialize(&Versions::new(state)).unwrap()
+    }
+
+    #[test]
+    fn from_bytes_reads_initialized_accounts_only() {
+        let (address, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let durable_nonce = DurableNonce::from_blockhash(&Hash::new_from_array([3; 32]));
+        let data = nonce_data(State::new_initialized(&authority, durable_nonce, 5_000));
+        assert_eq!(
+            NonceAccount::from_bytes(address, &data).unwrap(),
+            NonceAccount {
+                address,
+                authority,
+                nonce: *durable_nonce.as_hash(),
+            }
+        );
+
+        let error =
+            NonceAccount::from_bytes(address, &nonce_data(State::Uninitialized)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("not initialized"));
+        assert!(NonceAccount::from_bytes(address, &[0; 3]).is_err());
+    }
+
+    #[test]
+    fn authorize_to_vault_follows_the_nonce_advance() {
+        let (settings, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let nonce = NonceAccount {
+            address: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            nonce: Hash::new_from_array([3; 32]),
+        };
+        let vault = pda::smart_account(&settings, 1).0;
+        let transaction = nonce.transaction(
+            &[authorize_to_vault(
+                &nonce.address,
+                &nonce.authority,
+                &settings,
+                1,
+            )],
+            &payer,
+        );
+
+        let message = transaction.message();
+        let keys = message.static_account_keys();
+        let decoded: Vec<(SystemInstruction, Vec<Pubkey>)> = message
+            .instructions()
+            .iter()
+            .map(|instruction| {
+                (
+                    bincode::deserialize(&instruction.data).unwrap(),
+                    instruction
+                        .accounts
+                        .iter()
+                        .map(|index| keys[*index as usize])
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].0, SystemInstruction::AdvanceNonceAccount);
+        assert_eq!(
+            decoded[1].0,
+            SystemInstruction::AuthorizeNonceAccount(vault)
+        );
+        assert_eq!(decoded[1].1, [nonce.address, nonce.authority]);
+        assert_eq!(transaction.signers(), [payer, nonce.authority]);
+    }
+}
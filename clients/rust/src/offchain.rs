@@ -0,0 +1,337 @@
+//! Off-chain signed messages for signature-based flows.
+//!
+//! An [`OffchainMessage`] is a vote or session action a signer approves by
+//! signing bytes rather than a transaction, e.g. to be relayed later. The
+//! byte layout is versioned and domain-separated so a signature over it
+//! can never be mistaken for a transaction or for a message meant for
+//! another cluster, program or smart account:
+//!
+//! | bytes | field |
+//! |-------|-------|
+//! | 19    | [`SIGNING_DOMAIN`] |
+//! | 1     | version, currently [`OFFCHAIN_MESSAGE_VERSION`] |
+//! | 32    | chain id: the cluster's genesis hash |
+//! | 32    | program id |
+//! | 32    | settings |
+//! | 8     | nonce, little-endian |
+//! | 8     | expiry as a unix timestamp, little-endian |
+//! | ..    | the borsh-serialized [`OffchainAction`] |
+//!
+//! The nonce lets the verifier reject replays; expiry bounds how long a
+//! signature stays usable.
+//!
+//! Only off-chain verification is provided. The program does not accept
+//! these messages yet; that needs program changes, and the program is not
+//! part of this repository.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_pubkey::Pubkey;
+use thiserror::Error;
+
+/// Starts every message. The leading `0xff` cannot begin a serialized
+/// transaction message.
+pub const SIGNING_DOMAIN: &[u8; 19] = b"\xffastrolabe offchain";
+pub const OFFCHAIN_MESSAGE_VERSION: u8 = 0;
+
+const HEADER_SIZE: usize = SIGNING_DOMAIN.len() + 1 + 32 + 32 + 32 + 8 + 8;
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum OffchainMessageError {
+    #[error("not an off-chain message")]
+    Domain,
+    #[error("unsupported off-chain message version {0}")]
+    Version(u8),
+    #[error("message is for program {0}")]
+    Program(Pubkey),
+    #[error("malformed off-chain message: {0}")]
+    Malformed(String),
+    #[error("message expired at {0}")]
+    Expired(i64),
+    #[error("invalid signature")]
+    Signature,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Eq, PartialEq)]
+pub enum OffchainAction {
+    Approve {
+        transaction_index: u64,
+    },
+    Reject {
+        transaction_index: u64,
+    },
+    Cancel {
+        transaction_index: u64,
+    },
+    /// Lets `session_key` act for the signer until the message expires.
+    CreateSession {
+        session_key: Pubkey,
+    },
+    RevokeSession {
+        session_key: Pubkey,
+    },
+}
+
+/// See the [module documentation](self).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OffchainMessage {
+    pub chain_id: [u8; 32],
+    pub settings: Pubkey,
+    pub nonce: u64,
+    pub expires_at: i64,
+    pub action: OffchainAction,
+}
+
+impl OffchainMessage {
+    /// The bytes that are signed.
+    pub fn serialize(&self) -> Vec<u8> {
+        // The largest action is a tag and a key.
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + 1 + 32);
+        bytes.extend_from_slice(SIGNING_DOMAIN);
+        bytes.push(OFFCHAIN_MESSAGE_VERSION);
+        bytes.extend_from_slice(&self.chain_id);
+        bytes.extend_from_slice(crate::ID.as_ref());
+        bytes.extend_from_slice(self.settings.as_ref());
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+        bytes.extend_from_slice(&self.expires_at.to_le_bytes());
+        self.action
+            .serialize(&mut bytes)
+            .expect("in-memory serialization cannot fail");
+        bytes
+    }
+
+    /// Parses bytes produced by [`Self::serialize`] for this program.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, OffchainMessageError> {
+        let malformed = |e: &dyn std::fmt::Display| OffchainMessageError::Malformed(e.to_string());
+        let rest = bytes
+            .strip_prefix(SIGNING_DOMAIN.as_slice())
+            .ok_or(OffchainMessageError::Domain)?;
+        if rest.len() < HEADER_SIZE - SIGNING_DOMAIN.len() {
+            return Err(malformed(&"too short"));
+        }
+        if rest[0] != OFFCHAIN_MESSAGE_VERSION {
+            return Err(OffchainMessageError::Version(rest[0]));
+        }
+        let (chain_id, rest) = rest[1..].split_at(32);
+        let (program, rest) = rest.split_at(32);
+        let program = Pubkey::try_from(program).unwrap();
+        if program != crate::ID {
+            return Err(OffchainMessageError::Program(program));
+        }
+        let (settings, rest) = rest.split_at(32);
+        let (nonce, rest) = rest.split_at(8);
+        let (expires_at, mut rest) = rest.split_at(8);
+        let action = OffchainAction::deserialize(&mut rest).map_err(|e| malformed(&e))?;
+        if !rest.is_empty() {
+            return Err(malformed(&"trailing bytes"));
+        }
+        Ok(Self {
+            chain_id: chain_id.try_into().unwrap(),
+            settings: Pubkey::try_from(settings).unwrap(),
+            nonce: u64::from_le_bytes(nonce.try_into().unwrap()),
+            expires_at: i64::from_le_bytes(expires_at.try_into().unwrap()),
+            action,
+        })
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+
+    #[cfg(feature = "client")]
+    pub fn sign(
+        &self,
+        signer: &dyn solana_signer::Signer,
+    ) -> Result<solana_signature::Signature, solana_signer::SignerError> {
+        signer.try_sign_message(&self.serialize())
+    }
+
+    /// Checks that `signature` is `signer`'s over this message and that the
+    /// message has not expired at unix time `now`. The nonce is the
+    /// verifier's to track.
+    #[cfg(feature = "client")]
+    pub fn verify(
+        &self,
+        signer: &Pubkey,
+        signature: &solana_signature::Signature,
+        now: i64,
+    ) -> Result<(), OffchainMessageError> {
+        if !signature.verify(signer.as_ref(), &self.serialize()) {
+            return Err(OffchainMessageError::Signature);
+        }
+        if self.is_expired(now) {
+            return Err(OffchainMessageError::Expired(self.expires_at));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAIN_ID: [u8; 32] = [1; 32];
+    const SETTINGS: Pubkey = Pubkey::new_from_array([2; 32]);
+    const SESSION_KEY: Pubkey = Pubkey::new_from_array([6; 32]);
+
+    fn message(action: OffchainAction) -> OffchainMessage {
+        OffchainMessage {
+            chain_id: CHAIN_ID,
+            settings: SETTINGS,
+            nonce: 3,
+            expires_at: 1_000,
+            action,
+        }
+    }
+
+    fn header() -> Vec<u8> {
+        let mut bytes = b"\xffastrolabe offchain".to_vec();
+        bytes.push(0);
+        bytes.extend_from_slice(&[1; 32]);
+        bytes.extend_from_slice(crate::ID.as_ref());
+        bytes.extend_from_slice(&[2; 32]);
+        bytes.extend_from_slice(&[3, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0xe8, 0x03, 0, 0, 0, 0, 0, 0]);
+        bytes
+    }
+
+    fn actions() -> [(OffchainAction, Vec<u8>); 5] {
+        let index = vec![5, 0, 0, 0, 0, 0, 0, 0];
+        let key = [6; 32];
+        [
+            (
+                OffchainAction::Approve {
+                    transaction_index: 5,
+                },
+                [&[0], index.as_slice()].concat(),
+            ),
+            (
+                OffchainAction::Reject {
+                    transaction_index: 5,
+                },
+                [&[1], index.as_slice()].concat(),
+            ),
+            (
+                OffchainAction::Cancel {
+                    transaction_index: 5,
+                },
+                [&[2], index.as_slice()].concat(),
+            ),
+            (
+                OffchainAction::CreateSession {
+                    session_key: SESSION_KEY,
+                },
+                [&[3], key.as_slice()].concat(),
+            ),
+            (
+                OffchainAction::RevokeSession {
+                    session_key: SESSION_KEY,
+                },
+                [&[4], key.as_slice()].concat(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn serialize_matches_the_documented_layout() {
+        assert_eq!(header().len(), HEADER_SIZE);
+        for (action, tail) in actions() {
+            let bytes = message(action.clone()).serialize();
+            assert_eq!(bytes, [header(), tail].concat(), "{:?}", action);
+        }
+    }
+
+    #[test]
+    fn deserialize_round_trips() {
+        for (action, _) in actions() {
+            let message = message(action);
+            assert_eq!(
+                OffchainMessage::deserialize(&message.serialize()),
+                Ok(message)
+            );
+        }
+    }
+
+    #[test]
+    fn deserialize_rejects_foreign_and_malformed_bytes() {
+        let bytes = message(OffchainAction::Approve {
+            transaction_index: 5,
+        })
+        .serialize();
+        let with = |offset: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            bytes
+        };
+
+        assert_eq!(
+            OffchainMessage::deserialize(&with(0, 0)),
+            Err(OffchainMessageError::Domain)
+        );
+        assert_eq!(
+            OffchainMessage::deserialize(&with(SIGNING_DOMAIN.len(), 1)),
+            Err(OffchainMessageError::Version(1))
+        );
+        let program_offset = SIGNING_DOMAIN.len() + 1 + 32;
+        let mut other_program = crate::ID.to_bytes();
+        other_program[0] ^= 1;
+        let mut foreign = bytes.clone();
+        foreign[program_offset..program_offset + 32].copy_from_slice(&other_program);
+        assert_eq!(
+            OffchainMessage::deserialize(&foreign),
+            Err(OffchainMessageError::Program(Pubkey::new_from_array(
+                other_program
+            )))
+        );
+        assert_eq!(
+            OffchainMessage::deserialize(&[bytes.as_slice(), &[0]].concat()),
+            Err(OffchainMessageError::Malformed(
+                "trailing bytes".to_string()
+            ))
+        );
+        assert!(matches!(
+            OffchainMessage::deserialize(&bytes[..HEADER_SIZE - 1]),
+            Err(OffchainMessageError::Malformed(_))
+        ));
+        assert!(matches!(
+            OffchainMessage::deserialize(&with(HEADER_SIZE, 5)),
+            Err(OffchainMessageError::Malformed(_))
+        ));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn verify_checks_the_signer_the_message_and_the_expiry() {
+        use solana_keypair::Keypair;
+        use solana_signer::Signer;
+
+        let signer = Keypair::new();
+        let message = message(OffchainAction::Approve {
+            transaction_index: 5,
+        });
+        let signature = message.sign(&signer).unwrap();
+        assert_eq!(message.verify(&signer.pubkey(), &signature, 999), Ok(()));
+
+        assert_eq!(
+            message.verify(&signer.pubkey(), &signature, 1_000),
+            Err(OffchainMessageError::Expired(1_000))
+        );
+        assert_eq!(
+            message.verify(&Keypair::new().pubkey(), &signature, 999),
+            Err(OffchainMessageError::Signature)
+        );
+        let replayed = OffchainMessage {
+            nonce: 4,
+            ..message.clone()
+        };
+        assert_eq!(
+            replayed.verify(&signer.pubkey(), &signature, 999),
+            Err(OffchainMessageError::Signature)
+        );
+        // A signature over the bare action is not one over the message.
+        let bare = signer.sign_message(&borsh::to_vec(&message.action).unwrap());
+        assert_eq!(
+            message.verify(&signer.pubkey(), &bare, 999),
+            Err(OffchainMessageError::Signature)
+        );
+    }
+}
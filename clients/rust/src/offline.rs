@@ -0,0 +1,354 @@
+//! Offline signing for vote and execute transactions.
+//!
+//! The online machine builds an [`UnsignedTransaction`] against a durable
+//! nonce and exports it. Each air-gapped signer imports it and returns a
+//! `PUBKEY=SIGNATURE` line, the same format `solana --sign-only` prints.
+//! The online machine then verifies every signature and assembles the
+//! final transaction. Transactions can be built in either
+//! [`TransactionFormat`].
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use solana_hash::Hash;
+use solana_instruction::Instruction;
+use solana_message::{Message, VersionedMessage};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_signer::Signer;
+use solana_system_interface::instruction::advance_nonce_account;
+use solana_transaction::versioned::VersionedTransaction;
+use thiserror::Error;
+
+use crate::message::MessageError;
+use crate::transaction::TransactionFormat;
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum OfflineError {
+    #[error("invalid transaction encoding: {0}")]
+    Encoding(String),
+    #[error("invalid signature line: {0}")]
+    InvalidSignatureLine(String),
+    #[error("{0} is not a required signer")]
+    UnexpectedSigner(Pubkey),
+    #[error("signature from {0} does not verify")]
+    InvalidSignature(Pubkey),
+    #[error("missing signature from {0}")]
+    MissingSignature(Pubkey),
+    #[error("signing failed: {0}")]
+    Signer(String),
+    #[error(transparent)]
+    Message(#[from] MessageError),
+    #[error("lookup table {0} holds the nonce account, which must stay a static key")]
+    NonceAccountInLookupTable(Pubkey),
+}
+
+/// A transaction message waiting for signatures.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnsignedTransaction {
+    message: VersionedMessage,
+}
+
+impl UnsignedTransaction {
+    /// Builds `instructions` against a durable nonce. The nonce advance is
+    /// prepended, so the transaction stays valid until the nonce is used.
+    pub fn with_nonce(
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        nonce_account: &Pubkey,
+        nonce_authority: &Pubkey,
+        nonce: Hash,
+    ) -> Self {
+        let mut message = Message::new_with_nonce(
+            instructions.to_vec(),
+            Some(payer),
+            nonce_account,
+            nonce_authority,
+        );
+        message.recent_blockhash = nonce;
+        Self {
+            message: VersionedMessage::Legacy(message),
+        }
+    }
+
+    /// Like [`Self::with_nonce`], in `format`. The runtime only recognizes
+    /// a durable nonce transaction when the nonce account is a static key,
+    /// so a v0 format whose tables hold the nonce account is refused: the
+    /// account would be loaded from the table instead.
+    pub fn with_nonce_in_format(
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        nonce_account: &Pubkey,
+        nonce_authority: &Pubkey,
+        nonce: Hash,
+        format: &TransactionFormat,
+    ) -> Result<Self, OfflineError> {
+        if let Some(table) = format
+            .address_lookup_table_accounts()
+            .iter()
+            .find(|table| table.addresses.contains(nonce_account))
+        {
+            return Err(OfflineError::NonceAccountInLookupTable(table.key));
+        }
+        let instructions: Vec<Instruction> =
+            std::iter::once(advance_nonce_account(nonce_account, nonce_authority))
+                .chain(instructions.iter().cloned())
+                .collect();
+        Ok(Self {
+            message: format.compile(payer, &instructions, nonce)?,
+        })
+    }
+
+    pub fn message(&self) -> &VersionedMessage {
+        &self.message
+    }
+
+    /// Keys that must sign, in signature order.
+    pub fn signers(&self) -> &[Pubkey] {
+        &self.message.static_account_keys()
+            [..self.message.header().num_required_signatures as usize]
+    }
+
+    /// Base64 of the serialized message.
+    pub fn encode(&self) -> String {
+        STANDARD.encode(self.message.serialize())
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, OfflineError> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| OfflineError::Encoding(e.to_string()))?;
+        let message: VersionedMessage =
+            bincode::deserialize(&bytes).map_err(|e| OfflineError::Encoding(e.to_string()))?;
+        if message.header().num_required_signatures as usize > message.static_account_keys().len() {
+            return Err(OfflineError::Encoding(
+                "more signers than account keys".to_string(),
+            ));
+        }
+        Ok(Self { message })
+    }
+
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<(), io::Error> {
+        fs::write(path, self.encode())
+    }
+
+    pub fn read_from_file(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        Self::decode(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Signs the message with `signer`, which must be one of [`Self::signers`].
+    pub fn sign<S: Signer + ?Sized>(
+        &self,
+        signer: &S,
+    ) -> Result<(Pubkey, Signature), OfflineError> {
+        let pubkey = signer.pubkey();
+        if !self.signers().contains(&pubkey) {
+            return Err(OfflineError::UnexpectedSigner(pubkey));
+        }
+        let signature = signer
+            .try_sign_message(&self.message.serialize())
+            .map_err(|e| OfflineError::Signer(e.to_string()))?;
+        Ok((pubkey, signature))
+    }
+
+    /// Verifies `signatures` and orders them into a sendable transaction.
+    pub fn assemble(
+        self,
+        signatures: &[(Pubkey, Signature)],
+    ) -> Result<VersionedTransaction, OfflineError> {
+        let message_bytes = self.message.serialize();
+        for (pubkey, signature) in signatures {
+            if !self.signers().contains(pubkey) {
+                return Err(OfflineError::UnexpectedSigner(*pubkey));
+            }
+            if !signature.verify(pubkey.as_ref(), &message_bytes) {
+                return Err(OfflineError::InvalidSignature(*pubkey));
+            }
+        }
+        let signatures = self
+            .signers()
+            .iter()
+            .map(|signer| {
+                signatures
+                    .iter()
+                    .find(|(pubkey, _)| pubkey == signer)
+                    .map(|(_, signature)| *signature)
+                    .ok_or(OfflineError::MissingSignature(*signer))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(VersionedTransaction {
+            signatures,
+            message: self.message,
+        })
+    }
+}
+
+/// Formats a signature as a `PUBKEY=SIGNATURE` line.
+pub fn format_signature(pubkey: &Pubkey, signature: &Signature) -> String {
+    format!("{}={}", pubkey, signature)
+}
+
+/// Parses `PUBKEY=SIGNATURE` lines, ignoring blank ones.
+pub fn parse_signatures(text: &str) -> Result<Vec<(Pubkey, Signature)>, OfflineError> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let invalid = || OfflineError::InvalidSignatureLine(line.to_string());
+            let (pubkey, signature) = line.split_once('=').ok_or_else(invalid)?;
+            Ok((
+                Pubkey::from_str(pubkey).map_err(|_| invalid())?,
+                Signature::from_str(signature).map_err(|_| invalid())?,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::AccountMeta;
+    use solana_keypair::Keypair;
+    use solana_message::AddressLookupTableAccount;
+    use solana_sdk_ids::system_program;
+
+    use super::*;
+
+    /// An unsigned nonce transaction that `payer` and `cosigner` must sign.
+    fn unsigned(payer: &Keypair, cosigner: &Keypair) -> UnsignedTransaction {
+        UnsignedTransaction::with_nonce(
+            &[Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[7],
+                vec![AccountMeta::new_readonly(cosigner.pubkey(), true)],
+            )],
+            &payer.pubkey(),
+            &Pubkey::new_unique(),
+            &payer.pubkey(),
+            Hash::new_unique(),
+        )
+    }
+
+    #[test]
+    fn encode_and_decode_round_trip() {
+        let payer = Keypair::new();
+        let unsigned = unsigned(&payer, &Keypair::new());
+        assert_eq!(
+            UnsignedTransaction::decode(&unsigned.encode()).unwrap(),
+            unsigned
+        );
+        assert!(matches!(
+            UnsignedTransaction::decode("not base64!"),
+            Err(OfflineError::Encoding(_))
+        ));
+
+        let (pubkey, signature) = unsigned.sign(&payer).unwrap();
+        let line = format_signature(&pubkey, &signature);
+        assert_eq!(
+            parse_signatures(&format!("\n{}\n\n", line)).unwrap(),
+            [(pubkey, signature)]
+        );
+        assert_eq!(
+            parse_signatures("nonsense"),
+            Err(OfflineError::InvalidSignatureLine("nonsense".to_string()))
+        );
+    }
+
+    #[test]
+    fn assemble_orders_signatures_like_the_message() {
+        let payer = Keypair::new();
+        let cosigner = Keypair::new();
+        let unsigned = unsigned(&payer, &cosigner);
+        assert_eq!(unsigned.signers(), [payer.pubkey(), cosigner.pubkey()]);
+
+        // Signatures may come back in any order.
+        let signatures = [
+            unsigned.sign(&cosigner).unwrap(),
+            unsigned.sign(&payer).unwrap(),
+        ];
+        let transaction = unsigned.clone().assemble(&signatures).unwrap();
+        assert_eq!(transaction.signatures, [signatures[1].1, signatures[0].1]);
+        let message = transaction.message.serialize();
+        for (key, signature) in unsigned.signers().iter().zip(&transaction.signatures) {
+            assert!(signature.verify(key.as_ref(), &message));
+        }
+    }
+
+    #[test]
+    fn assemble_rejects_bad_and_missing_signatures() {
+        let payer = Keypair::new();
+        let cosigner = Keypair::new();
+        let stranger = Keypair::new();
+        let unsigned = unsigned(&payer, &cosigner);
+        let payer_signature = unsigned.sign(&payer).unwrap();
+
+        assert_eq!(
+            unsigned.sign(&stranger),
+            Err(OfflineError::UnexpectedSigner(stranger.pubkey()))
+        );
+        assert_eq!(
+            unsigned.clone().assemble(&[payer_signature]),
+            Err(OfflineError::MissingSignature(cosigner.pubkey()))
+        );
+        // The payer's signature passed off as the cosigner's.
+        assert_eq!(
+            unsigned
+                .clone()
+                .assemble(&[payer_signature, (cosigner.pubkey(), payer_signature.1)]),
+            Err(OfflineError::InvalidSignature(cosigner.pubkey()))
+        );
+        let foreign = (
+            stranger.pubkey(),
+            stranger.sign_message(&unsigned.message().serialize()),
+        );
+        assert_eq!(
+            unsigned.assemble(&[payer_signature, foreign]),
+            Err(OfflineError::UnexpectedSigner(stranger.pubkey()))
+        );
+    }
+
+    #[test]
+    fn with_nonce_in_format_keeps_the_nonce_account_static() {
+        let payer = Pubkey::new_unique();
+        let nonce_account = Pubkey::new_unique();
+        let nonce_authority = Pubkey::new_unique();
+        let nonce = Hash::new_unique();
+
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique(), nonce_account],
+        };
+        assert_eq!(
+            UnsignedTransaction::with_nonce_in_format(
+                &[],
+                &payer,
+                &nonce_account,
+                &nonce_authority,
+                nonce,
+                &TransactionFormat::V0(vec![table.clone()]),
+            ),
+            Err(OfflineError::NonceAccountInLookupTable(table.key))
+        );
+
+        let unsigned = UnsignedTransaction::with_nonce_in_format(
+            &[],
+            &payer,
+            &nonce_account,
+            &nonce_authority,
+            nonce,
+            &TransactionFormat::V0(Vec::new()),
+        )
+        .unwrap();
+        let message = unsigned.message();
+        assert!(matches!(message, VersionedMessage::V0(_)));
+        assert_eq!(*message.recent_blockhash(), nonce);
+        let keys = message.static_account_keys();
+        let advance = &message.instructions()[0];
+        assert_eq!(keys[advance.program_id_index as usize], system_program::ID);
+        assert_eq!(keys[advance.accounts[0] as usize], nonce_account);
+    }
+}
@@ -0,0 +1,289 @@
+//! Packs instructions into as few transactions as fit.
+//!
+//! Instructions are packed in [`InstructionGroup`]s, which always land in
+//! the same transaction and in order, e.g. create + proposal + approve.
+//! Groups are taken in order and a new transaction is started whenever the
+//! next group would exceed the packet size or the compute unit limit. Each
+//! transaction requests exactly the compute units of the groups it holds.
+//! The `versioned` variants pack into a [`TransactionFormat`], which lets
+//! more groups share a transaction when their accounts are in lookup tables.
+
+use solana_hash::Hash;
+use solana_instruction::Instruction;
+use solana_message::{Message, VersionedMessage};
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction::Transaction;
+use thiserror::Error;
+
+use crate::compute_budget::{
+    set_compute_unit_limit, DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT, MAX_COMPUTE_UNIT_LIMIT,
+};
+use crate::message::MessageError;
+use crate::transaction::{sign_message, wire_size, BuildError, TransactionFormat};
+
+/// Largest serialized transaction the network accepts.
+pub const PACKET_DATA_SIZE: usize = 1232;
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum PackError {
+    #[error("instruction group {0} does not fit in one transaction")]
+    GroupTooLarge(usize),
+    #[error("missing signer {0}")]
+    MissingSigner(Pubkey),
+    #[error("signing failed: {0}")]
+    Signer(String),
+    #[error("instruction group {0} does not compile: {1}")]
+    Compile(usize, MessageError),
+}
+
+/// Instructions that must execute together, with the compute units they use.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstructionGroup {
+    pub instructions: Vec<Instruction>,
+    pub compute_units: u32,
+}
+
+impl InstructionGroup {
+    /// A group budgeted at the default per-instruction limit.
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        let compute_units = DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT
+            .saturating_mul(instructions.len() as u32)
+            .min(MAX_COMPUTE_UNIT_LIMIT);
+        Self {
+            instructions,
+            compute_units,
+        }
+    }
+
+    /// Replaces the default budget with a measured or known one.
+    pub fn with_compute_units(mut self, compute_units: u32) -> Self {
+        self.compute_units = compute_units;
+        self
+    }
+}
+
+impl From<Instruction> for InstructionGroup {
+    fn from(instruction: Instruction) -> Self {
+        Self::new(vec![instruction])
+    }
+}
+
+/// Packs `groups` into messages paid for by `payer`. Each message starts with
+/// a compute unit limit covering its groups.
+pub fn pack_messages(
+    payer: &Pubkey,
+    groups: &[InstructionGroup],
+) -> Result<Vec<Message>, PackError> {
+    Ok(
+        pack_versioned_messages(payer, groups, &TransactionFormat::Legacy)?
+            .into_iter()
+            .map(|message| match message {
+                VersionedMessage::Legacy(message) => message,
+                VersionedMessage::V0(_) => {
+                    unreachable!("the legacy format compiles legacy messages")
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Like [`pack_messages`], in `format`. The messages have a default
+/// blockhash until they are signed.
+pub fn pack_versioned_messages(
+    payer: &Pubkey,
+    groups: &[InstructionGroup],
+    format: &TransactionFormat,
+) -> Result<Vec<VersionedMessage>, PackError> {
+    pack(groups, |instructions| {
+        let message = format.compile(payer, instructions, Hash::default())?;
+        Ok((wire_size(&message) <= PACKET_DATA_SIZE).then_some(message))
+    })
+}
+
+/// Packs `groups` and signs each transaction with the `signers` it needs.
+pub fn pack_transactions(
+    payer: &Pubkey,
+    groups: &[InstructionGroup],
+    signers: &[&dyn Signer],
+    recent_blockhash: Hash,
+) -> Result<Vec<Transaction>, PackError> {
+    Ok(pack_versioned_transactions(
+        payer,
+        groups,
+        &TransactionFormat::Legacy,
+        signers,
+        recent_blockhash,
+    )?
+    .into_iter()
+    .map(|transaction| {
+        transaction
+            .into_legacy_transaction()
+            .expect("the legacy format compiles legacy messages")
+    })
+    .collect())
+}
+
+/// Like [`pack_transactions`], in `format`.
+pub fn pack_versioned_transactions(
+    payer: &Pubkey,
+    groups: &[InstructionGroup],
+    format: &TransactionFormat,
+    signers: &[&dyn Signer],
+    recent_blockhash: Hash,
+) -> Result<Vec<VersionedTransaction>, PackError> {
+    pack_versioned_messages(payer, groups, format)?
+        .into_iter()
+        .map(|mut message| {
+            message.set_recent_blockhash(recent_blockhash);
+            sign_message(message, signers).map_err(|e| match e {
+                BuildError::MissingSigner(key) => PackError::MissingSigner(key),
+                e => PackError::Signer(e.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Takes groups in order into the message `build` returns for their
+/// instructions, starting a new message whenever the next group does not
+/// fit or the compute unit limit would be exceeded. A group that does not
+/// compile on its own fails with the error `build` returned; one that does
+/// not compile together with the groups before it, e.g. because they use
+/// too many accounts, starts a new message.
+fn pack<M>(
+    groups: &[InstructionGroup],
+    build: impl Fn(&[Instruction]) -> Result<Option<M>, MessageError>,
+) -> Result<Vec<M>, PackError> {
+    let build = |groups: &[InstructionGroup], compute_units: u32| {
+        if compute_units > MAX_COMPUTE_UNIT_LIMIT {
+            return Ok(None);
+        }
+        let instructions: Vec<Instruction> = std::iter::once(set_compute_unit_limit(compute_units))
+            .chain(
+                groups
+                    .iter()
+                    .flat_map(|group| group.instructions.iter().cloned()),
+            )
+            .collect();
+        build(&instructions)
+    };
+    let mut messages = Vec::new();
+    let mut current: Option<(M, u32, usize)> = None;
+
+    for (index, group) in groups.iter().enumerate() {
+        if let Some((message, compute_units, start)) = current.take() {
+            let compute_units = compute_units.saturating_add(group.compute_units);
+            match build(&groups[start..=index], compute_units) {
+                Ok(Some(next)) => {
+                    current = Some((next, compute_units, start));
+                    continue;
+                }
+                Ok(None) | Err(_) => messages.push(message),
+            }
+        }
+        let message = build(std::slice::from_ref(group), group.compute_units)
+            .map_err(|e| PackError::Compile(index, e))?
+            .ok_or(PackError::GroupTooLarge(index))?;
+        current = Some((message, group.compute_units, index));
+    }
+    messages.extend(current.map(|(message, _, _)| message));
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::AccountMeta;
+    use solana_keypair::Keypair;
+    use solana_message::{AddressLookupTableAccount, CompileError};
+
+    use super::*;
+
+    fn group(signer: &Pubkey, data_len: usize) -> InstructionGroup {
+        InstructionGroup::new(vec![Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &vec![0; data_len],
+            vec![AccountMeta::new(*signer, true)],
+        )])
+    }
+
+    #[test]
+    fn wire_size_matches_the_serialized_transaction() {
+        let payer = Keypair::new();
+        let groups = [group(&payer.pubkey(), 100), group(&payer.pubkey(), 200)];
+        let transactions =
+            pack_transactions(&payer.pubkey(), &groups, &[&payer], Hash::new_unique()).unwrap();
+        assert_eq!(transactions.len(), 1);
+        let transaction = &transactions[0];
+        assert!(
+            transaction.signatures[0].verify(payer.pubkey().as_ref(), &transaction.message_data())
+        );
+        assert_eq!(
+            wire_size(&VersionedMessage::Legacy(transaction.message.clone())),
+            bincode::serialize(transaction).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn pack_transactions_splits_at_the_packet_size() {
+        let payer = Keypair::new();
+        let groups: Vec<_> = (0..3).map(|_| group(&payer.pubkey(), 450)).collect();
+        let transactions =
+            pack_transactions(&payer.pubkey(), &groups, &[&payer], Hash::new_unique()).unwrap();
+        assert_eq!(transactions.len(), 2);
+        for transaction in &transactions {
+            assert!(transaction.signatures[0]
+                .verify(payer.pubkey().as_ref(), &transaction.message_data()));
+            assert!(bincode::serialize(transaction).unwrap().len() <= PACKET_DATA_SIZE);
+        }
+
+        let other = Keypair::new();
+        assert_eq!(
+            pack_transactions(&payer.pubkey(), &groups, &[&other], Hash::new_unique()),
+            Err(PackError::MissingSigner(payer.pubkey()))
+        );
+        assert_eq!(
+            pack_transactions(
+                &payer.pubkey(),
+                &[group(&payer.pubkey(), 1500)],
+                &[&payer],
+                Hash::default()
+            ),
+            Err(PackError::GroupTooLarge(0))
+        );
+    }
+
+    #[test]
+    fn groups_that_do_not_compile_report_why() {
+        let payer = Keypair::new();
+        // v0 lookups address a table with a `u8`, so the last of 300
+        // addresses cannot be loaded from it.
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: (0..300).map(|_| Pubkey::new_unique()).collect(),
+        };
+        let unreachable = InstructionGroup::from(Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![AccountMeta::new_readonly(table.addresses[299], false)],
+        ));
+        let format = TransactionFormat::V0(vec![table]);
+        assert_eq!(
+            pack_versioned_messages(
+                &payer.pubkey(),
+                &[group(&payer.pubkey(), 10), unreachable],
+                &format
+            ),
+            Err(PackError::Compile(
+                1,
+                MessageError::Compile(CompileError::AddressTableLookupIndexOverflow)
+            ))
+        );
+        assert_eq!(
+            pack_versioned_messages(&payer.pubkey(), &[group(&payer.pubkey(), 10)], &format)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
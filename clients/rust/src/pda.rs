@@ -0,0 +1,208 @@
+//! Program-derived address derivation for the smart account program.
+//!
+//! Every function returns `(address, bump)` and uses the same seeds as the
+//! on-chain program, so clients never need to hand-roll seed arrays.
+
+use solana_pubkey::Pubkey;
+
+pub const SEED_PREFIX: &[u8] = b"smart_account";
+pub const SEED_PROGRAM_CONFIG: &[u8] = b"program_config";
+pub const SEED_SETTINGS: &[u8] = b"settings";
+pub const SEED_SMART_ACCOUNT: &[u8] = b"smart_account";
+pub const SEED_TRANSACTION: &[u8] = b"transaction";
+pub const SEED_PROPOSAL: &[u8] = b"proposal";
+pub const SEED_BATCH_TRANSACTION: &[u8] = b"batch_transaction";
+pub const SEED_EPHEMERAL_SIGNER: &[u8] = b"ephemeral_signer";
+pub const SEED_SPENDING_LIMIT: &[u8] = b"spending_limit";
+pub const SEED_TRANSACTION_BUFFER: &[u8] = b"transaction_buffer";
+
+/// The global program config account.
+pub fn program_config() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SEED_PREFIX, SEED_PROGRAM_CONFIG], &crate::ID)
+}
+
+/// The settings account seeded by `ProgramConfig::smart_account_index`.
+pub fn settings(seed: u128) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SEED_PREFIX, SEED_SETTINGS, &seed.to_le_bytes()],
+        &crate::ID,
+    )
+}
+
+/// The smart account (vault) PDA at `account_index` that holds funds.
+pub fn smart_account(settings: &Pubkey, account_index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            settings.as_ref(),
+            SEED_SMART_ACCOUNT,
+            &[account_index],
+        ],
+        &crate::ID,
+    )
+}
+
+/// A transaction, settings transaction or batch at `transaction_index`.
+pub fn transaction(settings: &Pubkey, transaction_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            settings.as_ref(),
+            SEED_TRANSACTION,
+            &transaction_index.to_le_bytes(),
+        ],
+        &crate::ID,
+    )
+}
+
+/// The proposal for the transaction at `transaction_index`.
+pub fn proposal(settings: &Pubkey, transaction_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            settings.as_ref(),
+            SEED_TRANSACTION,
+            &transaction_index.to_le_bytes(),
+            SEED_PROPOSAL,
+        ],
+        &crate::ID,
+    )
+}
+
+/// A transaction inside the batch at `batch_index`; `transaction_index` is 1-based.
+pub fn batch_transaction(
+    settings: &Pubkey,
+    batch_index: u64,
+    transaction_index: u32,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            settings.as_ref(),
+            SEED_TRANSACTION,
+            &batch_index.to_le_bytes(),
+            SEED_BATCH_TRANSACTION,
+            &transaction_index.to_le_bytes(),
+        ],
+        &crate::ID,
+    )
+}
+
+/// A spending limit keyed by its `seed`.
+pub fn spending_limit(settings: &Pubkey, seed: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            settings.as_ref(),
+            SEED_SPENDING_LIMIT,
+            seed.as_ref(),
+        ],
+        &crate::ID,
+    )
+}
+
+/// A transaction buffer owned by `creator`.
+pub fn transaction_buffer(settings: &Pubkey, creator: &Pubkey, buffer_index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            settings.as_ref(),
+            SEED_TRANSACTION_BUFFER,
+            creator.as_ref(),
+            &[buffer_index],
+        ],
+        &crate::ID,
+    )
+}
+
+/// An ephemeral signer of `transaction`, signed for by the program during execution.
+pub fn ephemeral_signer(transaction: &Pubkey, ephemeral_signer_index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            transaction.as_ref(),
+            SEED_EPHEMERAL_SIGNER,
+            &[ephemeral_signer_index],
+        ],
+        &crate::ID,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_match_the_typescript_helpers() {
+        // What the seed lists of `deriveTransactionPda`, `deriveProposalPda`
+        // and `deriveSmartAccountInfo` in `utils/index.ts`, and of the
+        // program config and settings in `examples/createAccountTest.ts`,
+        // derive to for fixed inputs. Computed outside this crate.
+        let settings = Pubkey::new_from_array([1; 32]);
+        for (name, (address, bump), expected, expected_bump) in [
+            (
+                "program config",
+                program_config(),
+                "BJijekfyp4XVYm4XB8ZrPPMrjF3kWjZNQDvxpQE6ERgG",
+                254,
+            ),
+            (
+                "settings",
+                self::settings(1),
+                "8A8CYfDTjEUw9hYT12NNjVxvs76qP7ajuXKfnET8AfwS",
+                253,
+            ),
+            (
+                "smart account",
+                smart_account(&settings, 0),
+                "7ekD3G5EhBp5GoUPFZgpRpVb7HHFf3pZAAjw9HHCv3jB",
+                253,
+            ),
+            (
+                "transaction",
+                transaction(&settings, 7),
+                "BFeWbLW5V6Dqc15wEXQ8cy5NaXMXMHp1wcjc8YjiWvn5",
+                255,
+            ),
+            (
+                "proposal",
+                proposal(&settings, 7),
+                "6WTMyD277TWC8zH162qiwBUx1XYAU3Jipb3dEkxyLBo4",
+                255,
+            ),
+        ] {
+            assert_eq!(
+                (address.to_string().as_str(), bump),
+                (expected, expected_bump),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn accounts_of_one_smart_account_do_not_collide() {
+        let settings = Pubkey::new_from_array([1; 32]);
+        let creator = Pubkey::new_from_array([2; 32]);
+        let transaction = transaction(&settings, 1).0;
+        let addresses = [
+            program_config().0,
+            self::settings(1).0,
+            self::settings(2).0,
+            smart_account(&settings, 0).0,
+            smart_account(&settings, 1).0,
+            transaction,
+            self::transaction(&settings, 2).0,
+            proposal(&settings, 1).0,
+            batch_transaction(&settings, 1, 1).0,
+            batch_transaction(&settings, 1, 2).0,
+            spending_limit(&settings, &creator).0,
+            transaction_buffer(&settings, &creator, 0).0,
+            ephemeral_signer(&transaction, 0).0,
+        ];
+        for (i, a) in addresses.iter().enumerate() {
+            assert!(!a.is_on_curve());
+            assert!(addresses[i + 1..].iter().all(|b| a != b), "{} repeats", a);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Transfers of Metaplex programmable NFTs held by a vault.
+//!
+//! A programmable NFT stays frozen in its token account and moves only
+//! through the token metadata program's `Transfer` instruction, which
+//! needs the metadata and edition accounts, a token record for the source
+//! and the destination token account, and, if the NFT has a rule set, the
+//! rule set and the token auth rules program. [`transfer`] derives all of
+//! them; the rule set is read from the metadata account with [`rule_set`].
+//!
+//! [`transfer_from_vault`] has the vault as owner, authority and payer, and
+//! as the only signer, so it goes into a vault transaction as it is. The
+//! transfer creates the destination token account if needed. Rule set
+//! evaluation is expensive: vault transactions that move pNFTs usually need
+//! a raised compute unit limit, see [`crate::compute_budget`], and may need
+//! an address lookup table to fit.
+
+use std::io;
+
+use borsh::BorshDeserialize;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::{pubkey, Pubkey};
+use solana_sdk_ids::{system_program, sysvar};
+
+use crate::pda;
+use crate::smart_account::{
+    associated_token_address, ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+
+pub const TOKEN_METADATA_PROGRAM_ID: Pubkey =
+    pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+pub const TOKEN_AUTH_RULES_PROGRAM_ID: Pubkey =
+    pubkey!("auth9SigNpDKz4sJJ1DfCTuZrZNSAgh9sFD3rboVmgg");
+
+/// `Transfer` in the token metadata instruction enum, then `TransferArgs::V1`.
+const TRANSFER_V1: [u8; 2] = [49, 0];
+/// `Key::MetadataV1`.
+const METADATA_KEY: u8 = 4;
+/// `TokenStandard::ProgrammableNonFungible` and its edition.
+const PROGRAMMABLE_TOKEN_STANDARDS: [u8; 2] = [4, 5];
+
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    find_address(&[
+        b"metadata",
+        TOKEN_METADATA_PROGRAM_ID.as_ref(),
+        mint.as_ref(),
+    ])
+}
+
+/// The master or print edition of `mint`.
+pub fn edition_address(mint: &Pubkey) -> Pubkey {
+    find_address(&[
+        b"metadata",
+        TOKEN_METADATA_PROGRAM_ID.as_ref(),
+        mint.as_ref(),
+        b"edition",
+    ])
+}
+
+/// The record of `token_account`'s state and delegate for `mint`.
+pub fn token_record_address(mint: &Pubkey, token_account: &Pubkey) -> Pubkey {
+    find_address(&[
+        b"metadata",
+        TOKEN_METADATA_PROGRAM_ID.as_ref(),
+        mint.as_ref(),
+        b"token_record",
+        token_account.as_ref(),
+    ])
+}
+
+fn find_address(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &TOKEN_METADATA_PROGRAM_ID).0
+}
+
+/// Moves the pNFT `mint` between the associated token accounts of
+/// `source_owner` and `destination_owner`. `authority` is the source owner
+/// or its delegate, and `rule_set` the one in the NFT's metadata.
+pub fn transfer(
+    mint: &Pubkey,
+    source_owner: &Pubkey,
+    destination_owner: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    rule_set: Option<&Pubkey>,
+) -> Instruction {
+    let token = associated_token_address(source_owner, mint, &TOKEN_PROGRAM_ID);
+    let destination_token = associated_token_address(destination_owner, mint, &TOKEN_PROGRAM_ID);
+    // Unused optional accounts are passed as the program itself.
+    let (authorization_rules_program, authorization_rules) = match rule_set {
+        Some(rule_set) => (TOKEN_AUTH_RULES_PROGRAM_ID, *rule_set),
+        None => (TOKEN_METADATA_PROGRAM_ID, TOKEN_METADATA_PROGRAM_ID),
+    };
+    let mut data = TRANSFER_V1.to_vec();
+    // Amount, and no authorization data.
+    data.extend_from_slice(&1u64.to_le_bytes());
+    data.push(0);
+    Instruction {
+        program_id: TOKEN_METADATA_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(token, false),
+            AccountMeta::new_readonly(*source_owner, false),
+            AccountMeta::new(destination_token, false),
+            AccountMeta::new_readonly(*destination_owner, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(metadata_address(mint), false),
+            AccountMeta::new_readonly(edition_address(mint), false),
+            AccountMeta::new(token_record_address(mint, &token), false),
+            AccountMeta::new(token_record_address(mint, &destination_token), false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(sysvar::instructions::ID, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(authorization_rules_program, false),
+            AccountMeta::new_readonly(authorization_rules, false),
+        ],
+        data,
+    }
+}
+
+/// [`transfer`] out of the vault of `settings` at `account_index`.
+pub fn transfer_from_vault(
+    settings: &Pubkey,
+    account_index: u8,
+    mint: &Pubkey,
+    destination_owner: &Pubkey,
+    rule_set: Option<&Pubkey>,
+) -> Instruction {
+    let vault = pda::smart_account(settings, account_index).0;
+    transfer(mint, &vault, destination_owner, &vault, &vault, rule_set)
+}
+
+/// The metadata fields up to the programmable config.
+#[derive(BorshDeserialize)]
+struct Metadata {
+    key: u8,
+    _update_authority: Pubkey,
+    _mint: Pubkey,
+    _name: Vec<u8>,
+    _symbol: Vec<u8>,
+    _uri: Vec<u8>,
+    _seller_fee_basis_points: u16,
+    /// Address, verified and share.
+    _creators: Option<Vec<(Pubkey, bool, u8)>>,
+    _primary_sale_happened: bool,
+    _is_mutable: bool,
+    _edition_nonce: Option<u8>,
+    token_standard: Option<u8>,
+    /// Verified and key.
+    _collection: Option<(bool, Pubkey)>,
+    /// Use method, remaining and total.
+    _uses: Option<(u8, u64, u64)>,
+    /// Variant and size or padding.
+    _collection_details: Option<(u8, u64)>,
+    /// `ProgrammableConfig::V1`'s variant and rule set.
+    programmable_config: Option<(u8, Option<Pubkey>)>,
+}
+
+/// The rule set of a pNFT, from the data of its metadata account.
+pub fn rule_set(metadata: &[u8]) -> Result<Option<Pubkey>, io::Error> {
+    let metadata = Metadata::deserialize(&mut &metadata[..])?;
+    if metadata.key != METADATA_KEY {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a metadata account",
+        ));
+    }
+    if !metadata
+        .token_standard
+        .is_some_and(|standard| PROGRAMMABLE_TOKEN_STANDARDS.contains(&standard))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a programmable NFT",
+        ));
+    }
+    Ok(metadata
+        .programmable_config
+        .and_then(|(_, rule_set)| rule_set))
+}
+
+/// Fetches the metadata of `mint` and returns its rule set.
+#[cfg(feature = "client")]
+pub async fn fetch_rule_set<R: crate::rpc::AccountFetcher>(
+    rpc: &R,
+    mint: &Pubkey,
+) -> Result<Option<Pubkey>, io::Error> {
+    let address = metadata_address(mint);
+    let account = crate::rpc::fetch_multiple(rpc, &[address])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Account not found: {}", address),
+            )
+        })?;
+    rule_set(&account.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Metadata of a pNFT with `rule_set`, laid out as the program writes it.
+    fn metadata(token_standard: u8, rule_set: Option<Pubkey>) -> Vec<u8> {
+        let name = b"Astrolabe #1".to_vec();
+        borsh::to_vec(&(
+            (METADATA_KEY, Pubkey::new_unique(), Pubkey::new_unique()),
+            (name, b"ASTRO".to_vec(), b"https://example.com".to_vec()),
+            500u16,
+            Some(vec![(Pubkey::new_unique(), true, 100u8)]),
+            (false, true, Some(255u8), Some(token_standard)),
+            (
+                Some((true, Pubkey::new_unique())),
+                None::<(u8, u64, u64)>,
+                None::<(u8, u64)>,
+            ),
+            Some((0u8, rule_set)),
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn rule_set_is_read_from_the_metadata() {
+        let rules = Pubkey::new_unique();
+        let mut data = metadata(4, Some(rules));
+        // Metadata accounts are allocated with room to spare.
+        data.resize(679, 0);
+        assert_eq!(rule_set(&data).unwrap(), Some(rules));
+        assert_eq!(rule_set(&metadata(5, None)).unwrap(), None);
+        assert_eq!(
+            rule_set(&metadata(0, None)).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn transfer_from_vault_is_signed_by_the_vault_alone() {
+        let settings = Pubkey::new_unique();
+        let vault = pda::smart_account(&settings, 2).0;
+        let (mint, destination, rules) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        let instruction = transfer_from_vault(&settings, 2, &mint, &destination, Some(&rules));
+        assert_eq!(instruction.data, [49, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(instruction
+            .accounts
+            .iter()
+            .all(|meta| !meta.is_signer || meta.pubkey == vault));
+        let token = associated_token_address(&vault, &mint, &TOKEN_PROGRAM_ID);
+        assert_eq!(
+            instruction.accounts[7].pubkey,
+            token_record_address(&mint, &token)
+        );
+        assert_eq!(instruction.accounts[15].pubkey, TOKEN_AUTH_RULES_PROGRAM_ID);
+        assert_eq!(instruction.accounts[16].pubkey, rules);
+
+        let instruction = transfer_from_vault(&settings, 2, &mint, &destination, None);
+        assert_eq!(instruction.accounts[16].pubkey, TOKEN_METADATA_PROGRAM_ID);
+    }
+}
@@ -0,0 +1,321 @@
+//! Checks a built transaction before it is sent.
+//!
+//! [`preflight`] measures a transaction against the packet size limit, then
+//! simulates it and compares the compute units it used with the limit it
+//! requests. When a limit is exceeded the error says what to change: load
+//! accounts from a lookup table, request more compute units, or split the
+//! work over several transactions. Simulation skips signature checks and
+//! replaces the blockhash, so the transaction may be unsigned and its own
+//! blockhash stays unused.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+
+use solana_instruction::error::InstructionError;
+use solana_message::VersionedMessage;
+use solana_sdk_ids::compute_budget;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction_error::TransactionError;
+use thiserror::Error;
+
+use crate::compute_budget::{
+    DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT, MAX_COMPUTE_UNIT_LIMIT, SET_COMPUTE_UNIT_LIMIT,
+};
+use crate::packing::PACKET_DATA_SIZE;
+use crate::program_error::{decode_logs, decode_transaction_error, DecodedError};
+use crate::transaction::wire_size;
+
+/// A lookup table in a v0 message: its address and two empty index lists.
+const LOOKUP_TABLE_OVERHEAD: usize = 32 + 1 + 1;
+
+pub trait TransactionSimulator {
+    /// Simulates `transaction` against the latest blockhash without
+    /// verifying its signatures.
+    fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> impl Future<Output = Result<Simulation, io::Error>> + Send;
+}
+
+#[cfg(feature = "fetch")]
+impl TransactionSimulator for solana_client::nonblocking::rpc_client::RpcClient {
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<Simulation, io::Error> {
+        let config = solana_client::rpc_config::RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            ..Default::default()
+        };
+        let result = self
+            .simulate_transaction_with_config(transaction, config)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?
+            .value;
+        Ok(Simulation {
+            error: result.err,
+            units_consumed: result.units_consumed,
+            logs: result.logs.unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Simulation {
+    pub error: Option<TransactionError>,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+/// What to change so that a transaction fits.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Suggestion {
+    /// Load the accounts from an address lookup table, e.g. with
+    /// [`crate::client::SmartAccountClient::execute_with_lookup_table`].
+    LookupTable,
+    /// Request this many compute units with a compute unit limit
+    /// instruction.
+    ComputeUnitLimit(u32),
+    /// Spread the work over several transactions, e.g. as a batch, or with
+    /// [`crate::packing`] for independent instructions.
+    Split,
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LookupTable => write!(f, "load its accounts from an address lookup table"),
+            Self::ComputeUnitLimit(units) => write!(f, "request {} compute units", units),
+            Self::Split => write!(f, "split it into several transactions"),
+        }
+    }
+}
+
+/// What a transaction that passed the checks uses.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Preflight {
+    /// Serialized size in bytes.
+    pub size: usize,
+    /// Compute units the transaction requests.
+    pub compute_unit_limit: u32,
+    /// Compute units the simulation used, if the transport reports them.
+    pub units_consumed: Option<u64>,
+}
+
+#[derive(Debug, Error)]
+pub enum PreflightError {
+    #[error("transaction is {size} bytes, over the {PACKET_DATA_SIZE}-byte limit; {suggestion}")]
+    TooLarge { size: usize, suggestion: Suggestion },
+    #[error("transaction needs more than the {limit} compute units it requests; {suggestion}")]
+    ComputeBudgetExceeded {
+        limit: u32,
+        units_consumed: Option<u64>,
+        suggestion: Suggestion,
+    },
+    /// Simulation failed for another reason, e.g. a program error, which
+    /// sending would fail with as well.
+    #[error("simulation failed: {error}")]
+    Simulation {
+        error: TransactionError,
+        program_error: Option<DecodedError>,
+        logs: Vec<String>,
+    },
+    #[error(transparent)]
+    Rpc(#[from] io::Error),
+}
+
+/// Checks that `transaction` fits in a packet and within the compute units
+/// it requests.
+pub async fn preflight<R: TransactionSimulator>(
+    rpc: &R,
+    transaction: &VersionedTransaction,
+) -> Result<Preflight, PreflightError> {
+    let message = &transaction.message;
+    let size = wire_size(message);
+    if size > PACKET_DATA_SIZE {
+        let suggestion = if size_with_lookup_table(message) <= PACKET_DATA_SIZE {
+            Suggestion::LookupTable
+        } else {
+            Suggestion::Split
+        };
+        return Err(PreflightError::TooLarge { size, suggestion });
+    }
+
+    let limit = compute_unit_limit(message);
+    let simulation = rpc.simulate_transaction(transaction).await?;
+    let exceeded = match &simulation.error {
+        Some(TransactionError::InstructionError(
+            _,
+            InstructionError::ComputationalBudgetExceeded,
+        )) => true,
+        Some(error) => {
+            return Err(PreflightError::Simulation {
+                error: error.clone(),
+                program_error: decode_logs(&simulation.logs)
+                    .or_else(|| decode_transaction_error(error)),
+                logs: simulation.logs,
+            })
+        }
+        None => simulation
+            .units_consumed
+            .is_some_and(|units| units > limit as u64),
+    };
+    if exceeded {
+        let suggestion = if limit < MAX_COMPUTE_UNIT_LIMIT {
+            Suggestion::ComputeUnitLimit(MAX_COMPUTE_UNIT_LIMIT)
+        } else {
+            Suggestion::Split
+        };
+        return Err(PreflightError::ComputeBudgetExceeded {
+            limit,
+            units_consumed: simulation.units_consumed,
+            suggestion,
+        });
+    }
+    Ok(Preflight {
+        size,
+        compute_unit_limit: limit,
+        units_consumed: simulation.units_consumed,
+    })
+}
+
+/// The compute units `message` requests: its compute unit limit, or the
+/// default the runtime grants its instructions.
+pub fn compute_unit_limit(message: &VersionedMessage) -> u32 {
+    let keys = message.static_account_keys();
+    let mut metered = 0u32;
+    for instruction in message.instructions() {
+        if keys.get(instruction.program_id_index as usize) != Some(&compute_budget::ID) {
+            metered += 1;
+            continue;
+        }
+        if instruction.data.first() == Some(&SET_COMPUTE_UNIT_LIMIT) {
+            if let Some(units) = instruction.data.get(1..5) {
+                return u32::from_le_bytes(units.try_into().unwrap()).min(MAX_COMPUTE_UNIT_LIMIT);
+            }
+        }
+    }
+    DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT
+        .saturating_mul(metered)
+        .min(MAX_COMPUTE_UNIT_LIMIT)
+}
+
+/// Roughly the size `message` would have with every static account that is
+/// neither a signer nor an invoked program loaded from one new lookup table.
+fn size_with_lookup_table(message: &VersionedMessage) -> usize {
+    let keys = message.static_account_keys();
+    let num_signers = message.header().num_required_signatures as usize;
+    let movable = (num_signers..keys.len())
+        .filter(|&index| {
+            !message
+                .instructions()
+                .iter()
+                .any(|ix| ix.program_id_index as usize == index)
+        })
+        .count();
+    // A legacy message also gains the version prefix and the table list.
+    let v0_overhead = match message {
+        VersionedMessage::Legacy(_) => 2,
+        VersionedMessage::V0(_) => 0,
+    };
+    (wire_size(message) + v0_overhead + LOOKUP_TABLE_OVERHEAD + movable)
+        .saturating_sub(movable * 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use solana_instruction::{AccountMeta, Instruction};
+    use solana_message::Message;
+    use solana_pubkey::Pubkey;
+    use solana_signature::Signature;
+
+    use super::*;
+    use crate::compute_budget::set_compute_unit_limit;
+    use crate::errors::AstrolabeSmartAccountError;
+    use crate::mock::{MockOutcome, MockRpc};
+
+    fn transaction(instructions: &[Instruction]) -> VersionedTransaction {
+        VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message: VersionedMessage::Legacy(Message::new(
+                instructions,
+                Some(&Pubkey::new_unique()),
+            )),
+        }
+    }
+
+    fn instruction(data: Vec<u8>, num_accounts: usize) -> Instruction {
+        Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: (0..num_accounts)
+                .map(|_| AccountMeta::new_readonly(Pubkey::new_unique(), false))
+                .collect(),
+            data,
+        }
+    }
+
+    #[test]
+    fn compute_units_are_checked_against_the_requested_limit() {
+        let rpc = MockRpc::new();
+        let transaction = transaction(&[set_compute_unit_limit(50_000), instruction(vec![1], 1)]);
+
+        rpc.set_units_consumed(Some(30_000));
+        let result = block_on(preflight(&rpc, &transaction)).unwrap();
+        assert_eq!(result.compute_unit_limit, 50_000);
+        assert_eq!(result.units_consumed, Some(30_000));
+        assert_eq!(result.size, wire_size(&transaction.message));
+
+        rpc.set_units_consumed(Some(60_000));
+        match block_on(preflight(&rpc, &transaction)) {
+            Err(PreflightError::ComputeBudgetExceeded {
+                limit: 50_000,
+                units_consumed: Some(60_000),
+                suggestion: Suggestion::ComputeUnitLimit(MAX_COMPUTE_UNIT_LIMIT),
+            }) => {}
+            other => panic!("unexpected preflight result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failed_simulations_carry_the_program_error() {
+        let rpc = MockRpc::new();
+        rpc.push_outcome(MockOutcome::Failed(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(AstrolabeSmartAccountError::TimeLockNotReleased.code()),
+        )));
+        match block_on(preflight(&rpc, &transaction(&[instruction(vec![1], 1)]))) {
+            Err(PreflightError::Simulation { program_error, .. }) => assert_eq!(
+                program_error.unwrap().error,
+                AstrolabeSmartAccountError::TimeLockNotReleased
+            ),
+            other => panic!("unexpected preflight result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn oversized_transactions_are_not_simulated() {
+        let rpc = MockRpc::new();
+        // The accounts are what make it too large, so a table helps.
+        match block_on(preflight(&rpc, &transaction(&[instruction(vec![1], 40)]))) {
+            Err(PreflightError::TooLarge {
+                suggestion: Suggestion::LookupTable,
+                ..
+            }) => {}
+            other => panic!("unexpected preflight result: {:?}", other),
+        }
+        // The data is, and no table makes it smaller.
+        match block_on(preflight(
+            &rpc,
+            &transaction(&[instruction(vec![1; PACKET_DATA_SIZE], 1)]),
+        )) {
+            Err(PreflightError::TooLarge {
+                suggestion: Suggestion::Split,
+                ..
+            }) => {}
+            other => panic!("unexpected preflight result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Priority fees from recent prioritization fees.
+//!
+//! Fees are local to the accounts a transaction writes, so the estimate
+//! looks only at fees recently paid for the writable accounts of the
+//! instructions being sent. [`with_priority_fee`] prepends the compute budget
+//! instructions; [`crate::client::SmartAccountClient::with_priority_fee`]
+//! does so for every transaction the client sends.
+
+use std::io;
+
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_sdk_ids::compute_budget;
+
+use crate::compute_budget::{
+    set_compute_unit_limit, set_compute_unit_price, DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT,
+    MAX_COMPUTE_UNIT_LIMIT, SET_COMPUTE_UNIT_LIMIT, SET_COMPUTE_UNIT_PRICE,
+};
+use crate::rpc::TransactionSender;
+
+/// How the compute unit price is chosen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PriorityFeeConfig {
+    /// Percentile of the recent fees to pay, from 0 to 100.
+    pub percentile: u8,
+    /// Lower bound on the price, in micro-lamports per compute unit.
+    pub min_micro_lamports: u64,
+    /// Upper bound on the price, in micro-lamports per compute unit.
+    pub max_micro_lamports: u64,
+    /// Compute units to request when the instructions set no limit. The fee
+    /// is charged on the requested units, so a tight limit is cheaper.
+    /// Defaults to the units the runtime would grant the instructions.
+    pub compute_unit_limit: Option<u32>,
+}
+
+impl Default for PriorityFeeConfig {
+    fn default() -> Self {
+        Self {
+            percentile: 75,
+            min_micro_lamports: 0,
+            max_micro_lamports: 1_000_000,
+            compute_unit_limit: None,
+        }
+    }
+}
+
+/// The `percentile`th of `fees`, or zero if there are none.
+pub fn percentile_fee(fees: &[u64], percentile: u8) -> u64 {
+    let mut fees = fees.to_vec();
+    fees.sort_unstable();
+    let Some(last) = fees.len().checked_sub(1) else {
+        return 0;
+    };
+    fees[last * percentile.min(100) as usize / 100]
+}
+
+/// Accounts `instructions` write, which are the ones fees are local to.
+pub fn writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut accounts: Vec<Pubkey> = Vec::new();
+    for meta in instructions.iter().flat_map(|ix| &ix.accounts) {
+        if meta.is_writable && !accounts.contains(&meta.pubkey) {
+            accounts.push(meta.pubkey);
+        }
+    }
+    accounts
+}
+
+/// The compute unit price for `instructions`, in micro-lamports.
+pub async fn estimate_priority_fee<R: TransactionSender>(
+    rpc: &R,
+    instructions: &[Instruction],
+    config: &PriorityFeeConfig,
+) -> Result<u64, io::Error> {
+    let fees = rpc
+        .get_recent_prioritization_fees(&writable_accounts(instructions))
+        .await?;
+    Ok(percentile_fee(&fees, config.percentile)
+        .clamp(config.min_micro_lamports, config.max_micro_lamports))
+}
+
+/// Prepends a compute unit price of `micro_lamports`, and a compute unit
+/// limit, unless `instructions` already set them.
+pub fn add_compute_budget(
+    instructions: &[Instruction],
+    micro_lamports: u64,
+    config: &PriorityFeeConfig,
+) -> Vec<Instruction> {
+    let sets = |tag: u8| {
+        instructions
+            .iter()
+            .any(|ix| ix.program_id == compute_budget::ID && ix.data.first() == Some(&tag))
+    };
+    let mut budget = Vec::new();
+    if !sets(SET_COMPUTE_UNIT_LIMIT) {
+        let metered = instructions
+            .iter()
+            .filter(|ix| ix.program_id != compute_budget::ID)
+            .count();
+        let default_limit = DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT
+            .saturating_mul(metered as u32)
+            .min(MAX_COMPUTE_UNIT_LIMIT);
+        budget.push(set_compute_unit_limit(
+            config.compute_unit_limit.unwrap_or(default_limit),
+        ));
+    }
+    if !sets(SET_COMPUTE_UNIT_PRICE) {
+        budget.push(set_compute_unit_price(micro_lamports));
+    }
+    budget
+        .into_iter()
+        .chain(instructions.iter().cloned())
+        .collect()
+}
+
+/// Estimates the fee for `instructions` and prepends the compute budget
+/// instructions that pay it.
+pub async fn with_priority_fee<R: TransactionSender>(
+    rpc: &R,
+    instructions: &[Instruction],
+    config: &PriorityFeeConfig,
+) -> Result<Vec<Instruction>, io::Error> {
+    let micro_lamports = estimate_priority_fee(rpc, instructions, config).await?;
+    Ok(add_compute_budget(instructions, micro_lamports, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use solana_instruction::AccountMeta;
+
+    use super::*;
+    use crate::mock::MockRpc;
+
+    fn instruction() -> Instruction {
+        Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(Pubkey::new_unique(), true),
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            ],
+            data: vec![1],
+        }
+    }
+
+    #[test]
+    fn percentiles_pick_from_the_sorted_fees() {
+        let fees = [50, 10, 40, 20, 30];
+        assert_eq!(percentile_fee(&fees, 0), 10);
+        assert_eq!(percentile_fee(&fees, 75), 40);
+        assert_eq!(percentile_fee(&fees, 100), 50);
+        assert_eq!(percentile_fee(&fees, 255), 50);
+        assert_eq!(percentile_fee(&[], 75), 0);
+    }
+
+    #[test]
+    fn the_fee_is_clamped_and_prepended() {
+        let rpc = MockRpc::new();
+        rpc.set_prioritization_fees(vec![5, 2_000_000, 3_000_000]);
+        let instructions = [instruction(), instruction()];
+        let config = PriorityFeeConfig::default();
+
+        let budgeted = block_on(with_priority_fee(&rpc, &instructions, &config)).unwrap();
+        assert_eq!(
+            budgeted,
+            [
+                set_compute_unit_limit(2 * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT),
+                set_compute_unit_price(config.max_micro_lamports),
+                instructions[0].clone(),
+                instructions[1].clone(),
+            ]
+        );
+        // Instructions that already set their budget keep it.
+        assert_eq!(add_compute_budget(&budgeted, 1, &config), budgeted);
+    }
+
+    #[test]
+    fn only_writable_accounts_count() {
+        let instructions = [instruction(), instruction()];
+        assert_eq!(
+            writable_accounts(&instructions),
+            [
+                instructions[0].accounts[0].pubkey,
+                instructions[1].accounts[0].pubkey
+            ]
+        );
+    }
+}
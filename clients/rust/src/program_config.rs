@@ -0,0 +1,49 @@
+//! Instruction builders for the global program config.
+
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_sdk_ids::system_program;
+
+use crate::instructions::*;
+use crate::pda;
+
+pub fn initialize_program_config(
+    initializer: Pubkey,
+    args: InitializeProgramConfigInstructionArgs,
+) -> Instruction {
+    InitializeProgramConfig {
+        program_config: pda::program_config().0,
+        initializer,
+        system_program: system_program::ID,
+    }
+    .instruction(args)
+}
+
+pub fn set_program_config_authority(authority: Pubkey, new_authority: Pubkey) -> Instruction {
+    SetProgramConfigAuthority {
+        program_config: pda::program_config().0,
+        authority,
+    }
+    .instruction(SetProgramConfigAuthorityInstructionArgs { new_authority })
+}
+
+pub fn set_program_config_smart_account_creation_fee(
+    authority: Pubkey,
+    new_smart_account_creation_fee: u64,
+) -> Instruction {
+    SetProgramConfigSmartAccountCreationFee {
+        program_config: pda::program_config().0,
+        authority,
+    }
+    .instruction(SetProgramConfigSmartAccountCreationFeeInstructionArgs {
+        new_smart_account_creation_fee,
+    })
+}
+
+pub fn set_program_config_treasury(authority: Pubkey, new_treasury: Pubkey) -> Instruction {
+    SetProgramConfigTreasury {
+        program_config: pda::program_config().0,
+        authority,
+    }
+    .instruction(SetProgramConfigTreasuryInstructionArgs { new_treasury })
+}
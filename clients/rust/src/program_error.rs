@@ -0,0 +1,253 @@
+//! Program errors from failed transactions, with what to do about them.
+//!
+//! The program fails with Anchor custom error codes. [`decode_logs`] finds
+//! the code in a transaction's or simulation's log messages and
+//! [`decode_transaction_error`] in its `TransactionError`; both return a
+//! [`DecodedError`] carrying the [`AstrolabeSmartAccountError`] variant, its
+//! message and a remediation hint.
+
+use std::fmt;
+
+use num_traits::FromPrimitive;
+
+use crate::errors::AstrolabeSmartAccountError;
+
+const CUSTOM_ERROR_LOG: &str = " failed: custom program error: 0x";
+
+impl AstrolabeSmartAccountError {
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::from_u32(code)
+    }
+
+    pub fn code(&self) -> u32 {
+        self.clone() as u32
+    }
+
+    /// What usually fixes the error, for showing next to its message.
+    pub fn hint(&self) -> &'static str {
+        use AstrolabeSmartAccountError::*;
+        match self {
+            AccountNotEmpty => "Close or drain the account before reusing it.",
+            DuplicateSigner => "List each signer key only once.",
+            EmptySigners => "Add at least one signer.",
+            TooManySigners => "Remove signers; at most 65535 are allowed.",
+            InvalidThreshold => {
+                "Set a threshold between 1 and the number of signers with the vote permission."
+            }
+            Unauthorized => {
+                "Sign with the key this action requires, e.g. the settings or program config authority."
+            }
+            NotASigner => "Sign with a key that is a signer of the smart account.",
+            InvalidTransactionMessage => {
+                "Rebuild the transaction message; account indexes or header counts are inconsistent."
+            }
+            StaleProposal => {
+                "The settings changed after this proposal was created; create a new transaction."
+            }
+            InvalidProposalStatus => {
+                "Fetch the proposal; it is not in a status that allows this action."
+            }
+            InvalidTransactionIndex => {
+                "Use the next transaction index, the settings' transaction_index plus one."
+            }
+            AlreadyApproved => "This signer already approved; nothing to do.",
+            AlreadyRejected => "This signer already rejected; nothing to do.",
+            AlreadyCancelled => "This signer already cancelled; nothing to do.",
+            InvalidNumberOfAccounts => {
+                "Pass every account the inner instructions use as remaining accounts, in message order."
+            }
+            InvalidAccount => "Check the accounts passed against the PDAs the program derives.",
+            RemoveLastSigner => "Add another signer before removing this one.",
+            NoVoters => "Give at least one signer the vote permission.",
+            NoProposers => "Give at least one signer the initiate permission.",
+            NoExecutors => "Give at least one signer the execute permission.",
+            InvalidStaleTransactionIndex => {
+                "stale_transaction_index must not exceed transaction_index."
+            }
+            NotSupportedForControlled => {
+                "Use the settings authority instructions of a controlled smart account instead."
+            }
+            TimeLockNotReleased => "Wait until the time lock after approval has passed.",
+            NoActions => "Add at least one settings action.",
+            MissingAccount => "Pass the optional account this instruction needs.",
+            InvalidMint => "Use the mint the spending limit was created for.",
+            InvalidDestination => "Send to one of the spending limit's destinations.",
+            SpendingLimitExceeded => {
+                "Send less, or wait for the spending limit's period to reset."
+            }
+            DecimalsMismatch => "Pass the mint's decimals.",
+            SpendingLimitExpired => "Create a new spending limit; this one has expired.",
+            UnknownPermission => "Use only the initiate, vote and execute permission bits.",
+            ProtectedAccount => {
+                "Do not pass the settings or proposal accounts as writable to the inner instructions."
+            }
+            TimeLockExceedsMaxAllowed => "Use a time lock of at most 90 days.",
+            IllegalAccountOwner => "Pass an account owned by the smart account program.",
+            RentReclamationDisabled => {
+                "Settings have no rent collector in this program version; rent goes to the rent collector each transaction and proposal records. Check the cluster runs the program this client targets."
+            }
+            InvalidRentCollector => {
+                "Pass the rent collector recorded on the transaction or proposal being closed."
+            }
+            ProposalForAnotherSmartAccount | TransactionForAnotherSmartAccount => {
+                "Derive the proposal and transaction from the same settings account."
+            }
+            TransactionNotMatchingProposal => {
+                "Pass the proposal with the same index as the transaction."
+            }
+            TransactionNotLastInBatch => {
+                "Close the batch's transactions from the last one backwards."
+            }
+            BatchNotEmpty => "Close the batch's transactions before closing the batch.",
+            SpendingLimitInvalidAmount => "Use a non-zero amount.",
+            InvalidInstructionArgs => "Check the instruction arguments.",
+            FinalBufferHashMismatch => {
+                "Upload the whole message to the buffer again; its hash does not match."
+            }
+            FinalBufferSizeExceeded => "Split the transaction; buffers hold at most 4000 bytes.",
+            FinalBufferSizeMismatch => {
+                "Upload the remaining chunks before creating the transaction from the buffer."
+            }
+            SmartAccountCreateDeprecated => "Use smart_account_create_v2.",
+            ThresholdNotReached => "Collect more approvals, or signatures for sync instructions.",
+            InvalidSignerCount => "Pass at least as many signers as the threshold.",
+            MissingSignature => "Sign with every signer passed to the instruction.",
+            InsufficientAggregatePermissions => {
+                "Include signers that together hold the initiate, vote and execute permissions."
+            }
+            InsufficientVotePermissions => {
+                "Include enough signers with the vote permission to reach the threshold."
+            }
+            TimeLockNotZero => "Sync instructions need a smart account without a time lock.",
+            NotImplemented => "This instruction is not supported by the deployed program yet.",
+        }
+    }
+}
+
+/// A program error from a failed transaction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecodedError {
+    /// The failing instruction, when the source reports it.
+    pub instruction_index: Option<u8>,
+    pub error: AstrolabeSmartAccountError,
+}
+
+impl DecodedError {
+    pub fn code(&self) -> u32 {
+        self.error.code()
+    }
+
+    pub fn message(&self) -> String {
+        self.error.to_string()
+    }
+
+    pub fn hint(&self) -> &'static str {
+        self.error.hint()
+    }
+}
+
+impl fmt::Display for DecodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}). {}", self.error, self.code(), self.hint())
+    }
+}
+
+/// The error the program failed with, from the log messages of a
+/// transaction or simulation. Only failures logged by this program count,
+/// so errors of programs it invokes, or that invoke it, are not mistaken
+/// for its own.
+pub fn decode_logs<S: AsRef<str>>(logs: &[S]) -> Option<DecodedError> {
+    let program = crate::ID.to_string();
+    logs.iter().find_map(|line| {
+        let (invoked, code) = line
+            .as_ref()
+            .strip_prefix("Program ")?
+            .split_once(CUSTOM_ERROR_LOG)?;
+        if invoked != program {
+            return None;
+        }
+        let code = u32::from_str_radix(code.trim(), 16).ok()?;
+        Some(DecodedError {
+            instruction_index: None,
+            error: AstrolabeSmartAccountError::from_code(code)?,
+        })
+    })
+}
+
+/// The program error in `error`, if an instruction failed with one of the
+/// program's codes. The code may come from another Anchor program the
+/// failing instruction invoked; [`decode_logs`] tells them apart.
+#[cfg(feature = "client")]
+pub fn decode_transaction_error(
+    error: &solana_transaction_error::TransactionError,
+) -> Option<DecodedError> {
+    use solana_instruction::error::InstructionError;
+    use solana_transaction_error::TransactionError;
+
+    let TransactionError::InstructionError(index, InstructionError::Custom(code)) = error else {
+        return None;
+    };
+    Some(DecodedError {
+        instruction_index: Some(*index),
+        error: AstrolabeSmartAccountError::from_code(*code)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_failures_of_the_program_are_decoded_from_logs() {
+        let program = crate::ID;
+        let other = solana_pubkey::Pubkey::new_unique();
+        let logs = [
+            format!("Program {} invoke [1]", program),
+            format!("Program {} invoke [2]", other),
+            format!("Program {} failed: custom program error: 0x1771", other),
+            format!("Program {} failed: custom program error: 0x1786", program),
+        ];
+        let decoded = decode_logs(&logs).unwrap();
+        assert_eq!(
+            decoded.error,
+            AstrolabeSmartAccountError::TimeLockNotReleased
+        );
+        assert_eq!(decoded.instruction_index, None);
+        assert_eq!(decoded.code(), 0x1786);
+        assert_eq!(
+            decoded.to_string(),
+            "Proposal time lock has not been released (6022). \
+             Wait until the time lock after approval has passed."
+        );
+
+        // Codes outside the program's range and other failures are not its
+        // errors.
+        let logs = [
+            format!("Program {} failed: custom program error: 0x1", program),
+            format!("Program {} failed: insufficient funds", program),
+        ];
+        assert_eq!(decode_logs(&logs), None);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn transaction_errors_keep_the_failing_instruction() {
+        use solana_instruction::error::InstructionError;
+        use solana_transaction_error::TransactionError;
+
+        let error = TransactionError::InstructionError(2, InstructionError::Custom(0x1770));
+        assert_eq!(
+            decode_transaction_error(&error),
+            Some(DecodedError {
+                instruction_index: Some(2),
+                error: AstrolabeSmartAccountError::AccountNotEmpty,
+            })
+        );
+        let error = TransactionError::InstructionError(0, InstructionError::InvalidArgument);
+        assert_eq!(decode_transaction_error(&error), None);
+        assert_eq!(
+            decode_transaction_error(&TransactionError::AccountNotFound),
+            None
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Rent and fee estimates for governance accounts.
+//!
+//! Sizes come from [`crate::layout`]; transaction accounts hold the message
+//! in the form `SmartAccount::transaction_args` serializes it. Rent uses the
+//! default rent parameters every public cluster runs with.
+
+use solana_rent::Rent;
+
+use crate::accounts::{
+    Batch, BatchTransaction, Proposal, Settings, SettingsTransaction, Transaction,
+};
+use crate::types::{SettingsAction, SmartAccountTransactionMessage};
+#[cfg(feature = "client")]
+use {
+    crate::decode::{TransactionAccount, VersionedProposal, VersionedSettings},
+    crate::lifecycle::{is_closable, TransactionKind},
+    crate::rpc::{fetch_multiple, fetch_transaction_page, index_pages, AccountFetcher},
+    solana_pubkey::Pubkey,
+    std::io,
+};
+
+/// Base fee charged per transaction signature.
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+pub fn transaction_account_size(
+    ephemeral_signers: u8,
+    message: &SmartAccountTransactionMessage,
+) -> usize {
+    Transaction::size(ephemeral_signers, message.size())
+}
+
+pub fn settings_transaction_size(actions: &[SettingsAction]) -> usize {
+    SettingsTransaction::size(actions)
+}
+
+/// Size of a proposal on a smart account with `num_signers` signers.
+pub fn proposal_size(num_signers: usize) -> usize {
+    Proposal::size(num_signers)
+}
+
+pub fn batch_transaction_size(
+    ephemeral_signers: u8,
+    message: &SmartAccountTransactionMessage,
+) -> usize {
+    BatchTransaction::size(ephemeral_signers, message.size())
+}
+
+pub fn settings_rent(num_signers: usize, num_restricted_signers: usize) -> u64 {
+    rent(Settings::size(num_signers, num_restricted_signers))
+}
+
+pub fn transaction_rent(ephemeral_signers: u8, message: &SmartAccountTransactionMessage) -> u64 {
+    rent(transaction_account_size(ephemeral_signers, message))
+}
+
+pub fn settings_transaction_rent(actions: &[SettingsAction]) -> u64 {
+    rent(settings_transaction_size(actions))
+}
+
+pub fn proposal_rent(num_signers: usize) -> u64 {
+    rent(proposal_size(num_signers))
+}
+
+pub fn batch_rent() -> u64 {
+    rent(Batch::LEN)
+}
+
+pub fn batch_transaction_rent(
+    ephemeral_signers: u8,
+    message: &SmartAccountTransactionMessage,
+) -> u64 {
+    rent(batch_transaction_size(ephemeral_signers, message))
+}
+
+/// Base fee of a transaction with `num_signatures` signatures, before any
+/// priority fee.
+pub fn transaction_fee(num_signatures: usize) -> u64 {
+    num_signatures as u64 * LAMPORTS_PER_SIGNATURE
+}
+
+/// Rent-exempt minimum for an account of `size` bytes.
+pub fn rent(size: usize) -> u64 {
+    Rent::default().minimum_balance(size)
+}
+
+/// Lamports held by transactions and proposals of `settings` that can be
+/// closed now, by [`is_closable`]. Batches are not counted, since closing
+/// them takes one instruction per inner transaction.
+///
+/// Every index since the first is looked up, one request at a time, so a
+/// long history takes one round trip per
+/// [`INDEXES_PER_PAGE`](crate::rpc::INDEXES_PER_PAGE) indexes.
+#[cfg(feature = "client")]
+pub async fn fetch_reclaimable_rent<R: AccountFetcher>(
+    rpc: &R,
+    settings: &Pubkey,
+) -> Result<u64, io::Error> {
+    let account = fetch_multiple(rpc, &[*settings])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Account not found: {}", settings),
+            )
+        })?;
+    let settings_data = VersionedSettings::from_bytes(&account.data)?.into_current();
+
+    let mut total = 0;
+    for page in index_pages(1, settings_data.transaction_index) {
+        for (index, transaction, proposal) in fetch_transaction_page(rpc, settings, page).await? {
+            let Some(transaction) = transaction else {
+                continue;
+            };
+            let kind = TransactionKind::from(&TransactionAccount::from_bytes(&transaction.data)?);
+            if kind == TransactionKind::Batch {
+                continue;
+            }
+            let status = proposal
+                .as_ref()
+                .map(|account| VersionedProposal::from_bytes(&account.data))
+                .transpose()?
+                .map(|proposal| proposal.into_current().status);
+            if is_closable(
+                kind,
+                status.as_ref(),
+                index <= settings_data.stale_transaction_index,
+            ) {
+                total += transaction.lamports + proposal.map_or(0, |p| p.lamports);
+            }
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::decode::{
+        BATCH_DISCRIMINATOR, PROPOSAL_DISCRIMINATOR, SETTINGS_DISCRIMINATOR,
+        SETTINGS_TRANSACTION_DISCRIMINATOR,
+    };
+    use crate::mock::MockRpc;
+    use crate::pda;
+    use crate::rpc::INDEXES_PER_PAGE;
+    use crate::types::ProposalStatus;
+
+    const SETTINGS: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    fn set_settings_transaction(rpc: &MockRpc, index: u64) -> u64 {
+        let transaction = SettingsTransaction {
+            discriminator: SETTINGS_TRANSACTION_DISCRIMINATOR,
+            settings: SETTINGS,
+            creator: Pubkey::default(),
+            rent_collector: Pubkey::default(),
+            index,
+            bump: 0,
+            actions: Vec::new(),
+        };
+        rpc.set_program_account(pda::transaction(&SETTINGS, index).0, &transaction);
+        rent(borsh::to_vec(&transaction).unwrap().len())
+    }
+
+    fn set_proposal(rpc: &MockRpc, index: u64, status: ProposalStatus) -> u64 {
+        let proposal = Proposal {
+            discriminator: PROPOSAL_DISCRIMINATOR,
+            settings: SETTINGS,
+            transaction_index: index,
+            rent_collector: Pubkey::default(),
+            status,
+            bump: 0,
+            approved: Vec::new(),
+            rejected: Vec::new(),
+            cancelled: Vec::new(),
+        };
+        rpc.set_program_account_with_space(
+            pda::proposal(&SETTINGS, index).0,
+            &proposal,
+            Proposal::size(1),
+        );
+        rent(Proposal::size(1))
+    }
+
+    #[test]
+    fn reclaimable_rent_counts_closable_transactions_across_pages() {
+        let rpc = MockRpc::new();
+        rpc.set_program_account_with_space(
+            SETTINGS,
+            &Settings {
+                discriminator: SETTINGS_DISCRIMINATOR,
+                seed: 0,
+                settings_authority: Pubkey::default(),
+                threshold: 1,
+                time_lock: 0,
+                transaction_index: 2 * INDEXES_PER_PAGE + 10,
+                stale_transaction_index: 2 * INDEXES_PER_PAGE,
+                archival_authority: None,
+                archivable_after: 0,
+                bump: 0,
+                signers: Vec::new(),
+                restricted_signers: Vec::new(),
+                account_utilization: 0,
+                reserved1: 0,
+                reserved2: 0,
+            },
+            Settings::size(0, 0),
+        );
+        let mut expected = 0;
+
+        // Executed, on the first page.
+        expected += set_settings_transaction(&rpc, 1);
+        expected += set_proposal(&rpc, 1, ProposalStatus::Executed { timestamp: 0 });
+        // Stale without a proposal, on the second page.
+        expected += set_settings_transaction(&rpc, INDEXES_PER_PAGE + 1);
+        // Stale and approved, which a settings transaction can no longer
+        // execute.
+        expected += set_settings_transaction(&rpc, 2 * INDEXES_PER_PAGE);
+        expected += set_proposal(
+            &rpc,
+            2 * INDEXES_PER_PAGE,
+            ProposalStatus::Approved { timestamp: 0 },
+        );
+        // Approved but not stale, and executing.
+        set_settings_transaction(&rpc, 2 * INDEXES_PER_PAGE + 1);
+        set_proposal(
+            &rpc,
+            2 * INDEXES_PER_PAGE + 1,
+            ProposalStatus::Approved { timestamp: 0 },
+        );
+        set_settings_transaction(&rpc, 2 * INDEXES_PER_PAGE + 2);
+        set_proposal(&rpc, 2 * INDEXES_PER_PAGE + 2, ProposalStatus::Executing);
+        // A proposal whose transaction was closed already.
+        set_proposal(&rpc, 3, ProposalStatus::Rejected { timestamp: 0 });
+        // Batches are left out.
+        rpc.set_program_account(
+            pda::transaction(&SETTINGS, 4).0,
+            &Batch {
+                discriminator: BATCH_DISCRIMINATOR,
+                settings: SETTINGS,
+                creator: Pubkey::default(),
+                rent_collector: Pubkey::default(),
+                index: 4,
+                bump: 0,
+                account_index: 0,
+                account_bump: 0,
+                size: 0,
+                executed_transaction_index: 0,
+            },
+        );
+        set_proposal(&rpc, 4, ProposalStatus::Executed { timestamp: 0 });
+
+        assert_eq!(
+            block_on(fetch_reclaimable_rent(&rpc, &SETTINGS)).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn fees_and_rent_scale_with_size() {
+        assert_eq!(transaction_fee(0), 0);
+        assert_eq!(transaction_fee(3), 3 * LAMPORTS_PER_SIGNATURE);
+        assert!(rent(0) > 0);
+        assert!(proposal_rent(2) > proposal_rent(1));
+        // Every signer can appear in each of the three vote lists.
+        assert_eq!(proposal_rent(1) - proposal_rent(0), rent(3 * 32) - rent(0));
+    }
+}
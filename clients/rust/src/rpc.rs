@@ -0,0 +1,495 @@
+//! Async fetch-and-decode helpers.
+//!
+//! `AccountFetcher` is the only transport these helpers need, and
+//! `TransactionSender` adds what [`crate::client::SmartAccountClient`] needs
+//! to land transactions. With the `fetch` feature both are implemented for
+//! the nonblocking `RpcClient`; tests and other transports can implement
+//! them directly. Both also hold for shared references, so one transport
+//! can back several clients.
+
+use std::future::Future;
+use std::io;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use futures::future::try_join_all;
+use solana_account::Account;
+use solana_hash::Hash;
+use solana_instruction::AccountMeta;
+use solana_message::AddressLookupTableAccount;
+use solana_pubkey::Pubkey;
+use solana_sdk_ids::sysvar;
+use solana_signature::Signature;
+use solana_transaction::versioned::VersionedTransaction;
+
+use crate::accounts::{Proposal, Settings};
+use crate::decode::{TransactionAccount, VersionedProposal, VersionedSettings};
+use crate::pda;
+use crate::sender::SendError;
+use crate::types::SmartAccountTransactionMessage;
+
+/// Upper bound on addresses per `getMultipleAccounts` request.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+/// Transaction indexes [`fetch_transaction_page`] looks up per request, two
+/// accounts each.
+pub const INDEXES_PER_PAGE: u64 = MAX_MULTIPLE_ACCOUNTS as u64 / 2;
+/// Size of the metadata that precedes the addresses in a lookup table.
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+/// Offset of `unix_timestamp` in the clock sysvar.
+const CLOCK_UNIX_TIMESTAMP_OFFSET: usize = 32;
+
+pub trait AccountFetcher {
+    /// Returns one entry per address, `None` where the account does not exist.
+    fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> impl Future<Output = Result<Vec<Option<Account>>, io::Error>> + Send;
+}
+
+#[cfg(feature = "fetch")]
+impl AccountFetcher for solana_client::nonblocking::rpc_client::RpcClient {
+    async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<Option<Account>>, io::Error> {
+        solana_client::nonblocking::rpc_client::RpcClient::get_multiple_accounts(self, addresses)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+impl<T: AccountFetcher + Sync> AccountFetcher for &T {
+    fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> impl Future<Output = Result<Vec<Option<Account>>, io::Error>> + Send {
+        (**self).get_multiple_accounts(addresses)
+    }
+}
+
+pub trait TransactionSender {
+    fn get_latest_blockhash(&self) -> impl Future<Output = Result<Hash, io::Error>> + Send;
+
+    /// Sends `transaction` and resolves once it is confirmed, or with an
+    /// error if it fails or is not confirmed before its blockhash expires.
+    /// Legacy transactions convert with `VersionedTransaction::from`.
+    ///
+    /// Failures the cluster reports for the transaction itself are
+    /// [`SendError::Transaction`], so callers can tell them from transport
+    /// errors, which may be retried.
+    fn send_and_confirm_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> impl Future<Output = Result<Signature, SendError>> + Send;
+
+    /// Resolves after `duration`, yielding to the transport's runtime.
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send;
+
+    /// Prioritization fees paid in recent slots by transactions that locked
+    /// any of `writable_accounts` for writing, in micro-lamports per compute
+    /// unit. Transports without fee data report none, which leaves fees at
+    /// the configured minimum.
+    fn get_recent_prioritization_fees(
+        &self,
+        _writable_accounts: &[Pubkey],
+    ) -> impl Future<Output = Result<Vec<u64>, io::Error>> + Send {
+        std::future::ready(Ok(Vec::new()))
+    }
+}
+
+impl<T: TransactionSender + Sync> TransactionSender for &T {
+    fn get_latest_blockhash(&self) -> impl Future<Output = Result<Hash, io::Error>> + Send {
+        (**self).get_latest_blockhash()
+    }
+
+    fn send_and_confirm_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> impl Future<Output = Result<Signature, SendError>> + Send {
+        (**self).send_and_confirm_transaction(transaction)
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        (**self).sleep(duration)
+    }
+
+    fn get_recent_prioritization_fees(
+        &self,
+        writable_accounts: &[Pubkey],
+    ) -> impl Future<Output = Result<Vec<u64>, io::Error>> + Send {
+        (**self).get_recent_prioritization_fees(writable_accounts)
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl TransactionSender for solana_client::nonblocking::rpc_client::RpcClient {
+    async fn get_latest_blockhash(&self) -> Result<Hash, io::Error> {
+        solana_client::nonblocking::rpc_client::RpcClient::get_latest_blockhash(self)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    async fn send_and_confirm_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<Signature, SendError> {
+        solana_client::nonblocking::rpc_client::RpcClient::send_and_confirm_transaction(
+            self,
+            transaction,
+        )
+        .await
+        .map_err(|e| match e.get_transaction_error() {
+            Some(error) => SendError::Transaction {
+                signature: None,
+                error,
+            },
+            None => SendError::Network(io::Error::other(e.to_string())),
+        })
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        writable_accounts: &[Pubkey],
+    ) -> Result<Vec<u64>, io::Error> {
+        Ok(
+            solana_client::nonblocking::rpc_client::RpcClient::get_recent_prioritization_fees(
+                self,
+                writable_accounts,
+            )
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect(),
+        )
+    }
+}
+
+/// A smart account's settings together with its live transactions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SmartAccountState {
+    pub settings: Settings,
+    pub transactions: Vec<TransactionState>,
+}
+
+/// The accounts at one transaction index; either may have been closed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransactionState {
+    pub transaction_index: u64,
+    pub transaction: Option<TransactionAccount>,
+    pub proposal: Option<Proposal>,
+}
+
+/// Fetches `settings` and every transaction and proposal after
+/// `stale_transaction_index`, one page of [`INDEXES_PER_PAGE`] indexes per
+/// request. Indexes whose accounts have all been closed are omitted.
+///
+/// Stale transactions can still be open: approved vault transactions and
+/// batches stay executable, and the others wait to be closed. With
+/// `include_stale` the lookup starts from the first index to include them,
+/// which costs one lookup per transaction ever created.
+pub async fn fetch_smart_account_state<R: AccountFetcher>(
+    rpc: &R,
+    settings: &Pubkey,
+    include_stale: bool,
+) -> Result<SmartAccountState, io::Error> {
+    let account = fetch_multiple(rpc, &[*settings])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Account not found: {}", settings),
+            )
+        })?;
+    let settings_data = VersionedSettings::from_bytes(&account.data)?.into_current();
+
+    let first = if include_stale {
+        1
+    } else {
+        settings_data.stale_transaction_index + 1
+    };
+    let mut transactions = Vec::new();
+    for page in index_pages(first, settings_data.transaction_index) {
+        for (transaction_index, transaction, proposal) in
+            fetch_transaction_page(rpc, settings, page).await?
+        {
+            let transaction = transaction
+                .map(|account| TransactionAccount::from_bytes(&account.data))
+                .transpose()?;
+            let proposal = proposal
+                .map(|account| {
+                    VersionedProposal::from_bytes(&account.data)
+                        .map(VersionedProposal::into_current)
+                })
+                .transpose()?;
+            if transaction.is_some() || proposal.is_some() {
+                transactions.push(TransactionState {
+                    transaction_index,
+                    transaction,
+                    proposal,
+                });
+            }
+        }
+    }
+
+    Ok(SmartAccountState {
+        settings: settings_data,
+        transactions,
+    })
+}
+
+/// Resolves the remaining accounts for `execute_transaction` on the vault
+/// transaction at `transaction_index`, fetching any lookup tables it uses.
+pub async fn fetch_execution_accounts<R: AccountFetcher>(
+    rpc: &R,
+    settings: &Pubkey,
+    transaction_index: u64,
+) -> Result<Vec<AccountMeta>, io::Error> {
+    let address = pda::transaction(settings, transaction_index).0;
+    let account = fetch_multiple(rpc, &[address])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Account not found: {}", address),
+            )
+        })?;
+    let TransactionAccount::Transaction(transaction) =
+        TransactionAccount::from_bytes(&account.data)?
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a vault transaction", address),
+        ));
+    };
+    let tables = fetch_address_lookup_tables(rpc, &transaction.message).await?;
+    transaction
+        .message
+        .execution_accounts(&tables)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Fetches the lookup tables referenced by `message`.
+pub async fn fetch_address_lookup_tables<R: AccountFetcher>(
+    rpc: &R,
+    message: &SmartAccountTransactionMessage,
+) -> Result<Vec<AddressLookupTableAccount>, io::Error> {
+    let keys: Vec<Pubkey> = message
+        .address_table_lookups
+        .iter()
+        .map(|lookup| lookup.account_key)
+        .collect();
+    let accounts = fetch_multiple(rpc, &keys).await?;
+    keys.into_iter()
+        .zip(accounts)
+        .map(|(key, account)| {
+            let account = account.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Account not found: {}", key),
+                )
+            })?;
+            let addresses = account
+                .data
+                .get(LOOKUP_TABLE_META_SIZE..)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} is not an address lookup table", key),
+                    )
+                })?
+                .chunks_exact(32)
+                .map(|chunk| Pubkey::try_from(chunk).unwrap())
+                .collect();
+            Ok(AddressLookupTableAccount { key, addresses })
+        })
+        .collect()
+}
+
+/// Current unix timestamp on the cluster clock, which is what time locks
+/// are measured against.
+pub async fn fetch_unix_timestamp<R: AccountFetcher>(rpc: &R) -> Result<i64, io::Error> {
+    let clock = fetch_multiple(rpc, &[sysvar::clock::ID])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "clock sysvar not found"))?;
+    unix_timestamp(&clock.data)
+}
+
+/// Reads `unix_timestamp` from the data of the clock sysvar.
+pub fn unix_timestamp(clock: &[u8]) -> Result<i64, io::Error> {
+    clock
+        .get(CLOCK_UNIX_TIMESTAMP_OFFSET..CLOCK_UNIX_TIMESTAMP_OFFSET + 8)
+        .map(|bytes| i64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed clock sysvar"))
+}
+
+/// Splits the transaction indexes `first..=last` into pages of at most
+/// [`INDEXES_PER_PAGE`], so a long history is fetched one request at a
+/// time rather than all at once.
+pub fn index_pages(first: u64, last: u64) -> impl Iterator<Item = RangeInclusive<u64>> {
+    (first..=last)
+        .step_by(INDEXES_PER_PAGE as usize)
+        .map(move |start| start..=last.min(start.saturating_add(INDEXES_PER_PAGE - 1)))
+}
+
+/// Fetches the transaction and proposal of `settings` at every index of
+/// `page`, one of [`index_pages`], in a single request. Either account is
+/// `None` where it was closed or never created.
+pub async fn fetch_transaction_page<R: AccountFetcher>(
+    rpc: &R,
+    settings: &Pubkey,
+    page: RangeInclusive<u64>,
+) -> Result<Vec<(u64, Option<Account>, Option<Account>)>, io::Error> {
+    let addresses: Vec<Pubkey> = page
+        .clone()
+        .flat_map(|index| {
+            [
+                pda::transaction(settings, index).0,
+                pda::proposal(settings, index).0,
+            ]
+        })
+        .collect();
+    let mut accounts = fetch_multiple(rpc, &addresses).await?.into_iter();
+    Ok(page
+        .map(|index| (index, accounts.next().flatten(), accounts.next().flatten()))
+        .collect())
+}
+
+/// `get_multiple_accounts` over any number of addresses, one concurrent
+/// request per `MAX_MULTIPLE_ACCOUNTS` chunk.
+pub async fn fetch_multiple<R: AccountFetcher>(
+    rpc: &R,
+    addresses: &[Pubkey],
+) -> Result<Vec<Option<Account>>, io::Error> {
+    let chunks = try_join_all(
+        addresses
+            .chunks(MAX_MULTIPLE_ACCOUNTS)
+            .map(|chunk| rpc.get_multiple_accounts(chunk)),
+    )
+    .await?;
+    let accounts: Vec<_> = chunks.into_iter().flatten().collect();
+    if accounts.len() != addresses.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "requested {} accounts, received {}",
+                addresses.len(),
+                accounts.len()
+            ),
+        ));
+    }
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::decode::{PROPOSAL_DISCRIMINATOR, SETTINGS_DISCRIMINATOR};
+    use crate::mock::MockRpc;
+    use crate::types::ProposalStatus;
+
+    const SETTINGS: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn rpc_client_implements_the_transports() {
+        fn transports<R>()
+        where
+            R: AccountFetcher
+                + TransactionSender
+                + crate::preflight::TransactionSimulator
+                + crate::scan::ProgramAccountScanner
+                + crate::sender::TransactionTransport,
+        {
+        }
+        transports::<solana_client::nonblocking::rpc_client::RpcClient>();
+    }
+
+    #[test]
+    fn index_pages_cover_the_range_in_bounded_pages() {
+        let pages: Vec<_> = index_pages(1, 2 * INDEXES_PER_PAGE + 1).collect();
+        assert_eq!(
+            pages,
+            [
+                1..=INDEXES_PER_PAGE,
+                INDEXES_PER_PAGE + 1..=2 * INDEXES_PER_PAGE,
+                2 * INDEXES_PER_PAGE + 1..=2 * INDEXES_PER_PAGE + 1,
+            ]
+        );
+        assert_eq!(index_pages(5, 4).count(), 0);
+        assert_eq!(
+            index_pages(u64::MAX, u64::MAX).collect::<Vec<_>>(),
+            [u64::MAX..=u64::MAX]
+        );
+    }
+
+    #[test]
+    fn smart_account_state_includes_stale_transactions_on_request() {
+        // Spread over three pages.
+        let last = 2 * INDEXES_PER_PAGE + 1;
+        let rpc = MockRpc::new();
+        rpc.set_program_account_with_space(
+            SETTINGS,
+            &Settings {
+                discriminator: SETTINGS_DISCRIMINATOR,
+                seed: 0,
+                settings_authority: Pubkey::default(),
+                threshold: 1,
+                time_lock: 0,
+                transaction_index: last,
+                stale_transaction_index: INDEXES_PER_PAGE + 1,
+                archival_authority: None,
+                archivable_after: 0,
+                bump: 0,
+                signers: Vec::new(),
+                restricted_signers: Vec::new(),
+                account_utilization: 0,
+                reserved1: 0,
+                reserved2: 0,
+            },
+            Settings::size(0, 0),
+        );
+        for index in [1, INDEXES_PER_PAGE + 1, last] {
+            rpc.set_program_account_with_space(
+                pda::proposal(&SETTINGS, index).0,
+                &Proposal {
+                    discriminator: PROPOSAL_DISCRIMINATOR,
+                    settings: SETTINGS,
+                    transaction_index: index,
+                    rent_collector: Pubkey::default(),
+                    status: ProposalStatus::Approved { timestamp: 0 },
+                    bump: 0,
+                    approved: Vec::new(),
+                    rejected: Vec::new(),
+                    cancelled: Vec::new(),
+                },
+                Proposal::size(0),
+            );
+        }
+
+        let indexes = |include_stale| {
+            block_on(fetch_smart_account_state(&rpc, &SETTINGS, include_stale))
+                .unwrap()
+                .transactions
+                .iter()
+                .map(|transaction| transaction.transaction_index)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(indexes(false), [last]);
+        assert_eq!(indexes(true), [1, INDEXES_PER_PAGE + 1, last]);
+    }
+}
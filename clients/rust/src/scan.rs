@@ -0,0 +1,303 @@
+//! Cached, paginated `getProgramAccounts` queries.
+//!
+//! [`ScanCache`] keeps the result of each filter query with the slot it was
+//! read at and serves it until it is older than `max_age`, so dashboards can
+//! page through hundreds of proposals without a full scan per refresh.
+//! Account notifications, e.g. from a `programSubscribe` stream, are folded
+//! in with [`ScanCache::apply_update`], which keeps cached queries current
+//! between scans. Pages are keyed by address, so a cursor stays valid when
+//! accounts are added or closed.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::io;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use solana_account::Account;
+use solana_pubkey::Pubkey;
+
+use crate::accounts::Proposal;
+use crate::decode::VersionedProposal;
+use crate::filters::{self, Filter};
+
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(30);
+
+pub trait ProgramAccountScanner {
+    /// Returns the program's accounts matching every filter, with the slot
+    /// the result reflects.
+    fn get_program_accounts(
+        &self,
+        filters: &[Filter],
+    ) -> impl Future<Output = Result<(u64, Vec<(Pubkey, Account)>), io::Error>> + Send;
+}
+
+/// The slot is read before the scan, so it is a lower bound: updates from
+/// earlier slots are already reflected.
+#[cfg(feature = "fetch")]
+impl ProgramAccountScanner for solana_client::nonblocking::rpc_client::RpcClient {
+    async fn get_program_accounts(
+        &self,
+        filters: &[Filter],
+    ) -> Result<(u64, Vec<(Pubkey, Account)>), io::Error> {
+        let slot = self
+            .get_slot()
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let config = solana_client::rpc_config::RpcProgramAccountsConfig {
+            filters: Some(filters.iter().cloned().map(Into::into).collect()),
+            ..Default::default()
+        };
+        let accounts = self
+            .get_program_accounts_with_config(&crate::ID, config)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        Ok((slot, accounts))
+    }
+}
+
+/// One cached query result.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Scan {
+    /// Slot of the most recent scan or update reflected.
+    pub slot: u64,
+    pub accounts: BTreeMap<Pubkey, Account>,
+}
+
+/// A page of a query, in address order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Page {
+    pub slot: u64,
+    pub accounts: Vec<(Pubkey, Account)>,
+    /// Pass as `after` to get the next page; `None` on the last page.
+    pub next: Option<Pubkey>,
+}
+
+struct Entry {
+    scan: Arc<Scan>,
+    fetched_at: Instant,
+}
+
+pub struct ScanCache<R> {
+    rpc: R,
+    max_age: Duration,
+    entries: Mutex<HashMap<Vec<Filter>, Entry>>,
+}
+
+impl<R: ProgramAccountScanner> ScanCache<R> {
+    pub fn new(rpc: R) -> Self {
+        Self {
+            rpc,
+            max_age: DEFAULT_MAX_AGE,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How long a query is served from the cache before it is scanned again.
+    /// With a live update stream this can be long.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn rpc(&self) -> &R {
+        &self.rpc
+    }
+
+    /// All accounts matching `filters`, from the cache if it is fresh.
+    pub async fn scan(&self, filters: &[Filter]) -> Result<Arc<Scan>, io::Error> {
+        if let Some(entry) = self.entries().get(filters) {
+            if entry.fetched_at.elapsed() < self.max_age {
+                return Ok(entry.scan.clone());
+            }
+        }
+        let (slot, accounts) = self.rpc.get_program_accounts(filters).await?;
+        let scan = Arc::new(Scan {
+            slot,
+            accounts: accounts.into_iter().collect(),
+        });
+        self.entries().insert(
+            filters.to_vec(),
+            Entry {
+                scan: scan.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+        Ok(scan)
+    }
+
+    /// Up to `limit` accounts matching `filters` with addresses after
+    /// `after`, or from the start if `after` is `None`.
+    pub async fn page(
+        &self,
+        filters: &[Filter],
+        after: Option<Pubkey>,
+        limit: usize,
+    ) -> Result<Page, io::Error> {
+        let scan = self.scan(filters).await?;
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut accounts = scan.accounts.range((start, Bound::Unbounded));
+        let page: Vec<(Pubkey, Account)> = accounts
+            .by_ref()
+            .take(limit)
+            .map(|(address, account)| (*address, account.clone()))
+            .collect();
+        let next = match accounts.next() {
+            Some(_) => page.last().map(|(address, _)| *address),
+            None => None,
+        };
+        Ok(Page {
+            slot: scan.slot,
+            accounts: page,
+            next,
+        })
+    }
+
+    /// A page of the proposals of `settings`, decoded. Undecodable accounts
+    /// are skipped.
+    pub async fn proposals_page(
+        &self,
+        settings: &Pubkey,
+        after: Option<Pubkey>,
+        limit: usize,
+    ) -> Result<(Vec<(Pubkey, Proposal)>, Option<Pubkey>), io::Error> {
+        let page = self
+            .page(&filters::proposals(settings), after, limit)
+            .await?;
+        let proposals = page
+            .accounts
+            .into_iter()
+            .filter_map(|(address, account)| {
+                VersionedProposal::from_bytes(&account.data)
+                    .ok()
+                    .map(|proposal| (address, proposal.into_current()))
+            })
+            .collect();
+        Ok((proposals, page.next))
+    }
+
+    /// Applies a notification that `address` changed at `slot`; `None`
+    /// means it was closed. Every cached query the account now matches, or
+    /// no longer matches, is updated. Notifications older than a query's
+    /// scan are ignored for it.
+    pub fn apply_update(&self, address: Pubkey, slot: u64, account: Option<Account>) {
+        for (filters, entry) in self.entries().iter_mut() {
+            if slot < entry.scan.slot {
+                continue;
+            }
+            let scan = Arc::make_mut(&mut entry.scan);
+            scan.slot = slot;
+            match &account {
+                Some(account)
+                    if account.owner == crate::ID
+                        && filters.iter().all(|filter| filter.matches(&account.data)) =>
+                {
+                    scan.accounts.insert(address, account.clone());
+                }
+                _ => {
+                    scan.accounts.remove(&address);
+                }
+            }
+        }
+    }
+
+    /// Drops the cached result of `filters`, so the next read scans.
+    pub fn invalidate(&self, filters: &[Filter]) {
+        self.entries().remove(filters);
+    }
+
+    pub fn clear(&self) {
+        self.entries().clear();
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<Vec<Filter>, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::decode::PROPOSAL_DISCRIMINATOR;
+    use crate::mock::MockRpc;
+    use crate::types::ProposalStatus;
+
+    fn proposal(settings: Pubkey, transaction_index: u64) -> Proposal {
+        Proposal {
+            discriminator: PROPOSAL_DISCRIMINATOR,
+            settings,
+            transaction_index,
+            rent_collector: Pubkey::default(),
+            status: ProposalStatus::Active { timestamp: 0 },
+            bump: 255,
+            approved: Vec::new(),
+            rejected: Vec::new(),
+            cancelled: Vec::new(),
+        }
+    }
+
+    fn add_proposal(rpc: &MockRpc, settings: Pubkey, index: u64) -> Pubkey {
+        let address = Pubkey::new_unique();
+        rpc.set_program_account_with_space(address, &proposal(settings, index), Proposal::size(1));
+        address
+    }
+
+    #[test]
+    fn proposals_are_paged_by_address() {
+        let settings = Pubkey::new_unique();
+        let cache = ScanCache::new(MockRpc::new());
+        let mut addresses: Vec<Pubkey> = (1..=3)
+            .map(|index| add_proposal(cache.rpc(), settings, index))
+            .collect();
+        addresses.sort();
+        add_proposal(cache.rpc(), Pubkey::new_unique(), 1);
+
+        let (first, next) = block_on(cache.proposals_page(&settings, None, 2)).unwrap();
+        assert_eq!(
+            first
+                .iter()
+                .map(|(address, _)| *address)
+                .collect::<Vec<_>>(),
+            addresses[..2]
+        );
+        assert_eq!(next, Some(addresses[1]));
+        let (last, next) = block_on(cache.proposals_page(&settings, next, 2)).unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].0, addresses[2]);
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn updates_keep_cached_queries_current() {
+        let settings = Pubkey::new_unique();
+        let cache = ScanCache::new(MockRpc::new()).with_max_age(Duration::from_secs(3600));
+        cache.rpc().set_clock(10, 0);
+        let first = add_proposal(cache.rpc(), settings, 1);
+        let query = filters::proposals(&settings);
+        assert_eq!(block_on(cache.scan(&query)).unwrap().accounts.len(), 1);
+
+        // Served from the cache until an update or invalidation.
+        let second = add_proposal(cache.rpc(), settings, 2);
+        assert_eq!(block_on(cache.scan(&query)).unwrap().accounts.len(), 1);
+        cache.apply_update(second, 11, cache.rpc().account(&second));
+        // Older than the scan, so ignored.
+        cache.apply_update(first, 9, None);
+        let scan = block_on(cache.scan(&query)).unwrap();
+        assert_eq!(scan.slot, 11);
+        assert_eq!(scan.accounts.len(), 2);
+
+        cache.apply_update(first, 12, None);
+        let scan = block_on(cache.scan(&query)).unwrap();
+        assert_eq!(scan.accounts.keys().collect::<Vec<_>>(), [&second]);
+
+        // A new scan sees the cluster again, where `first` was never
+        // closed.
+        cache.rpc().remove_account(&second);
+        cache.invalidate(&query);
+        let scan = block_on(cache.scan(&query)).unwrap();
+        assert_eq!(scan.accounts.keys().collect::<Vec<_>>(), [&first]);
+    }
+}
@@ -0,0 +1,595 @@
+//! Sending and confirming transactions with a configurable strategy.
+//!
+//! [`StrategySender`] drives a [`TransactionTransport`]: it rebroadcasts a
+//! transaction until it reaches the requested commitment, re-signs with a
+//! fresh blockhash when the old one expires, and reports failures as
+//! [`SendError`] so callers can tell a program rejecting the transaction
+//! apart from the network dropping it. It also implements
+//! [`TransactionSender`], so [`crate::client::SmartAccountClient`] can use it.
+
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use solana_hash::Hash;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_signer::Signer;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction_error::TransactionError;
+use thiserror::Error;
+
+use crate::program_error::{decode_transaction_error, DecodedError};
+use crate::rpc::TransactionSender;
+use crate::transaction::{BuildError, TransactionFormat};
+
+/// Blocks a blockhash stays valid for after the block that produced it.
+const MAX_PROCESSING_AGE: u64 = 150;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Commitment {
+    Processed,
+    #[default]
+    Confirmed,
+    Finalized,
+}
+
+#[cfg(feature = "fetch")]
+impl From<Commitment> for solana_commitment_config::CommitmentConfig {
+    fn from(commitment: Commitment) -> Self {
+        match commitment {
+            Commitment::Processed => Self::processed(),
+            Commitment::Confirmed => Self::confirmed(),
+            Commitment::Finalized => Self::finalized(),
+        }
+    }
+}
+
+/// Options for a single `sendTransaction` call.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SendOptions {
+    pub skip_preflight: bool,
+    pub preflight_commitment: Commitment,
+}
+
+/// Where a sent transaction stands.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SignatureStatus {
+    pub slot: u64,
+    /// `None` once the transaction is rooted on a node that no longer
+    /// reports levels, which is at least finalized.
+    pub commitment: Option<Commitment>,
+    pub err: Option<TransactionError>,
+}
+
+impl SignatureStatus {
+    pub fn satisfies(&self, commitment: Commitment) -> bool {
+        self.commitment.is_none_or(|reached| reached >= commitment)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SendError {
+    /// The transport failed; the transaction may or may not have landed.
+    #[error(transparent)]
+    Network(#[from] io::Error),
+    /// The transaction was rejected in preflight, in which case there is no
+    /// signature, or it landed and failed.
+    #[error("transaction failed: {error}")]
+    Transaction {
+        signature: Option<Signature>,
+        error: TransactionError,
+    },
+    #[error("transaction {0} expired before it was confirmed")]
+    Expired(Signature),
+    #[error(transparent)]
+    Build(#[from] BuildError),
+}
+
+impl SendError {
+    /// Whether an instruction returned an error, as opposed to the
+    /// transaction failing for reasons like fees or an unknown blockhash.
+    pub fn is_program_error(&self) -> bool {
+        matches!(
+            self,
+            Self::Transaction {
+                error: TransactionError::InstructionError(..),
+                ..
+            }
+        )
+    }
+
+    /// The program error an instruction failed with, if any.
+    pub fn program_error(&self) -> Option<DecodedError> {
+        match self {
+            Self::Transaction { error, .. } => decode_transaction_error(error),
+            _ => None,
+        }
+    }
+}
+
+/// The RPC calls [`StrategySender`] is built on.
+pub trait TransactionTransport {
+    /// Returns the blockhash and the last block height it is valid for.
+    fn get_latest_blockhash(
+        &self,
+        commitment: Commitment,
+    ) -> impl Future<Output = Result<(Hash, u64), io::Error>> + Send;
+
+    fn get_block_height(
+        &self,
+        commitment: Commitment,
+    ) -> impl Future<Output = Result<u64, io::Error>> + Send;
+
+    /// Submits `transaction` without waiting for it. Preflight failures are
+    /// reported as [`SendError::Transaction`].
+    fn send_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        options: SendOptions,
+    ) -> impl Future<Output = Result<Signature, SendError>> + Send;
+
+    fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> impl Future<Output = Result<Option<SignatureStatus>, io::Error>> + Send;
+
+    /// Resolves after `duration`, yielding to the transport's runtime.
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send;
+
+    /// See [`TransactionSender::get_recent_prioritization_fees`].
+    fn get_recent_prioritization_fees(
+        &self,
+        _writable_accounts: &[Pubkey],
+    ) -> impl Future<Output = Result<Vec<u64>, io::Error>> + Send {
+        std::future::ready(Ok(Vec::new()))
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl TransactionTransport for solana_client::nonblocking::rpc_client::RpcClient {
+    async fn get_latest_blockhash(&self, commitment: Commitment) -> Result<(Hash, u64), io::Error> {
+        self.get_latest_blockhash_with_commitment(commitment.into())
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    async fn get_block_height(&self, commitment: Commitment) -> Result<u64, io::Error> {
+        self.get_block_height_with_commitment(commitment.into())
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    async fn send_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        options: SendOptions,
+    ) -> Result<Signature, SendError> {
+        let config = solana_client::rpc_config::RpcSendTransactionConfig {
+            skip_preflight: options.skip_preflight,
+            preflight_commitment: Some(
+                solana_commitment_config::CommitmentConfig::from(options.preflight_commitment)
+                    .commitment,
+            ),
+            ..Default::default()
+        };
+        self.send_transaction_with_config(transaction, config)
+            .await
+            .map_err(|e| match e.get_transaction_error() {
+                Some(error) => SendError::Transaction {
+                    signature: None,
+                    error,
+                },
+                None => SendError::Network(io::Error::other(e.to_string())),
+            })
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<SignatureStatus>, io::Error> {
+        use solana_transaction_status_client_types::TransactionConfirmationStatus;
+
+        let mut statuses = self
+            .get_signature_statuses(&[*signature])
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?
+            .value;
+        Ok(statuses.pop().flatten().map(|status| SignatureStatus {
+            slot: status.slot,
+            commitment: status
+                .confirmation_status
+                .map(|confirmation| match confirmation {
+                    TransactionConfirmationStatus::Processed => Commitment::Processed,
+                    TransactionConfirmationStatus::Confirmed => Commitment::Confirmed,
+                    TransactionConfirmationStatus::Finalized => Commitment::Finalized,
+                }),
+            err: status.err,
+        }))
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        writable_accounts: &[Pubkey],
+    ) -> Result<Vec<u64>, io::Error> {
+        TransactionSender::get_recent_prioritization_fees(self, writable_accounts).await
+    }
+}
+
+/// How [`StrategySender`] sends and confirms.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SendStrategy {
+    /// Options for the first submission. Rebroadcasts always skip preflight,
+    /// since a copy that already landed would fail simulation.
+    pub options: SendOptions,
+    /// Commitment a transaction must reach to count as confirmed.
+    pub commitment: Commitment,
+    /// How often an unconfirmed transaction is rebroadcast.
+    pub resend_interval: Duration,
+    /// How many times to re-sign with a fresh blockhash after expiry.
+    /// Only [`StrategySender::send_instructions`] can re-sign.
+    pub max_blockhash_refreshes: usize,
+    /// Format of the transactions [`StrategySender::send_instructions`]
+    /// builds.
+    pub format: TransactionFormat,
+}
+
+impl Default for SendStrategy {
+    fn default() -> Self {
+        Self {
+            options: SendOptions::default(),
+            commitment: Commitment::Confirmed,
+            resend_interval: Duration::from_secs(2),
+            max_blockhash_refreshes: 3,
+            format: TransactionFormat::default(),
+        }
+    }
+}
+
+pub struct StrategySender<R> {
+    transport: R,
+    strategy: SendStrategy,
+}
+
+impl<R: TransactionTransport> StrategySender<R> {
+    pub fn new(transport: R, strategy: SendStrategy) -> Self {
+        Self {
+            transport,
+            strategy,
+        }
+    }
+
+    pub fn transport(&self) -> &R {
+        &self.transport
+    }
+
+    pub fn strategy(&self) -> &SendStrategy {
+        &self.strategy
+    }
+
+    /// Signs `instructions` with a fresh blockhash and sends them. If the
+    /// blockhash expires first, the transaction is re-signed and resent up
+    /// to `max_blockhash_refreshes` times.
+    pub async fn send_instructions(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        signers: &[&dyn Signer],
+    ) -> Result<Signature, SendError> {
+        let mut refreshes = 0;
+        loop {
+            let (blockhash, last_valid_block_height) = self
+                .transport
+                .get_latest_blockhash(self.strategy.commitment)
+                .await?;
+            let transaction = self
+                .strategy
+                .format
+                .sign(payer, instructions, signers, blockhash)?;
+            match self
+                .send_and_confirm(&transaction, last_valid_block_height)
+                .await
+            {
+                Err(SendError::Expired(_)) if refreshes < self.strategy.max_blockhash_refreshes => {
+                    refreshes += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends a signed transaction and rebroadcasts it until it reaches the
+    /// strategy's commitment, fails, or passes `last_valid_block_height`.
+    ///
+    /// A send that fails in transit is rebroadcast like one that was not
+    /// confirmed yet: resending the same signed transaction cannot execute
+    /// it twice. A transaction the cluster rejects is not resent.
+    pub async fn send_and_confirm(
+        &self,
+        transaction: &VersionedTransaction,
+        last_valid_block_height: u64,
+    ) -> Result<Signature, SendError> {
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+        match self
+            .transport
+            .send_transaction(transaction, self.strategy.options)
+            .await
+        {
+            Ok(_) | Err(SendError::Network(_)) => {}
+            Err(e) => return Err(e),
+        }
+        let rebroadcast = SendOptions {
+            skip_preflight: true,
+            ..self.strategy.options
+        };
+        loop {
+            self.transport.sleep(self.strategy.resend_interval).await;
+            match self.transport.get_signature_status(&signature).await? {
+                Some(SignatureStatus {
+                    err: Some(error), ..
+                }) => {
+                    return Err(SendError::Transaction {
+                        signature: Some(signature),
+                        error,
+                    })
+                }
+                Some(status) if status.satisfies(self.strategy.commitment) => return Ok(signature),
+                Some(_) => {}
+                None => {
+                    let block_height = self
+                        .transport
+                        .get_block_height(self.strategy.commitment)
+                        .await?;
+                    if block_height > last_valid_block_height {
+                        // It may have landed in the last blocks it was valid
+                        // for, after the status above was read. Look once
+                        // more so that it is not re-signed and sent twice.
+                        if self
+                            .transport
+                            .get_signature_status(&signature)
+                            .await?
+                            .is_none()
+                        {
+                            return Err(SendError::Expired(signature));
+                        }
+                        continue;
+                    }
+                    match self
+                        .transport
+                        .send_transaction(transaction, rebroadcast)
+                        .await
+                    {
+                        Ok(_) | Err(SendError::Network(_)) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Transactions handed over already signed cannot be re-signed, so their
+/// expiry is estimated from the current block height.
+impl<R: TransactionTransport + Sync> TransactionSender for StrategySender<R> {
+    async fn get_latest_blockhash(&self) -> Result<Hash, io::Error> {
+        Ok(self
+            .transport
+            .get_latest_blockhash(self.strategy.commitment)
+            .await?
+            .0)
+    }
+
+    async fn send_and_confirm_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<Signature, SendError> {
+        let block_height = self
+            .transport
+            .get_block_height(self.strategy.commitment)
+            .await?;
+        self.send_and_confirm(transaction, block_height + MAX_PROCESSING_AGE)
+            .await
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        self.transport.sleep(duration).await
+    }
+
+    async fn get_recent_prioritization_fees(
+        &self,
+        writable_accounts: &[Pubkey],
+    ) -> Result<Vec<u64>, io::Error> {
+        self.transport
+            .get_recent_prioritization_fees(writable_accounts)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use futures::executor::block_on;
+    use solana_instruction::error::InstructionError;
+    use solana_keypair::Keypair;
+
+    use super::*;
+    use crate::mock::{MockOutcome, MockRpc};
+
+    fn sender(max_blockhash_refreshes: usize) -> StrategySender<MockRpc> {
+        StrategySender::new(
+            MockRpc::new(),
+            SendStrategy {
+                max_blockhash_refreshes,
+                ..SendStrategy::default()
+            },
+        )
+    }
+
+    fn send(sender: &StrategySender<MockRpc>, payer: &Keypair) -> Result<Signature, SendError> {
+        block_on(sender.send_instructions(&[], &payer.pubkey(), &[payer]))
+    }
+
+    fn program_error() -> TransactionError {
+        TransactionError::InstructionError(0, InstructionError::Custom(6000))
+    }
+
+    #[test]
+    fn send_and_confirm_rebroadcasts_until_the_blockhash_expires() {
+        let sender = sender(0);
+        let payer = Keypair::new();
+        let transaction = TransactionFormat::Legacy
+            .sign(&payer.pubkey(), &[], &[&payer], Hash::new_unique())
+            .unwrap();
+        sender.transport().push_outcome(MockOutcome::Dropped);
+        let result = block_on(sender.send_and_confirm(&transaction, 3));
+        assert!(matches!(result, Err(SendError::Expired(s)) if s == transaction.signatures[0]));
+        // The first send and a rebroadcast at each of block heights 1 to 3.
+        let sent = sender.transport().sent_transactions();
+        assert_eq!(sent.len(), 4);
+        assert!(sent.iter().all(|sent| *sent == transaction));
+    }
+
+    /// Reports a transaction that was never confirmed as landed once the
+    /// block height passes `expires_at`, as if it landed in the last block
+    /// it was valid for.
+    struct LandsAtExpiry {
+        inner: MockRpc,
+        expires_at: u64,
+        landed: AtomicBool,
+    }
+
+    impl TransactionTransport for LandsAtExpiry {
+        async fn get_latest_blockhash(
+            &self,
+            commitment: Commitment,
+        ) -> Result<(Hash, u64), io::Error> {
+            TransactionTransport::get_latest_blockhash(&self.inner, commitment).await
+        }
+
+        async fn get_block_height(&self, commitment: Commitment) -> Result<u64, io::Error> {
+            let block_height = self.inner.get_block_height(commitment).await?;
+            if block_height > self.expires_at {
+                self.landed.store(true, Ordering::Relaxed);
+            }
+            Ok(block_height)
+        }
+
+        async fn send_transaction(
+            &self,
+            transaction: &VersionedTransaction,
+            options: SendOptions,
+        ) -> Result<Signature, SendError> {
+            self.inner.send_transaction(transaction, options).await
+        }
+
+        async fn get_signature_status(
+            &self,
+            signature: &Signature,
+        ) -> Result<Option<SignatureStatus>, io::Error> {
+            if !self.landed.load(Ordering::Relaxed) {
+                return self.inner.get_signature_status(signature).await;
+            }
+            Ok(Some(SignatureStatus {
+                slot: 0,
+                commitment: Some(Commitment::Finalized),
+                err: None,
+            }))
+        }
+
+        async fn sleep(&self, duration: Duration) {
+            TransactionTransport::sleep(&self.inner, duration).await
+        }
+    }
+
+    #[test]
+    fn a_transaction_landing_as_its_blockhash_expires_is_not_expired() {
+        let transport = LandsAtExpiry {
+            inner: MockRpc::new(),
+            expires_at: 3,
+            landed: Default::default(),
+        };
+        transport.inner.push_outcome(MockOutcome::Dropped);
+        let sender = StrategySender::new(transport, SendStrategy::default());
+        let payer = Keypair::new();
+        let transaction = TransactionFormat::Legacy
+            .sign(&payer.pubkey(), &[], &[&payer], Hash::new_unique())
+            .unwrap();
+        assert_eq!(
+            block_on(sender.send_and_confirm(&transaction, 3)).unwrap(),
+            transaction.signatures[0]
+        );
+        assert_eq!(sender.transport().inner.sent_transactions().len(), 4);
+    }
+
+    #[test]
+    fn send_instructions_resigns_after_the_blockhash_expires() {
+        let sender = self::sender(1);
+        let payer = Keypair::new();
+        sender.transport().push_outcome(MockOutcome::Dropped);
+        let signature = send(&sender, &payer).unwrap();
+        let sent = sender.transport().sent_transactions();
+        let (first, last) = (&sent[0], &sent[sent.len() - 1]);
+        assert_ne!(first.signatures[0], signature);
+        assert_eq!(last.signatures[0], signature);
+        assert_ne!(
+            first.message.recent_blockhash(),
+            last.message.recent_blockhash()
+        );
+
+        let sender = self::sender(0);
+        sender.transport().push_outcome(MockOutcome::Dropped);
+        assert!(matches!(send(&sender, &payer), Err(SendError::Expired(_))));
+    }
+
+    #[test]
+    fn program_errors_are_not_resent() {
+        let sender = sender(3);
+        let payer = Keypair::new();
+        sender
+            .transport()
+            .push_outcome(MockOutcome::Rejected(program_error()));
+        let error = send(&sender, &payer).unwrap_err();
+        assert!(error.is_program_error());
+        assert!(matches!(
+            error,
+            SendError::Transaction {
+                signature: None,
+                ..
+            }
+        ));
+        assert_eq!(sender.transport().sent_transactions().len(), 1);
+
+        let sender = self::sender(3);
+        sender
+            .transport()
+            .push_outcome(MockOutcome::Failed(program_error()));
+        let error = send(&sender, &payer).unwrap_err();
+        assert!(error.is_program_error());
+        assert!(matches!(
+            error,
+            SendError::Transaction {
+                signature: Some(_),
+                ..
+            }
+        ));
+        assert_eq!(sender.transport().sent_transactions().len(), 1);
+    }
+
+    #[test]
+    fn network_errors_are_resent() {
+        let sender = sender(0);
+        let payer = Keypair::new();
+        sender.transport().push_outcome(MockOutcome::NetworkError);
+        sender.transport().push_outcome(MockOutcome::NetworkError);
+        let signature = send(&sender, &payer).unwrap();
+        let sent = sender.transport().sent_transactions();
+        assert_eq!(sent.len(), 3);
+        assert!(sent.iter().all(|sent| sent.signatures[0] == signature));
+    }
+}
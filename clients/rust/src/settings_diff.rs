@@ -0,0 +1,399 @@
+//! Previews of what a settings transaction changes.
+//!
+//! [`SettingsDiff::new`] applies a settings transaction's actions to the
+//! current settings the way the program does and records every change, so
+//! voters can review a config proposal instead of its raw actions. The
+//! `Display` impl renders the diff one change per line.
+
+use std::fmt;
+
+use solana_pubkey::Pubkey;
+
+use crate::accounts::Settings;
+use crate::types::{Period, Permissions, SettingsAction, SmartAccountSigner};
+
+/// A spending limit created by an `AddSpendingLimit` action.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewSpendingLimit {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub seed: Pubkey,
+    pub account_index: u8,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub period: Period,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<Vec<serde_with::DisplayFromStr>>")
+    )]
+    pub signers: Vec<Pubkey>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<Vec<serde_with::DisplayFromStr>>")
+    )]
+    pub destinations: Vec<Pubkey>,
+    pub expiration: i64,
+}
+
+/// A signer whose permissions a settings transaction changes, by removing
+/// and adding it again.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PermissionChange {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub key: Pubkey,
+    pub before: Permissions,
+    pub after: Permissions,
+}
+
+/// The settings after a settings transaction, and how they differ.
+/// Changed scalars are `Some((before, after))`. Signers are compared by
+/// key, so a signer that is removed and added back with other permissions
+/// shows up in `permissions_changed` only.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SettingsDiff {
+    pub after: Settings,
+    pub signers_added: Vec<SmartAccountSigner>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<Vec<serde_with::DisplayFromStr>>")
+    )]
+    pub signers_removed: Vec<Pubkey>,
+    pub permissions_changed: Vec<PermissionChange>,
+    /// Keys added while they were already signers. The program fails the
+    /// transaction with `DuplicateSigner`; `after` lists them twice.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<Vec<serde_with::DisplayFromStr>>")
+    )]
+    pub duplicate_signers: Vec<Pubkey>,
+    pub threshold: Option<(u16, u16)>,
+    pub time_lock: Option<(u32, u32)>,
+    pub archival_authority: Option<(Option<Pubkey>, Option<Pubkey>)>,
+    pub spending_limits_added: Vec<NewSpendingLimit>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<Vec<serde_with::DisplayFromStr>>")
+    )]
+    pub spending_limits_removed: Vec<Pubkey>,
+}
+
+impl SettingsDiff {
+    /// Applies `actions` to `settings` in order. Changes to signers,
+    /// threshold or time lock make every pending transaction stale, as
+    /// executing them on-chain does.
+    pub fn new(settings: &Settings, actions: &[SettingsAction]) -> Self {
+        let mut after = settings.clone();
+        let mut spending_limits_added = Vec::new();
+        let mut spending_limits_removed = Vec::new();
+        let mut duplicate_signers = Vec::new();
+        let mut invalidates = false;
+
+        for action in actions {
+            match action {
+                SettingsAction::AddSigner { new_signer } => {
+                    if after
+                        .signers
+                        .iter()
+                        .any(|signer| signer.key == new_signer.key)
+                    {
+                        duplicate_signers.push(new_signer.key);
+                    }
+                    after.signers.push(new_signer.clone());
+                    after.signers.sort_by_key(|signer| signer.key);
+                    invalidates = true;
+                }
+                SettingsAction::RemoveSigner { old_signer } => {
+                    after.signers.retain(|signer| signer.key != *old_signer);
+                    invalidates = true;
+                }
+                SettingsAction::ChangeThreshold { new_threshold } => {
+                    after.threshold = *new_threshold;
+                    invalidates = true;
+                }
+                SettingsAction::SetTimeLock { new_time_lock } => {
+                    after.time_lock = *new_time_lock;
+                    invalidates = true;
+                }
+                SettingsAction::AddSpendingLimit {
+                    seed,
+                    account_index,
+                    mint,
+                    amount,
+                    period,
+                    signers,
+                    destinations,
+                    expiration,
+                } => spending_limits_added.push(NewSpendingLimit {
+                    seed: *seed,
+                    account_index: *account_index,
+                    mint: *mint,
+                    amount: *amount,
+                    period: *period,
+                    signers: signers.clone(),
+                    destinations: destinations.clone(),
+                    expiration: *expiration,
+                }),
+                SettingsAction::RemoveSpendingLimit { spending_limit } => {
+                    spending_limits_removed.push(*spending_limit)
+                }
+                SettingsAction::SetArchivalAuthority {
+                    new_archival_authority,
+                } => after.archival_authority = *new_archival_authority,
+            }
+        }
+        if invalidates {
+            after.stale_transaction_index = after.transaction_index;
+        }
+
+        let find = |signers: &[SmartAccountSigner], key: &Pubkey| {
+            signers
+                .iter()
+                .find(|signer| signer.key == *key)
+                .map(|signer| signer.permissions.clone())
+        };
+        let mut signers_added: Vec<SmartAccountSigner> = Vec::new();
+        for signer in &after.signers {
+            if find(&settings.signers, &signer.key).is_none()
+                && find(&signers_added, &signer.key).is_none()
+            {
+                signers_added.push(signer.clone());
+            }
+        }
+        let signers_removed = settings
+            .signers
+            .iter()
+            .filter(|signer| find(&after.signers, &signer.key).is_none())
+            .map(|signer| signer.key)
+            .collect();
+        let permissions_changed = settings
+            .signers
+            .iter()
+            .filter_map(|signer| {
+                let permissions = find(&after.signers, &signer.key)?;
+                (permissions != signer.permissions).then(|| PermissionChange {
+                    key: signer.key,
+                    before: signer.permissions.clone(),
+                    after: permissions,
+                })
+            })
+            .collect();
+
+        Self {
+            signers_added,
+            signers_removed,
+            permissions_changed,
+            duplicate_signers,
+            threshold: changed(settings.threshold, after.threshold),
+            time_lock: changed(settings.time_lock, after.time_lock),
+            archival_authority: changed(settings.archival_authority, after.archival_authority),
+            spending_limits_added,
+            spending_limits_removed,
+            after,
+        }
+    }
+
+    /// Signers left with the vote permission.
+    pub fn voters(&self) -> usize {
+        self.after
+            .signers
+            .iter()
+            .filter(|signer| signer.permissions.has(Permissions::VOTE))
+            .count()
+    }
+
+    /// Whether the threshold can still be met by the voters left.
+    pub fn is_satisfiable(&self) -> bool {
+        self.after.threshold >= 1 && self.after.threshold as usize <= self.voters()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signers_added.is_empty()
+            && self.signers_removed.is_empty()
+            && self.permissions_changed.is_empty()
+            && self.duplicate_signers.is_empty()
+            && self.threshold.is_none()
+            && self.time_lock.is_none()
+            && self.archival_authority.is_none()
+            && self.spending_limits_added.is_empty()
+            && self.spending_limits_removed.is_empty()
+    }
+}
+
+impl fmt::Display for SettingsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        for signer in &self.signers_added {
+            writeln!(
+                f,
+                "+ signer {} (permissions {:#05b})",
+                signer.key, signer.permissions.mask
+            )?;
+        }
+        for key in &self.signers_removed {
+            writeln!(f, "- signer {}", key)?;
+        }
+        for change in &self.permissions_changed {
+            writeln!(
+                f,
+                "  signer {} permissions: {:#05b} -> {:#05b}",
+                change.key, change.before.mask, change.after.mask
+            )?;
+        }
+        if let Some((before, after)) = self.threshold {
+            writeln!(f, "  threshold: {} -> {}", before, after)?;
+        }
+        if let Some((before, after)) = self.time_lock {
+            writeln!(f, "  time lock: {}s -> {}s", before, after)?;
+        }
+        if let Some((before, after)) = self.archival_authority {
+            writeln!(
+                f,
+                "  archival authority: {} -> {}",
+                display_optional(before),
+                display_optional(after)
+            )?;
+        }
+        for limit in &self.spending_limits_added {
+            writeln!(
+                f,
+                "+ spending limit {}: {} of mint {} per {:?} from account {}",
+                limit.seed, limit.amount, limit.mint, limit.period, limit.account_index
+            )?;
+        }
+        for spending_limit in &self.spending_limits_removed {
+            writeln!(f, "- spending limit {}", spending_limit)?;
+        }
+        for key in &self.duplicate_signers {
+            writeln!(
+                f,
+                "! signer {} is added twice; the program rejects this",
+                key
+            )?;
+        }
+        if !self.is_satisfiable() {
+            writeln!(
+                f,
+                "! threshold {} cannot be met by {} voters",
+                self.after.threshold,
+                self.voters()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn changed<T: PartialEq>(before: T, after: T) -> Option<(T, T)> {
+    (before != after).then_some((before, after))
+}
+
+fn display_optional(key: Option<Pubkey>) -> String {
+    key.map_or_else(|| "none".to_string(), |key| key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(key: u8, mask: u8) -> SmartAccountSigner {
+        SmartAccountSigner {
+            key: Pubkey::new_from_array([key; 32]),
+            permissions: Permissions { mask },
+        }
+    }
+
+    fn settings(signers: Vec<SmartAccountSigner>, threshold: u16) -> Settings {
+        Settings {
+            discriminator: [0; 8],
+            seed: 0,
+            settings_authority: Pubkey::default(),
+            threshold,
+            time_lock: 0,
+            transaction_index: 5,
+            stale_transaction_index: 0,
+            archival_authority: None,
+            archivable_after: 0,
+            bump: 0,
+            signers,
+            restricted_signers: Vec::new(),
+            account_utilization: 0,
+            reserved1: 0,
+            reserved2: 0,
+        }
+    }
+
+    #[test]
+    fn adding_an_existing_signer_is_reported() {
+        let before = settings(vec![signer(1, 7)], 1);
+        let diff = SettingsDiff::new(
+            &before,
+            &[SettingsAction::AddSigner {
+                new_signer: signer(1, 2),
+            }],
+        );
+        assert_eq!(diff.duplicate_signers, [signer(1, 0).key]);
+        assert_eq!(diff.after.signers.len(), 2);
+        assert!(diff.signers_added.is_empty());
+        assert!(diff.to_string().contains("added twice"));
+    }
+
+    #[test]
+    fn permission_changes_are_reported_apart_from_additions() {
+        let before = settings(vec![signer(1, 7), signer(2, 7)], 1);
+        let diff = SettingsDiff::new(
+            &before,
+            &[
+                SettingsAction::RemoveSigner {
+                    old_signer: signer(2, 0).key,
+                },
+                SettingsAction::AddSigner {
+                    new_signer: signer(2, 1),
+                },
+                SettingsAction::AddSigner {
+                    new_signer: signer(3, 7),
+                },
+            ],
+        );
+        assert_eq!(diff.signers_added, [signer(3, 7)]);
+        assert!(diff.signers_removed.is_empty());
+        assert_eq!(
+            diff.permissions_changed,
+            [PermissionChange {
+                key: signer(2, 0).key,
+                before: Permissions { mask: 7 },
+                after: Permissions { mask: 1 },
+            }]
+        );
+        assert!(diff.duplicate_signers.is_empty());
+        assert_eq!(diff.after.stale_transaction_index, 5);
+    }
+
+    #[test]
+    fn satisfiability_counts_only_voters() {
+        let before = settings(vec![signer(1, 7), signer(2, 5), signer(3, 5)], 1);
+        let diff = SettingsDiff::new(
+            &before,
+            &[SettingsAction::ChangeThreshold { new_threshold: 2 }],
+        );
+        assert_eq!(diff.voters(), 1);
+        assert!(!diff.is_satisfiable());
+        assert!(diff.to_string().contains("cannot be met by 1 voters"));
+
+        let diff = SettingsDiff::new(&before, &[]);
+        assert!(diff.is_satisfiable());
+        assert!(diff.is_empty());
+    }
+}
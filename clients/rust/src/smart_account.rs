@@ -0,0 +1,755 @@
+//! Instruction builders that wrap account ordering and PDA derivation.
+
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::{pubkey, Pubkey};
+use solana_sdk_ids::system_program;
+
+use crate::accounts::{ProgramConfig, SpendingLimit};
+use crate::errors::AstrolabeSmartAccountError;
+use crate::instructions::*;
+use crate::pda;
+use crate::types::{
+    CreateTransactionArgs, SettingsAction, SmartAccountSigner, SmartAccountTransactionMessage,
+    VoteOnProposalArgs,
+};
+
+pub(crate) const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+#[cfg(feature = "client")]
+pub(crate) const TOKEN_2022_PROGRAM_ID: Pubkey =
+    pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub(crate) const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Builds the `create_smart_account` instruction for the next free settings seed.
+///
+/// Returns the instruction together with the [`SmartAccount`] it creates.
+pub fn create_smart_account(
+    program_config: &ProgramConfig,
+    creator: Pubkey,
+    args: CreateSmartAccountInstructionArgs,
+) -> (Instruction, SmartAccount) {
+    let smart_account = SmartAccount::from_seed(program_config.smart_account_index + 1);
+    let instruction = CreateSmartAccount {
+        program_config: pda::program_config().0,
+        settings: smart_account.settings,
+        treasury: program_config.treasury,
+        creator,
+        system_program: system_program::ID,
+        program: crate::ID,
+    }
+    .instruction(args);
+    (instruction, smart_account)
+}
+
+/// Instruction builders bound to a single smart account.
+///
+/// Every method derives the PDAs it touches from the settings address and
+/// the indices it is given, so callers only supply signers and payers.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SmartAccount {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    settings: Pubkey,
+}
+
+impl SmartAccount {
+    pub fn new(settings: Pubkey) -> Self {
+        Self { settings }
+    }
+
+    /// The smart account whose settings PDA is derived from `seed`.
+    pub fn from_seed(seed: u128) -> Self {
+        Self::new(pda::settings(seed).0)
+    }
+
+    pub fn settings(&self) -> Pubkey {
+        self.settings
+    }
+
+    /// Address and bump of the smart account PDA that holds funds.
+    pub fn smart_account_pda(&self, account_index: u8) -> (Pubkey, u8) {
+        pda::smart_account(&self.settings, account_index)
+    }
+
+    pub fn transaction_pda(&self, transaction_index: u64) -> (Pubkey, u8) {
+        pda::transaction(&self.settings, transaction_index)
+    }
+
+    pub fn proposal_pda(&self, transaction_index: u64) -> (Pubkey, u8) {
+        pda::proposal(&self.settings, transaction_index)
+    }
+
+    pub fn batch_transaction_pda(&self, batch_index: u64, transaction_index: u32) -> (Pubkey, u8) {
+        pda::batch_transaction(&self.settings, batch_index, transaction_index)
+    }
+
+    pub fn spending_limit_pda(&self, seed: &Pubkey) -> (Pubkey, u8) {
+        pda::spending_limit(&self.settings, seed)
+    }
+
+    pub fn transaction_buffer_pda(&self, creator: &Pubkey, buffer_index: u8) -> (Pubkey, u8) {
+        pda::transaction_buffer(&self.settings, creator, buffer_index)
+    }
+
+    /// Fills in `CreateTransactionArgs`, including the smart account bump
+    /// and the serialized message.
+    pub fn transaction_args(
+        &self,
+        account_index: u8,
+        ephemeral_signers: u8,
+        message: &SmartAccountTransactionMessage,
+        memo: Option<String>,
+    ) -> CreateTransactionArgs {
+        CreateTransactionArgs {
+            account_index,
+            account_bump: self.smart_account_pda(account_index).1,
+            ephemeral_signers,
+            transaction_message: borsh::to_vec(message).unwrap(),
+            memo,
+        }
+    }
+
+    pub fn create_transaction(
+        &self,
+        transaction_index: u64,
+        creator: Pubkey,
+        rent_payer: Pubkey,
+        args: CreateTransactionArgs,
+    ) -> Instruction {
+        CreateTransaction {
+            settings: self.settings,
+            transaction: self.transaction_pda(transaction_index).0,
+            creator,
+            rent_payer,
+            system_program: system_program::ID,
+        }
+        .instruction(CreateTransactionInstructionArgs { args })
+    }
+
+    pub fn create_transaction_buffer(
+        &self,
+        creator: Pubkey,
+        rent_payer: Pubkey,
+        args: CreateTransactionBufferInstructionArgs,
+    ) -> Instruction {
+        CreateTransactionBuffer {
+            settings: self.settings,
+            transaction_buffer: self.transaction_buffer_pda(&creator, args.buffer_index).0,
+            creator,
+            rent_payer,
+            system_program: system_program::ID,
+        }
+        .instruction(args)
+    }
+
+    pub fn extend_transaction_buffer(
+        &self,
+        creator: Pubkey,
+        buffer_index: u8,
+        buffer: Vec<u8>,
+    ) -> Instruction {
+        ExtendTransactionBuffer {
+            settings: self.settings,
+            transaction_buffer: self.transaction_buffer_pda(&creator, buffer_index).0,
+            creator,
+        }
+        .instruction(ExtendTransactionBufferInstructionArgs { buffer })
+    }
+
+    /// Creates a transaction from a previously uploaded buffer, closing the
+    /// buffer in the same instruction.
+    pub fn create_transaction_from_buffer(
+        &self,
+        transaction_index: u64,
+        creator: Pubkey,
+        rent_payer: Pubkey,
+        buffer_index: u8,
+        args: CreateTransactionArgs,
+    ) -> Instruction {
+        CreateTransactionFromBuffer {
+            settings: self.settings,
+            transaction: self.transaction_pda(transaction_index).0,
+            transaction_creator: creator,
+            rent_payer,
+            system_program: system_program::ID,
+            transaction_buffer: self.transaction_buffer_pda(&creator, buffer_index).0,
+            creator,
+        }
+        .instruction(CreateTransactionFromBufferInstructionArgs { args })
+    }
+
+    pub fn close_transaction_buffer(&self, creator: Pubkey, buffer_index: u8) -> Instruction {
+        CloseTransactionBuffer {
+            settings: self.settings,
+            transaction_buffer: self.transaction_buffer_pda(&creator, buffer_index).0,
+            creator,
+        }
+        .instruction()
+    }
+
+    pub fn create_proposal(
+        &self,
+        transaction_index: u64,
+        creator: Pubkey,
+        rent_payer: Pubkey,
+        draft: bool,
+    ) -> Instruction {
+        CreateProposal {
+            settings: self.settings,
+            proposal: self.proposal_pda(transaction_index).0,
+            creator,
+            rent_payer,
+            system_program: system_program::ID,
+        }
+        .instruction(CreateProposalInstructionArgs {
+            transaction_index,
+            draft,
+        })
+    }
+
+    pub fn activate_proposal(&self, transaction_index: u64, signer: Pubkey) -> Instruction {
+        ActivateProposal {
+            settings: self.settings,
+            signer,
+            proposal: self.proposal_pda(transaction_index).0,
+        }
+        .instruction()
+    }
+
+    pub fn approve_proposal(
+        &self,
+        transaction_index: u64,
+        signer: Pubkey,
+        memo: Option<String>,
+    ) -> Instruction {
+        ApproveProposal {
+            settings: self.settings,
+            signer,
+            proposal: self.proposal_pda(transaction_index).0,
+            system_program: Some(system_program::ID),
+        }
+        .instruction(ApproveProposalInstructionArgs {
+            args: VoteOnProposalArgs { memo },
+        })
+    }
+
+    pub fn reject_proposal(
+        &self,
+        transaction_index: u64,
+        signer: Pubkey,
+        memo: Option<String>,
+    ) -> Instruction {
+        RejectProposal {
+            settings: self.settings,
+            signer,
+            proposal: self.proposal_pda(transaction_index).0,
+            system_program: Some(system_program::ID),
+        }
+        .instruction(RejectProposalInstructionArgs {
+            args: VoteOnProposalArgs { memo },
+        })
+    }
+
+    pub fn cancel_proposal(
+        &self,
+        transaction_index: u64,
+        signer: Pubkey,
+        memo: Option<String>,
+    ) -> Instruction {
+        CancelProposal {
+            settings: self.settings,
+            signer,
+            proposal: self.proposal_pda(transaction_index).0,
+            system_program: Some(system_program::ID),
+        }
+        .instruction(CancelProposalInstructionArgs {
+            args: VoteOnProposalArgs { memo },
+        })
+    }
+
+    /// `message_accounts` are the accounts referenced by the stored message,
+    /// in the order the program expects them; see
+    /// [`SmartAccountTransactionMessage::execution_accounts`].
+    pub fn execute_transaction(
+        &self,
+        transaction_index: u64,
+        signer: Pubkey,
+        message_accounts: &[AccountMeta],
+    ) -> Instruction {
+        ExecuteTransaction {
+            settings: self.settings,
+            proposal: self.proposal_pda(transaction_index).0,
+            transaction: self.transaction_pda(transaction_index).0,
+            signer,
+        }
+        .instruction_with_remaining_accounts(message_accounts)
+    }
+
+    /// Executes `instructions` immediately, with `signers` meeting the
+    /// threshold in place of a proposal. Fails with `TooManySigners` for more
+    /// than 255 signers, which the instruction cannot count.
+    pub fn execute_transaction_sync(
+        &self,
+        account_index: u8,
+        signers: &[Pubkey],
+        instructions: Vec<u8>,
+        message_accounts: &[AccountMeta],
+    ) -> Result<Instruction, AstrolabeSmartAccountError> {
+        let num_signers = sync_signer_count(signers)?;
+        let remaining_accounts: Vec<AccountMeta> = signers
+            .iter()
+            .map(|signer| AccountMeta::new_readonly(*signer, true))
+            .chain(message_accounts.iter().cloned())
+            .collect();
+        Ok(ExecuteTransactionSync {
+            settings: self.settings,
+            program: crate::ID,
+        }
+        .instruction_with_remaining_accounts(
+            ExecuteTransactionSyncInstructionArgs {
+                account_index,
+                num_signers,
+                instructions,
+            },
+            &remaining_accounts,
+        ))
+    }
+
+    pub fn close_transaction(
+        &self,
+        transaction_index: u64,
+        proposal_rent_collector: Pubkey,
+        transaction_rent_collector: Pubkey,
+    ) -> Instruction {
+        CloseTransaction {
+            settings: self.settings,
+            proposal: self.proposal_pda(transaction_index).0,
+            transaction: self.transaction_pda(transaction_index).0,
+            proposal_rent_collector,
+            transaction_rent_collector,
+            system_program: system_program::ID,
+        }
+        .instruction()
+    }
+
+    pub fn create_settings_transaction(
+        &self,
+        transaction_index: u64,
+        creator: Pubkey,
+        rent_payer: Pubkey,
+        actions: Vec<SettingsAction>,
+        memo: Option<String>,
+    ) -> Instruction {
+        CreateSettingsTransaction {
+            settings: self.settings,
+            transaction: self.transaction_pda(transaction_index).0,
+            creator,
+            rent_payer,
+            system_program: system_program::ID,
+        }
+        .instruction(CreateSettingsTransactionInstructionArgs { actions, memo })
+    }
+
+    /// `actions` must be the actions stored on the settings transaction; the
+    /// spending limit accounts they touch are appended automatically.
+    pub fn execute_settings_transaction(
+        &self,
+        transaction_index: u64,
+        signer: Pubkey,
+        rent_payer: Option<Pubkey>,
+        actions: &[SettingsAction],
+    ) -> Instruction {
+        ExecuteSettingsTransaction {
+            settings: self.settings,
+            signer,
+            proposal: self.proposal_pda(transaction_index).0,
+            transaction: self.transaction_pda(transaction_index).0,
+            rent_payer,
+            system_program: rent_payer.map(|_| system_program::ID),
+        }
+        .instruction_with_remaining_accounts(&self.spending_limit_accounts(actions))
+    }
+
+    /// Applies `actions` immediately, with `signers` meeting the threshold in
+    /// place of a proposal. Fails like [`Self::execute_transaction_sync`].
+    pub fn execute_settings_transaction_sync(
+        &self,
+        signers: &[Pubkey],
+        rent_payer: Option<Pubkey>,
+        actions: Vec<SettingsAction>,
+        memo: Option<String>,
+    ) -> Result<Instruction, AstrolabeSmartAccountError> {
+        let num_signers = sync_signer_count(signers)?;
+        let remaining_accounts: Vec<AccountMeta> = signers
+            .iter()
+            .map(|signer| AccountMeta::new_readonly(*signer, true))
+            .chain(self.spending_limit_accounts(&actions))
+            .collect();
+        Ok(ExecuteSettingsTransactionSync {
+            settings: self.settings,
+            rent_payer,
+            system_program: rent_payer.map(|_| system_program::ID),
+            program: crate::ID,
+        }
+        .instruction_with_remaining_accounts(
+            ExecuteSettingsTransactionSyncInstructionArgs {
+                num_signers,
+                actions,
+                memo,
+            },
+            &remaining_accounts,
+        ))
+    }
+
+    pub fn close_settings_transaction(
+        &self,
+        transaction_index: u64,
+        proposal_rent_collector: Pubkey,
+        transaction_rent_collector: Pubkey,
+    ) -> Instruction {
+        CloseSettingsTransaction {
+            settings: self.settings,
+            proposal: self.proposal_pda(transaction_index).0,
+            transaction: self.transaction_pda(transaction_index).0,
+            proposal_rent_collector,
+            transaction_rent_collector,
+            system_program: system_program::ID,
+        }
+        .instruction()
+    }
+
+    pub fn create_batch(
+        &self,
+        batch_index: u64,
+        creator: Pubkey,
+        rent_payer: Pubkey,
+        account_index: u8,
+        memo: Option<String>,
+    ) -> Instruction {
+        CreateBatch {
+            settings: self.settings,
+            batch: self.transaction_pda(batch_index).0,
+            creator,
+            rent_payer,
+            system_program: system_program::ID,
+        }
+        .instruction(CreateBatchInstructionArgs {
+            account_index,
+            memo,
+        })
+    }
+
+    /// Adds the next transaction to a draft batch. `transaction_index` is the
+    /// 1-based position inside the batch, i.e. the batch size after adding.
+    pub fn add_transaction_to_batch(
+        &self,
+        batch_index: u64,
+        transaction_index: u32,
+        signer: Pubkey,
+        rent_payer: Pubkey,
+        ephemeral_signers: u8,
+        message: &SmartAccountTransactionMessage,
+    ) -> Instruction {
+        AddTransactionToBatch {
+            settings: self.settings,
+            proposal: self.proposal_pda(batch_index).0,
+            batch: self.transaction_pda(batch_index).0,
+            transaction: self.batch_transaction_pda(batch_index, transaction_index).0,
+            signer,
+            rent_payer,
+            system_program: system_program::ID,
+        }
+        .instruction(AddTransactionToBatchInstructionArgs {
+            ephemeral_signers,
+            transaction_message: borsh::to_vec(message).unwrap(),
+        })
+    }
+
+    pub fn execute_batch_transaction(
+        &self,
+        batch_index: u64,
+        transaction_index: u32,
+        signer: Pubkey,
+        message_accounts: &[AccountMeta],
+    ) -> Instruction {
+        ExecuteBatchTransaction {
+            settings: self.settings,
+            signer,
+            proposal: self.proposal_pda(batch_index).0,
+            batch: self.transaction_pda(batch_index).0,
+            transaction: self.batch_transaction_pda(batch_index, transaction_index).0,
+        }
+        .instruction_with_remaining_accounts(message_accounts)
+    }
+
+    /// Closes the batch transaction at `transaction_index`, which must be the
+    /// last one remaining in the batch.
+    pub fn close_batch_transaction(
+        &self,
+        batch_index: u64,
+        transaction_index: u32,
+        transaction_rent_collector: Pubkey,
+    ) -> Instruction {
+        CloseBatchTransaction {
+            settings: self.settings,
+            proposal: self.proposal_pda(batch_index).0,
+            batch: self.transaction_pda(batch_index).0,
+            transaction: self.batch_transaction_pda(batch_index, transaction_index).0,
+            transaction_rent_collector,
+            system_program: system_program::ID,
+        }
+        .instruction()
+    }
+
+    pub fn close_batch(
+        &self,
+        batch_index: u64,
+        proposal_rent_collector: Pubkey,
+        batch_rent_collector: Pubkey,
+    ) -> Instruction {
+        CloseBatch {
+            settings: self.settings,
+            proposal: self.proposal_pda(batch_index).0,
+            batch: self.transaction_pda(batch_index).0,
+            proposal_rent_collector,
+            batch_rent_collector,
+            system_program: system_program::ID,
+        }
+        .instruction()
+    }
+
+    /// Transfers from the smart account under `spending_limit` without a
+    /// proposal. SPL mints default to the legacy token program.
+    pub fn use_spending_limit(
+        &self,
+        spending_limit: &SpendingLimit,
+        signer: Pubkey,
+        destination: Pubkey,
+        token_program: Option<Pubkey>,
+        args: UseSpendingLimitInstructionArgs,
+    ) -> Instruction {
+        let smart_account = self.smart_account_pda(spending_limit.account_index).0;
+        let is_sol = spending_limit.mint == Pubkey::default();
+        let token_program = token_program.unwrap_or(TOKEN_PROGRAM_ID);
+        let token_account = |owner: &Pubkey| {
+            (!is_sol).then(|| associated_token_address(owner, &spending_limit.mint, &token_program))
+        };
+        UseSpendingLimit {
+            settings: self.settings,
+            signer,
+            spending_limit: self.spending_limit_pda(&spending_limit.seed).0,
+            smart_account,
+            destination,
+            system_program: is_sol.then_some(system_program::ID),
+            mint: (!is_sol).then_some(spending_limit.mint),
+            smart_account_token_account: token_account(&smart_account),
+            destination_token_account: token_account(&destination),
+            token_program: (!is_sol).then_some(token_program),
+            program: crate::ID,
+        }
+        .instruction(args)
+    }
+
+    pub fn add_signer_as_authority(
+        &self,
+        settings_authority: Pubkey,
+        rent_payer: Option<Pubkey>,
+        new_signer: SmartAccountSigner,
+        memo: Option<String>,
+    ) -> Instruction {
+        AddSignerAsAuthority {
+            settings: self.settings,
+            settings_authority,
+            rent_payer,
+            system_program: rent_payer.map(|_| system_program::ID),
+            program: crate::ID,
+        }
+        .instruction(AddSignerAsAuthorityInstructionArgs { new_signer, memo })
+    }
+
+    pub fn remove_signer_as_authority(
+        &self,
+        settings_authority: Pubkey,
+        rent_payer: Option<Pubkey>,
+        old_signer: Pubkey,
+        memo: Option<String>,
+    ) -> Instruction {
+        RemoveSignerAsAuthority {
+            settings: self.settings,
+            settings_authority,
+            rent_payer,
+            system_program: rent_payer.map(|_| system_program::ID),
+            program: crate::ID,
+        }
+        .instruction(RemoveSignerAsAuthorityInstructionArgs { old_signer, memo })
+    }
+
+    pub fn change_threshold_as_authority(
+        &self,
+        settings_authority: Pubkey,
+        rent_payer: Option<Pubkey>,
+        new_threshold: u16,
+        memo: Option<String>,
+    ) -> Instruction {
+        ChangeThresholdAsAuthority {
+            settings: self.settings,
+            settings_authority,
+            rent_payer,
+            system_program: rent_payer.map(|_| system_program::ID),
+            program: crate::ID,
+        }
+        .instruction(ChangeThresholdAsAuthorityInstructionArgs {
+            new_threshold,
+            memo,
+        })
+    }
+
+    pub fn set_time_lock_as_authority(
+        &self,
+        settings_authority: Pubkey,
+        rent_payer: Option<Pubkey>,
+        time_lock: u32,
+        memo: Option<String>,
+    ) -> Instruction {
+        SetTimeLockAsAuthority {
+            settings: self.settings,
+            settings_authority,
+            rent_payer,
+            system_program: rent_payer.map(|_| system_program::ID),
+            program: crate::ID,
+        }
+        .instruction(SetTimeLockAsAuthorityInstructionArgs { time_lock, memo })
+    }
+
+    pub fn set_new_settings_authority_as_authority(
+        &self,
+        settings_authority: Pubkey,
+        rent_payer: Option<Pubkey>,
+        new_settings_authority: Pubkey,
+        memo: Option<String>,
+    ) -> Instruction {
+        SetNewSettingsAuthorityAsAuthority {
+            settings: self.settings,
+            settings_authority,
+            rent_payer,
+            system_program: rent_payer.map(|_| system_program::ID),
+            program: crate::ID,
+        }
+        .instruction(SetNewSettingsAuthorityAsAuthorityInstructionArgs {
+            new_settings_authority,
+            memo,
+        })
+    }
+
+    pub fn set_archival_authority_as_authority(
+        &self,
+        settings_authority: Pubkey,
+        rent_payer: Option<Pubkey>,
+        new_archival_authority: Option<Pubkey>,
+        memo: Option<String>,
+    ) -> Instruction {
+        SetArchivalAuthorityAsAuthority {
+            settings: self.settings,
+            settings_authority,
+            rent_payer,
+            system_program: rent_payer.map(|_| system_program::ID),
+            program: crate::ID,
+        }
+        .instruction(SetArchivalAuthorityAsAuthorityInstructionArgs {
+            new_archival_authority,
+            memo,
+        })
+    }
+
+    pub fn add_spending_limit_as_authority(
+        &self,
+        settings_authority: Pubkey,
+        rent_payer: Pubkey,
+        args: AddSpendingLimitAsAuthorityInstructionArgs,
+    ) -> Instruction {
+        AddSpendingLimitAsAuthority {
+            settings: self.settings,
+            settings_authority,
+            spending_limit: self.spending_limit_pda(&args.seed).0,
+            rent_payer,
+            system_program: system_program::ID,
+            program: crate::ID,
+        }
+        .instruction(args)
+    }
+
+    pub fn remove_spending_limit_as_authority(
+        &self,
+        settings_authority: Pubkey,
+        spending_limit: Pubkey,
+        rent_collector: Pubkey,
+        memo: Option<String>,
+    ) -> Instruction {
+        RemoveSpendingLimitAsAuthority {
+            settings: self.settings,
+            settings_authority,
+            spending_limit,
+            rent_collector,
+            program: crate::ID,
+        }
+        .instruction(RemoveSpendingLimitAsAuthorityInstructionArgs { memo })
+    }
+
+    fn spending_limit_accounts(&self, actions: &[SettingsAction]) -> Vec<AccountMeta> {
+        actions
+            .iter()
+            .filter_map(|action| match action {
+                SettingsAction::AddSpendingLimit { seed, .. } => {
+                    Some(self.spending_limit_pda(seed).0)
+                }
+                SettingsAction::RemoveSpendingLimit { spending_limit } => Some(*spending_limit),
+                _ => None,
+            })
+            .map(|spending_limit| AccountMeta::new(spending_limit, false))
+            .collect()
+    }
+}
+
+pub(crate) fn associated_token_address(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+/// `signers.len()` as the sync instructions count it.
+fn sync_signer_count(signers: &[Pubkey]) -> Result<u8, AstrolabeSmartAccountError> {
+    u8::try_from(signers.len()).map_err(|_| AstrolabeSmartAccountError::TooManySigners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_instructions_reject_more_signers_than_they_can_count() {
+        let smart_account = SmartAccount::new(Pubkey::new_unique());
+        let signers: Vec<Pubkey> = (0..256).map(|_| Pubkey::new_unique()).collect();
+        assert_eq!(
+            smart_account.execute_transaction_sync(0, &signers, Vec::new(), &[]),
+            Err(AstrolabeSmartAccountError::TooManySigners)
+        );
+        assert_eq!(
+            smart_account.execute_settings_transaction_sync(&signers, None, Vec::new(), None),
+            Err(AstrolabeSmartAccountError::TooManySigners)
+        );
+
+        let instruction = smart_account
+            .execute_transaction_sync(0, &signers[..255], Vec::new(), &[])
+            .unwrap();
+        assert_eq!(instruction.accounts.len(), 2 + 255);
+    }
+}
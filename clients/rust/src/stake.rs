@@ -0,0 +1,160 @@
+//! Native stake accounts managed by a vault.
+//!
+//! [`VaultStake`] builds the stake program instructions for a vault that is
+//! both staker and withdrawer of its stake accounts. The vault is the only
+//! signer of each instruction, so they can go into a vault transaction as
+//! they are, e.g. with [`SmartAccountTransactionMessage::try_compile`].
+//!
+//! Stake accounts are derived from the vault with a seed, see
+//! [`VaultStake::stake_account`], because a vault cannot sign for a fresh
+//! keypair. Seeds are at most 32 bytes.
+//!
+//! [`SmartAccountTransactionMessage::try_compile`]:
+//! crate::types::SmartAccountTransactionMessage::try_compile
+
+use solana_instruction::Instruction;
+use solana_pubkey::{Pubkey, PubkeyError};
+use solana_rent::Rent;
+use solana_stake_interface::instruction as stake;
+use solana_stake_interface::program::ID as STAKE_PROGRAM_ID;
+use solana_stake_interface::state::{Authorized, Lockup, StakeStateV2};
+
+use crate::pda;
+
+/// Size of a stake account.
+pub const STAKE_ACCOUNT_SIZE: usize = StakeStateV2::size_of();
+
+pub fn stake_account_rent() -> u64 {
+    Rent::default().minimum_balance(STAKE_ACCOUNT_SIZE)
+}
+
+/// See the [module documentation](self).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VaultStake {
+    vault: Pubkey,
+}
+
+impl VaultStake {
+    /// The vault of `settings` at `account_index`.
+    pub fn new(settings: &Pubkey, account_index: u8) -> Self {
+        Self {
+            vault: pda::smart_account(settings, account_index).0,
+        }
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        self.vault
+    }
+
+    /// Address of the stake account created with `seed`.
+    pub fn stake_account(&self, seed: &str) -> Result<Pubkey, PubkeyError> {
+        Pubkey::create_with_seed(&self.vault, seed, &STAKE_PROGRAM_ID)
+    }
+
+    /// Creates the stake account for `seed`, funded with `lamports` from the
+    /// vault. `lamports` includes [`stake_account_rent`].
+    pub fn create(&self, seed: &str, lamports: u64) -> Result<Vec<Instruction>, PubkeyError> {
+        Ok(stake::create_account_with_seed(
+            &self.vault,
+            &self.stake_account(seed)?,
+            &self.vault,
+            seed,
+            &Authorized::auto(&self.vault),
+            &Lockup::default(),
+            lamports,
+        ))
+    }
+
+    /// [`Self::create`], then delegates the new account to `vote_account`.
+    pub fn create_and_delegate(
+        &self,
+        seed: &str,
+        vote_account: &Pubkey,
+        lamports: u64,
+    ) -> Result<Vec<Instruction>, PubkeyError> {
+        let mut instructions = self.create(seed, lamports)?;
+        instructions.push(self.delegate(&self.stake_account(seed)?, vote_account));
+        Ok(instructions)
+    }
+
+    /// Delegates `stake_account` to `vote_account`, or redelegates it once
+    /// it is fully deactivated.
+    pub fn delegate(&self, stake_account: &Pubkey, vote_account: &Pubkey) -> Instruction {
+        stake::delegate_stake(stake_account, &self.vault, vote_account)
+    }
+
+    pub fn deactivate(&self, stake_account: &Pubkey) -> Instruction {
+        stake::deactivate_stake(stake_account, &self.vault)
+    }
+
+    /// Moves `lamports` of `stake_account` into a new stake account created
+    /// with `seed`, which keeps the delegation. The new account has to end
+    /// up rent exempt.
+    pub fn split(
+        &self,
+        stake_account: &Pubkey,
+        lamports: u64,
+        seed: &str,
+    ) -> Result<Vec<Instruction>, PubkeyError> {
+        Ok(stake::split_with_seed(
+            stake_account,
+            &self.vault,
+            lamports,
+            &self.stake_account(seed)?,
+            &self.vault,
+            seed,
+        ))
+    }
+
+    /// Merges `source` into `destination` and closes `source`. Both must be
+    /// in a compatible state, e.g. delegated to the same vote account.
+    pub fn merge(&self, destination: &Pubkey, source: &Pubkey) -> Vec<Instruction> {
+        stake::merge(destination, source, &self.vault)
+    }
+
+    /// Withdraws `lamports` of `stake_account` to `recipient`. Only inactive
+    /// stake and the excess above it can be withdrawn.
+    pub fn withdraw(
+        &self,
+        stake_account: &Pubkey,
+        recipient: &Pubkey,
+        lamports: u64,
+    ) -> Instruction {
+        stake::withdraw(stake_account, &self.vault, recipient, lamports, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SmartAccountTransactionMessage;
+
+    #[test]
+    fn the_vault_is_the_only_signer() {
+        let stake = VaultStake::new(&Pubkey::new_unique(), 1);
+        let vote_account = Pubkey::new_unique();
+        let stake_account = stake.stake_account("validator-1").unwrap();
+
+        let mut instructions = stake
+            .create_and_delegate("validator-1", &vote_account, stake_account_rent() + 1)
+            .unwrap();
+        instructions.push(stake.deactivate(&stake_account));
+        instructions.extend(stake.split(&stake_account, 1, "validator-1-split").unwrap());
+        instructions.extend(stake.merge(&stake_account, &Pubkey::new_unique()));
+        instructions.push(stake.withdraw(&stake_account, &stake.vault(), 1));
+        let message =
+            SmartAccountTransactionMessage::try_compile(&stake.vault(), &instructions, &[])
+                .unwrap();
+        assert_eq!(message.num_signers, 1);
+        assert_eq!(message.account_keys[0], stake.vault());
+    }
+
+    #[test]
+    fn seeds_longer_than_32_bytes_are_rejected() {
+        let stake = VaultStake::new(&Pubkey::new_unique(), 0);
+        assert_eq!(
+            stake.create(&"s".repeat(33), stake_account_rent()),
+            Err(PubkeyError::MaxSeedLengthExceeded)
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Liquid staking from a vault through SPL stake pools.
+//!
+//! [`VaultStakePool`] builds the stake pool instructions that deposit SOL
+//! from a vault for pool tokens and withdraw SOL for them again. The vault
+//! is the only signer, so the instructions go into a vault transaction as
+//! they are, and [`crate::summary`] shows approvers the amounts for pools of
+//! the SPL stake pool program.
+//!
+//! Pools are read with [`StakePool::from_account`], which only accepts
+//! pools owned by an allowed program, e.g. [`DEFAULT_STAKE_POOL_PROGRAMS`].
+//! The check runs where the proposal is built; the smart account program
+//! executes whatever was approved, so approvers should also check the
+//! program the instructions go to. Pools that restrict SOL deposits or
+//! withdrawals to an authority are not supported. Instruction layouts are
+//! those of spl-stake-pool 2.0.
+
+use std::io;
+
+use solana_account::Account;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::{pubkey, Pubkey};
+use solana_sdk_ids::{stake, system_program, sysvar};
+use thiserror::Error;
+
+use crate::pda;
+use crate::rpc::{fetch_multiple, AccountFetcher};
+use crate::smart_account::{associated_token_address, ASSOCIATED_TOKEN_PROGRAM_ID};
+
+pub const SPL_STAKE_POOL_PROGRAM_ID: Pubkey =
+    pubkey!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
+/// The programs [`StakePool::from_account`] accepts unless told otherwise.
+pub const DEFAULT_STAKE_POOL_PROGRAMS: &[Pubkey] = &[SPL_STAKE_POOL_PROGRAM_ID];
+
+/// Indexes into the stake pool program's instruction enum.
+pub(crate) const DEPOSIT_SOL: u8 = 14;
+pub(crate) const WITHDRAW_SOL: u8 = 16;
+
+/// `AccountType::StakePool`.
+const STAKE_POOL_ACCOUNT_TYPE: u8 = 1;
+/// Offsets of the fields after the manager, staker, deposit authority and
+/// bump seed.
+const RESERVE_STAKE_OFFSET: usize = 130;
+const POOL_MINT_OFFSET: usize = 162;
+const MANAGER_FEE_ACCOUNT_OFFSET: usize = 194;
+const TOKEN_PROGRAM_OFFSET: usize = 226;
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum StakePoolError {
+    #[error("stake pool {pool} belongs to {program}, which is not an allowed stake pool program")]
+    ProgramNotAllowed { pool: Pubkey, program: Pubkey },
+    #[error("{0} is not a stake pool")]
+    NotAStakePool(Pubkey),
+}
+
+/// The accounts of a stake pool that SOL deposits and withdrawals use.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StakePool {
+    pub address: Pubkey,
+    pub program_id: Pubkey,
+    pub reserve_stake: Pubkey,
+    pub pool_mint: Pubkey,
+    pub manager_fee_account: Pubkey,
+    pub token_program: Pubkey,
+}
+
+impl StakePool {
+    /// Reads the pool at `address` from its account, which must be owned by
+    /// one of `allowed_programs`.
+    pub fn from_account(
+        address: Pubkey,
+        account: &Account,
+        allowed_programs: &[Pubkey],
+    ) -> Result<Self, StakePoolError> {
+        if !allowed_programs.contains(&account.owner) {
+            return Err(StakePoolError::ProgramNotAllowed {
+                pool: address,
+                program: account.owner,
+            });
+        }
+        let key = |offset: usize| {
+            account
+                .data
+                .get(offset..offset + 32)
+                .map(|bytes| Pubkey::try_from(bytes).unwrap())
+                .ok_or(StakePoolError::NotAStakePool(address))
+        };
+        if account.data.first() != Some(&STAKE_POOL_ACCOUNT_TYPE) {
+            return Err(StakePoolError::NotAStakePool(address));
+        }
+        Ok(Self {
+            address,
+            program_id: account.owner,
+            reserve_stake: key(RESERVE_STAKE_OFFSET)?,
+            pool_mint: key(POOL_MINT_OFFSET)?,
+            manager_fee_account: key(MANAGER_FEE_ACCOUNT_OFFSET)?,
+            token_program: key(TOKEN_PROGRAM_OFFSET)?,
+        })
+    }
+
+    /// The pool's authority over its stake accounts and mint.
+    pub fn withdraw_authority(&self) -> Pubkey {
+        Pubkey::find_program_address(&[self.address.as_ref(), b"withdraw"], &self.program_id).0
+    }
+}
+
+/// Fetches the pool at `address`, see [`StakePool::from_account`].
+pub async fn fetch_stake_pool<R: AccountFetcher>(
+    rpc: &R,
+    address: &Pubkey,
+    allowed_programs: &[Pubkey],
+) -> Result<StakePool, io::Error> {
+    let account = fetch_multiple(rpc, &[*address])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Account not found: {}", address),
+            )
+        })?;
+    StakePool::from_account(*address, &account, allowed_programs)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// See the [module documentation](self).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VaultStakePool {
+    vault: Pubkey,
+    pool: StakePool,
+}
+
+impl VaultStakePool {
+    /// The vault of `settings` at `account_index`, staking with `pool`.
+    pub fn new(settings: &Pubkey, account_index: u8, pool: StakePool) -> Self {
+        Self {
+            vault: pda::smart_account(settings, account_index).0,
+            pool,
+        }
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        self.vault
+    }
+
+    /// The vault's associated token account for the pool's tokens.
+    pub fn pool_token_account(&self) -> Pubkey {
+        associated_token_address(&self.vault, &self.pool.pool_mint, &self.pool.token_program)
+    }
+
+    /// Deposits `lamports` from the vault for pool tokens, creating the
+    /// vault's pool token account if it does not exist.
+    pub fn deposit_sol(&self, lamports: u64) -> Vec<Instruction> {
+        let pool_token_account = self.pool_token_account();
+        vec![
+            Instruction {
+                program_id: ASSOCIATED_TOKEN_PROGRAM_ID,
+                accounts: vec![
+                    AccountMeta::new(self.vault, true),
+                    AccountMeta::new(pool_token_account, false),
+                    AccountMeta::new_readonly(self.vault, false),
+                    AccountMeta::new_readonly(self.pool.pool_mint, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                    AccountMeta::new_readonly(self.pool.token_program, false),
+                ],
+                // `CreateIdempotent`.
+                data: vec![1],
+            },
+            self.instruction(
+                vec![
+                    AccountMeta::new(self.pool.address, false),
+                    AccountMeta::new_readonly(self.pool.withdraw_authority(), false),
+                    AccountMeta::new(self.pool.reserve_stake, false),
+                    AccountMeta::new(self.vault, true),
+                    AccountMeta::new(pool_token_account, false),
+                    AccountMeta::new(self.pool.manager_fee_account, false),
+                    // The vault refers itself, so no referral fee leaves it.
+                    AccountMeta::new(pool_token_account, false),
+                    AccountMeta::new(self.pool.pool_mint, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                    AccountMeta::new_readonly(self.pool.token_program, false),
+                ],
+                DEPOSIT_SOL,
+                lamports,
+            ),
+        ]
+    }
+
+    /// Burns `pool_tokens` of the vault for SOL from the pool's reserve,
+    /// paid to the vault.
+    pub fn withdraw_sol(&self, pool_tokens: u64) -> Instruction {
+        self.instruction(
+            vec![
+                AccountMeta::new(self.pool.address, false),
+                AccountMeta::new_readonly(self.pool.withdraw_authority(), false),
+                AccountMeta::new_readonly(self.vault, true),
+                AccountMeta::new(self.pool_token_account(), false),
+                AccountMeta::new(self.pool.reserve_stake, false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(self.pool.manager_fee_account, false),
+                AccountMeta::new(self.pool.pool_mint, false),
+                AccountMeta::new_readonly(sysvar::clock::ID, false),
+                AccountMeta::new_readonly(sysvar::stake_history::ID, false),
+                AccountMeta::new_readonly(stake::ID, false),
+                AccountMeta::new_readonly(self.pool.token_program, false),
+            ],
+            WITHDRAW_SOL,
+            pool_tokens,
+        )
+    }
+
+    fn instruction(&self, accounts: Vec<AccountMeta>, index: u8, amount: u64) -> Instruction {
+        let mut data = vec![index];
+        data.extend_from_slice(&amount.to_le_bytes());
+        Instruction {
+            program_id: self.pool.program_id,
+            accounts,
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smart_account::TOKEN_PROGRAM_ID;
+    use crate::summary::summarize;
+    use crate::types::SmartAccountTransactionMessage;
+
+    fn pool_account(owner: Pubkey, pool: &StakePool) -> Account {
+        let mut data = vec![0; 300];
+        data[0] = STAKE_POOL_ACCOUNT_TYPE;
+        for (offset, key) in [
+            (RESERVE_STAKE_OFFSET, pool.reserve_stake),
+            (POOL_MINT_OFFSET, pool.pool_mint),
+            (MANAGER_FEE_ACCOUNT_OFFSET, pool.manager_fee_account),
+            (TOKEN_PROGRAM_OFFSET, pool.token_program),
+        ] {
+            data[offset..offset + 32].copy_from_slice(key.as_ref());
+        }
+        Account {
+            lamports: 1,
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn pool() -> StakePool {
+        StakePool {
+            address: Pubkey::new_unique(),
+            program_id: SPL_STAKE_POOL_PROGRAM_ID,
+            reserve_stake: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            manager_fee_account: Pubkey::new_unique(),
+            token_program: TOKEN_PROGRAM_ID,
+        }
+    }
+
+    #[test]
+    fn only_pools_of_allowed_programs_are_read() {
+        let pool = pool();
+        let account = pool_account(SPL_STAKE_POOL_PROGRAM_ID, &pool);
+        assert_eq!(
+            StakePool::from_account(pool.address, &account, DEFAULT_STAKE_POOL_PROGRAMS),
+            Ok(pool)
+        );
+
+        let fork = Pubkey::new_unique();
+        assert_eq!(
+            StakePool::from_account(
+                pool.address,
+                &pool_account(fork, &pool),
+                DEFAULT_STAKE_POOL_PROGRAMS
+            ),
+            Err(StakePoolError::ProgramNotAllowed {
+                pool: pool.address,
+                program: fork,
+            })
+        );
+        assert_eq!(
+            StakePool::from_account(pool.address, &pool_account(fork, &pool), &[fork])
+                .unwrap()
+                .program_id,
+            fork
+        );
+
+        let mut validator_list = account.clone();
+        validator_list.data[0] = 2;
+        assert_eq!(
+            StakePool::from_account(pool.address, &validator_list, DEFAULT_STAKE_POOL_PROGRAMS),
+            Err(StakePoolError::NotAStakePool(pool.address))
+        );
+        validator_list.data.truncate(200);
+        validator_list.data[0] = STAKE_POOL_ACCOUNT_TYPE;
+        assert_eq!(
+            StakePool::from_account(pool.address, &validator_list, DEFAULT_STAKE_POOL_PROGRAMS),
+            Err(StakePoolError::NotAStakePool(pool.address))
+        );
+    }
+
+    #[test]
+    fn approvers_see_the_deposit_and_the_withdrawal() {
+        let pool = pool();
+        let staking = VaultStakePool::new(&Pubkey::new_unique(), 0, pool);
+        let mut instructions = staking.deposit_sol(2_000_000_000);
+        instructions.push(staking.withdraw_sol(500));
+        assert_eq!(instructions[1].data, [14, 0, 148, 53, 119, 0, 0, 0, 0]);
+        assert_eq!(instructions[2].data, [16, 244, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            instructions[1].accounts[4].pubkey,
+            staking.pool_token_account()
+        );
+
+        let message =
+            SmartAccountTransactionMessage::try_compile(&staking.vault(), &instructions, &[])
+                .unwrap();
+        assert_eq!(message.num_signers, 1);
+        assert_eq!(message.account_keys[0], staking.vault());
+
+        let summaries: Vec<String> = summarize(&message, &[])
+            .unwrap()
+            .iter()
+            .skip(1)
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                format!(
+                    "Deposit 2 SOL from {} into stake pool {}",
+                    staking.vault(),
+                    pool.address
+                ),
+                format!(
+                    "Redeem 500 base units of pool tokens from {} in stake pool {} for SOL to {}",
+                    staking.pool_token_account(),
+                    pool.address,
+                    staking.vault()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Snapshots of a smart account's on-chain state as JSON.
+//!
+//! [`export_state`] reads the settings account, every program account that
+//! belongs to it, the transactions of its batches and its vaults into a
+//! [`StateSnapshot`]. The JSON form is canonical: accounts are keyed and
+//! ordered by address and hold their raw data in base64, next to a decoded
+//! view for people reading the file. [`import_state`] loads a snapshot into
+//! a [`MockRpc`], so a support case or a migration rehearsal can be replayed
+//! as a test fixture; only the raw data is read back.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use solana_account::Account;
+use solana_pubkey::Pubkey;
+use thiserror::Error;
+
+use crate::accounts::{BatchTransaction, SpendingLimit, TransactionBuffer};
+use crate::decode::{
+    TransactionAccount, VersionedProposal, VersionedSettings, BATCH_DISCRIMINATOR,
+    BATCH_TRANSACTION_DISCRIMINATOR, PROPOSAL_DISCRIMINATOR, SETTINGS_DISCRIMINATOR,
+    SETTINGS_TRANSACTION_DISCRIMINATOR, SPENDING_LIMIT_DISCRIMINATOR,
+    TRANSACTION_BUFFER_DISCRIMINATOR, TRANSACTION_DISCRIMINATOR,
+};
+use crate::filters;
+use crate::mock::MockRpc;
+use crate::pda;
+use crate::rpc::{fetch_multiple, AccountFetcher};
+use crate::scan::ProgramAccountScanner;
+
+/// Version of the snapshot format written by [`export_state`].
+pub const STATE_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("unsupported snapshot version {0}")]
+    UnsupportedVersion(u32),
+    #[error("invalid data for account {0}: {1}")]
+    Data(Pubkey, String),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountKind {
+    Settings,
+    Proposal,
+    Transaction,
+    SettingsTransaction,
+    Batch,
+    BatchTransaction,
+    SpendingLimit,
+    TransactionBuffer,
+    Vault,
+    Unknown,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AccountSnapshot {
+    pub kind: AccountKind,
+    pub lamports: u64,
+    #[serde(with = "serde_with::As::<serde_with::DisplayFromStr>")]
+    pub owner: Pubkey,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    /// The account data, in base64.
+    pub data: String,
+    /// The data decoded, for reading only; it is ignored on import.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<serde_json::Value>,
+}
+
+impl AccountSnapshot {
+    pub fn new(kind: AccountKind, account: &Account) -> Self {
+        Self {
+            kind,
+            lamports: account.lamports,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: STANDARD.encode(&account.data),
+            decoded: decode(kind, &account.data),
+        }
+    }
+
+    pub fn to_account(&self) -> Result<Account, base64::DecodeError> {
+        Ok(Account {
+            lamports: self.lamports,
+            data: STANDARD.decode(&self.data)?,
+            owner: self.owner,
+            executable: self.executable,
+            rent_epoch: self.rent_epoch,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StateSnapshot {
+    pub version: u32,
+    /// Slot the program accounts were scanned at.
+    pub slot: u64,
+    #[serde(with = "serde_with::As::<serde_with::DisplayFromStr>")]
+    pub settings: Pubkey,
+    #[serde(with = "serde_with::As::<BTreeMap<serde_with::DisplayFromStr, serde_with::Same>>")]
+    pub accounts: BTreeMap<Pubkey, AccountSnapshot>,
+}
+
+impl StateSnapshot {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("snapshots always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, StateError> {
+        let snapshot: Self = serde_json::from_str(json)?;
+        if snapshot.version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+
+    pub fn to_accounts(&self) -> Result<Vec<(Pubkey, Account)>, StateError> {
+        self.accounts
+            .iter()
+            .map(|(address, snapshot)| {
+                let account = snapshot
+                    .to_account()
+                    .map_err(|e| StateError::Data(*address, e.to_string()))?;
+                Ok((*address, account))
+            })
+            .collect()
+    }
+}
+
+/// Snapshots `settings` with everything that belongs to it. Vaults that do
+/// not exist are left out.
+pub async fn export_state<R: AccountFetcher + ProgramAccountScanner>(
+    rpc: &R,
+    settings: &Pubkey,
+) -> Result<StateSnapshot, io::Error> {
+    let settings_account = fetch_multiple(rpc, &[*settings])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Account not found: {}", settings),
+            )
+        })?;
+    let settings_data = VersionedSettings::from_bytes(&settings_account.data)?.into_current();
+    let (slot, program_accounts) = rpc
+        .get_program_accounts(&filters::settings_accounts(settings))
+        .await?;
+
+    let mut accounts = BTreeMap::new();
+    accounts.insert(
+        *settings,
+        AccountSnapshot::new(AccountKind::Settings, &settings_account),
+    );
+    let mut extra: Vec<(Pubkey, AccountKind)> = (0..=settings_data.account_utilization)
+        .map(|index| (pda::smart_account(settings, index).0, AccountKind::Vault))
+        .collect();
+    for (address, account) in &program_accounts {
+        let kind = kind(&account.data);
+        if let (AccountKind::Batch, Ok(TransactionAccount::Batch(batch))) =
+            (kind, TransactionAccount::from_bytes(&account.data))
+        {
+            extra.extend((1..=batch.size).map(|index| {
+                (
+                    pda::batch_transaction(settings, batch.index, index).0,
+                    AccountKind::BatchTransaction,
+                )
+            }));
+        }
+        accounts.insert(*address, AccountSnapshot::new(kind, account));
+    }
+    let addresses: Vec<Pubkey> = extra.iter().map(|(address, _)| *address).collect();
+    for ((address, kind), account) in extra.iter().zip(fetch_multiple(rpc, &addresses).await?) {
+        if let Some(account) = account {
+            accounts.insert(*address, AccountSnapshot::new(*kind, &account));
+        }
+    }
+    Ok(StateSnapshot {
+        version: STATE_VERSION,
+        slot,
+        settings: *settings,
+        accounts,
+    })
+}
+
+/// Loads the accounts of the snapshot in `json` into `rpc`, replacing any
+/// at the same addresses.
+pub fn import_state(rpc: &MockRpc, json: &str) -> Result<StateSnapshot, StateError> {
+    let snapshot = StateSnapshot::from_json(json)?;
+    for (address, account) in snapshot.to_accounts()? {
+        rpc.set_account(address, account);
+    }
+    Ok(snapshot)
+}
+
+fn kind(data: &[u8]) -> AccountKind {
+    let Some(discriminator) = data.get(..8) else {
+        return AccountKind::Unknown;
+    };
+    match <[u8; 8]>::try_from(discriminator).unwrap() {
+        SETTINGS_DISCRIMINATOR => AccountKind::Settings,
+        PROPOSAL_DISCRIMINATOR => AccountKind::Proposal,
+        TRANSACTION_DISCRIMINATOR => AccountKind::Transaction,
+        SETTINGS_TRANSACTION_DISCRIMINATOR => AccountKind::SettingsTransaction,
+        BATCH_DISCRIMINATOR => AccountKind::Batch,
+        BATCH_TRANSACTION_DISCRIMINATOR => AccountKind::BatchTransaction,
+        SPENDING_LIMIT_DISCRIMINATOR => AccountKind::SpendingLimit,
+        TRANSACTION_BUFFER_DISCRIMINATOR => AccountKind::TransactionBuffer,
+        _ => AccountKind::Unknown,
+    }
+}
+
+fn decode(kind: AccountKind, data: &[u8]) -> Option<serde_json::Value> {
+    let value = match kind {
+        AccountKind::Settings => {
+            serde_json::to_value(VersionedSettings::from_bytes(data).ok()?.into_current())
+        }
+        AccountKind::Proposal => {
+            serde_json::to_value(VersionedProposal::from_bytes(data).ok()?.into_current())
+        }
+        AccountKind::Transaction | AccountKind::SettingsTransaction | AccountKind::Batch => {
+            serde_json::to_value(TransactionAccount::from_bytes(data).ok()?)
+        }
+        AccountKind::BatchTransaction => {
+            serde_json::to_value(BatchTransaction::from_bytes(data).ok()?)
+        }
+        AccountKind::SpendingLimit => serde_json::to_value(SpendingLimit::from_bytes(data).ok()?),
+        AccountKind::TransactionBuffer => {
+            serde_json::to_value(TransactionBuffer::from_bytes(data).ok()?)
+        }
+        AccountKind::Vault | AccountKind::Unknown => return None,
+    };
+    value.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::accounts::{Proposal, Settings};
+    use crate::types::ProposalStatus;
+
+    #[test]
+    fn snapshots_replay_into_a_mock() {
+        let rpc = MockRpc::new();
+        rpc.set_clock(7, 0);
+        let settings = Pubkey::new_unique();
+        rpc.set_program_account_with_space(
+            settings,
+            &Settings {
+                discriminator: SETTINGS_DISCRIMINATOR,
+                seed: 0,
+                settings_authority: Pubkey::default(),
+                threshold: 1,
+                time_lock: 0,
+                transaction_index: 1,
+                stale_transaction_index: 0,
+                archival_authority: None,
+                archivable_after: 0,
+                bump: 255,
+                signers: Vec::new(),
+                restricted_signers: Vec::new(),
+                account_utilization: 0,
+                reserved1: 0,
+                reserved2: 0,
+            },
+            Settings::size(0, 0),
+        );
+        let proposal = pda::proposal(&settings, 1).0;
+        rpc.set_program_account_with_space(
+            proposal,
+            &Proposal {
+                discriminator: PROPOSAL_DISCRIMINATOR,
+                settings,
+                transaction_index: 1,
+                rent_collector: Pubkey::default(),
+                status: ProposalStatus::Active { timestamp: 0 },
+                bump: 255,
+                approved: Vec::new(),
+                rejected: Vec::new(),
+                cancelled: Vec::new(),
+            },
+            Proposal::size(1),
+        );
+        let vault = pda::smart_account(&settings, 0).0;
+        rpc.set_account(
+            vault,
+            Account {
+                lamports: 42,
+                data: Vec::new(),
+                owner: Pubkey::default(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let snapshot = block_on(export_state(&rpc, &settings)).unwrap();
+        assert_eq!(snapshot.slot, 7);
+        let kinds: BTreeMap<Pubkey, AccountKind> = snapshot
+            .accounts
+            .iter()
+            .map(|(address, account)| (*address, account.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            BTreeMap::from([
+                (settings, AccountKind::Settings),
+                (proposal, AccountKind::Proposal),
+                (vault, AccountKind::Vault),
+            ])
+        );
+        assert_eq!(
+            snapshot.accounts[&proposal].decoded.as_ref().unwrap()["transaction_index"],
+            1
+        );
+
+        let replay = MockRpc::new();
+        replay.set_clock(7, 0);
+        let imported = import_state(&replay, &snapshot.to_json()).unwrap();
+        assert_eq!(imported, snapshot);
+        assert_eq!(replay.account(&proposal), rpc.account(&proposal));
+        assert_eq!(
+            block_on(export_state(&replay, &settings)).unwrap(),
+            snapshot
+        );
+
+        let newer = snapshot
+            .to_json()
+            .replacen("\"version\": 1", "\"version\": 2", 1);
+        assert!(matches!(
+            StateSnapshot::from_json(&newer),
+            Err(StateError::UnsupportedVersion(2))
+        ));
+    }
+}
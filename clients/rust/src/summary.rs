@@ -0,0 +1,702 @@
+//! Human-readable summaries of vault transaction messages.
+//!
+//! [`summarize`] resolves every account an instruction touches and parses
+//! the common System, SPL Token, Token-2022, Associated Token Account,
+//! Stake, Vote, SPL Stake Pool and Memo instructions, so approval UIs can show what a
+//! transaction does instead of raw bytes.
+
+use std::fmt;
+
+use solana_instruction::AccountMeta;
+use solana_message::AddressLookupTableAccount;
+use solana_pubkey::{pubkey, Pubkey};
+use solana_sdk_ids::{
+    address_lookup_table, bpf_loader_upgradeable, compute_budget, stake, system_program, vote,
+};
+use solana_vote_interface::state::VoteAuthorize;
+
+use crate::message::MessageError;
+use crate::smart_account::{ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use crate::stake_pool::{DEPOSIT_SOL, SPL_STAKE_POOL_PROGRAM_ID, WITHDRAW_SOL};
+use crate::token::AuthorityType;
+use crate::types::SmartAccountTransactionMessage;
+
+const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+const SOL_DECIMALS: u8 = 9;
+
+/// One instruction of a vault transaction with its accounts resolved.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstructionSummary {
+    pub program_id: Pubkey,
+    pub program_name: Option<&'static str>,
+    pub accounts: Vec<AccountMeta>,
+    pub data: Vec<u8>,
+    /// `None` when the program or instruction is not one this module parses.
+    pub parsed: Option<ParsedInstruction>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParsedInstruction {
+    SystemCreateAccount {
+        from: Pubkey,
+        to: Pubkey,
+        lamports: u64,
+        space: u64,
+        owner: Pubkey,
+    },
+    SystemAssign {
+        account: Pubkey,
+        owner: Pubkey,
+    },
+    SystemTransfer {
+        from: Pubkey,
+        to: Pubkey,
+        lamports: u64,
+    },
+    TokenTransfer {
+        source: Pubkey,
+        destination: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+    },
+    TokenTransferChecked {
+        source: Pubkey,
+        mint: Pubkey,
+        destination: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+        decimals: u8,
+    },
+    TokenApprove {
+        source: Pubkey,
+        delegate: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    },
+    TokenApproveChecked {
+        source: Pubkey,
+        mint: Pubkey,
+        delegate: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+        decimals: u8,
+    },
+    TokenRevoke {
+        source: Pubkey,
+        owner: Pubkey,
+    },
+    TokenSetAuthority {
+        account: Pubkey,
+        authority_type: AuthorityType,
+        /// `None` removes the authority.
+        new_authority: Option<Pubkey>,
+    },
+    TokenFreezeAccount {
+        account: Pubkey,
+        mint: Pubkey,
+        authority: Pubkey,
+    },
+    TokenThawAccount {
+        account: Pubkey,
+        mint: Pubkey,
+        authority: Pubkey,
+    },
+    TokenMintTo {
+        mint: Pubkey,
+        account: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+    },
+    TokenMintToChecked {
+        mint: Pubkey,
+        account: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+        decimals: u8,
+    },
+    TokenBurn {
+        account: Pubkey,
+        mint: Pubkey,
+        authority: Pubkey,
+        amount: u64,
+    },
+    TokenCloseAccount {
+        account: Pubkey,
+        destination: Pubkey,
+        owner: Pubkey,
+    },
+    CreateAssociatedTokenAccount {
+        payer: Pubkey,
+        account: Pubkey,
+        owner: Pubkey,
+        mint: Pubkey,
+    },
+    StakeDelegate {
+        stake: Pubkey,
+        vote: Pubkey,
+    },
+    StakeWithdraw {
+        stake: Pubkey,
+        to: Pubkey,
+        lamports: u64,
+    },
+    StakeDeactivate {
+        stake: Pubkey,
+    },
+    StakePoolDepositSol {
+        pool: Pubkey,
+        from: Pubkey,
+        lamports: u64,
+    },
+    StakePoolWithdrawSol {
+        pool: Pubkey,
+        source: Pubkey,
+        to: Pubkey,
+        pool_tokens: u64,
+    },
+    VoteAuthorize {
+        vote: Pubkey,
+        new_authority: Pubkey,
+        authorize: VoteAuthorize,
+    },
+    VoteWithdraw {
+        vote: Pubkey,
+        to: Pubkey,
+        lamports: u64,
+    },
+    VoteUpdateCommission {
+        vote: Pubkey,
+        commission: u8,
+    },
+    Memo(String),
+}
+
+/// Summarizes every instruction in `message`. `address_lookup_table_accounts`
+/// must cover each table the message references.
+pub fn summarize(
+    message: &SmartAccountTransactionMessage,
+    address_lookup_table_accounts: &[AddressLookupTableAccount],
+) -> Result<Vec<InstructionSummary>, MessageError> {
+    let account_metas = message.account_metas(address_lookup_table_accounts)?;
+    let resolve = |index: u8| {
+        account_metas
+            .get(index as usize)
+            .cloned()
+            .ok_or(MessageError::InvalidAccountIndex(index))
+    };
+
+    message
+        .instructions
+        .iter()
+        .map(|instruction| {
+            let program_id = resolve(instruction.program_id_index)?.pubkey;
+            let accounts = instruction
+                .account_indexes
+                .iter()
+                .map(|&index| resolve(index))
+                .collect::<Result<Vec<_>, _>>()?;
+            let keys: Vec<Pubkey> = accounts.iter().map(|meta| meta.pubkey).collect();
+            Ok(InstructionSummary {
+                program_id,
+                program_name: program_name(&program_id),
+                parsed: parse(&program_id, &keys, &instruction.data),
+                accounts,
+                data: instruction.data.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Display name of well-known programs.
+pub fn program_name(program_id: &Pubkey) -> Option<&'static str> {
+    Some(match *program_id {
+        id if id == system_program::ID => "System Program",
+        id if id == TOKEN_PROGRAM_ID => "Token Program",
+        id if id == TOKEN_2022_PROGRAM_ID => "Token-2022 Program",
+        id if id == ASSOCIATED_TOKEN_PROGRAM_ID => "Associated Token Account Program",
+        id if id == stake::ID => "Stake Program",
+        id if id == MEMO_PROGRAM_ID => "Memo Program",
+        id if id == compute_budget::ID => "Compute Budget Program",
+        id if id == address_lookup_table::ID => "Address Lookup Table Program",
+        id if id == bpf_loader_upgradeable::ID => "BPF Upgradeable Loader",
+        id if id == vote::ID => "Vote Program",
+        id if id == SPL_STAKE_POOL_PROGRAM_ID => "Stake Pool Program",
+        id if id == crate::ID => "Astrolabe Smart Account Program",
+        _ => return None,
+    })
+}
+
+fn parse(program_id: &Pubkey, keys: &[Pubkey], data: &[u8]) -> Option<ParsedInstruction> {
+    let key = |i: usize| keys.get(i).copied();
+    match *program_id {
+        id if id == system_program::ID => {
+            let mut data = Reader(data);
+            match data.u32()? {
+                0 => Some(ParsedInstruction::SystemCreateAccount {
+                    from: key(0)?,
+                    to: key(1)?,
+                    lamports: data.u64()?,
+                    space: data.u64()?,
+                    owner: data.pubkey()?,
+                }),
+                1 => Some(ParsedInstruction::SystemAssign {
+                    account: key(0)?,
+                    owner: data.pubkey()?,
+                }),
+                2 => Some(ParsedInstruction::SystemTransfer {
+                    from: key(0)?,
+                    to: key(1)?,
+                    lamports: data.u64()?,
+                }),
+                _ => None,
+            }
+        }
+        id if id == TOKEN_PROGRAM_ID || id == TOKEN_2022_PROGRAM_ID => {
+            let mut data = Reader(data);
+            match data.u8()? {
+                3 => Some(ParsedInstruction::TokenTransfer {
+                    source: key(0)?,
+                    destination: key(1)?,
+                    authority: key(2)?,
+                    amount: data.u64()?,
+                }),
+                4 => Some(ParsedInstruction::TokenApprove {
+                    source: key(0)?,
+                    delegate: key(1)?,
+                    owner: key(2)?,
+                    amount: data.u64()?,
+                }),
+                5 => Some(ParsedInstruction::TokenRevoke {
+                    source: key(0)?,
+                    owner: key(1)?,
+                }),
+                6 => Some(ParsedInstruction::TokenSetAuthority {
+                    account: key(0)?,
+                    authority_type: AuthorityType::from_u8(data.u8()?)?,
+                    new_authority: data.option_pubkey()?,
+                }),
+                7 => Some(ParsedInstruction::TokenMintTo {
+                    mint: key(0)?,
+                    account: key(1)?,
+                    authority: key(2)?,
+                    amount: data.u64()?,
+                }),
+                8 => Some(ParsedInstruction::TokenBurn {
+                    account: key(0)?,
+                    mint: key(1)?,
+                    authority: key(2)?,
+                    amount: data.u64()?,
+                }),
+                9 => Some(ParsedInstruction::TokenCloseAccount {
+                    account: key(0)?,
+                    destination: key(1)?,
+                    owner: key(2)?,
+                }),
+                10 => Some(ParsedInstruction::TokenFreezeAccount {
+                    account: key(0)?,
+                    mint: key(1)?,
+                    authority: key(2)?,
+                }),
+                11 => Some(ParsedInstruction::TokenThawAccount {
+                    account: key(0)?,
+                    mint: key(1)?,
+                    authority: key(2)?,
+                }),
+                12 => Some(ParsedInstruction::TokenTransferChecked {
+                    source: key(0)?,
+                    mint: key(1)?,
+                    destination: key(2)?,
+                    authority: key(3)?,
+                    amount: data.u64()?,
+                    decimals: data.u8()?,
+                }),
+                13 => Some(ParsedInstruction::TokenApproveChecked {
+                    source: key(0)?,
+                    mint: key(1)?,
+                    delegate: key(2)?,
+                    owner: key(3)?,
+                    amount: data.u64()?,
+                    decimals: data.u8()?,
+                }),
+                14 => Some(ParsedInstruction::TokenMintToChecked {
+                    mint: key(0)?,
+                    account: key(1)?,
+                    authority: key(2)?,
+                    amount: data.u64()?,
+                    decimals: data.u8()?,
+                }),
+                _ => None,
+            }
+        }
+        // `Create` has empty data or a 0 tag; `CreateIdempotent` is tag 1.
+        id if id == ASSOCIATED_TOKEN_PROGRAM_ID && matches!(data, [] | [0] | [1]) => {
+            Some(ParsedInstruction::CreateAssociatedTokenAccount {
+                payer: key(0)?,
+                account: key(1)?,
+                owner: key(2)?,
+                mint: key(3)?,
+            })
+        }
+        id if id == stake::ID => {
+            let mut data = Reader(data);
+            match data.u32()? {
+                2 => Some(ParsedInstruction::StakeDelegate {
+                    stake: key(0)?,
+                    vote: key(1)?,
+                }),
+                4 => Some(ParsedInstruction::StakeWithdraw {
+                    stake: key(0)?,
+                    to: key(1)?,
+                    lamports: data.u64()?,
+                }),
+                5 => Some(ParsedInstruction::StakeDeactivate { stake: key(0)? }),
+                _ => None,
+            }
+        }
+        id if id == SPL_STAKE_POOL_PROGRAM_ID => {
+            let mut data = Reader(data);
+            match data.u8()? {
+                DEPOSIT_SOL => Some(ParsedInstruction::StakePoolDepositSol {
+                    pool: key(0)?,
+                    from: key(3)?,
+                    lamports: data.u64()?,
+                }),
+                WITHDRAW_SOL => Some(ParsedInstruction::StakePoolWithdrawSol {
+                    pool: key(0)?,
+                    source: key(3)?,
+                    to: key(5)?,
+                    pool_tokens: data.u64()?,
+                }),
+                _ => None,
+            }
+        }
+        id if id == vote::ID => {
+            let mut data = Reader(data);
+            match data.u32()? {
+                1 => Some(ParsedInstruction::VoteAuthorize {
+                    vote: key(0)?,
+                    new_authority: data.pubkey()?,
+                    authorize: match data.u32()? {
+                        0 => VoteAuthorize::Voter,
+                        1 => VoteAuthorize::Withdrawer,
+                        _ => return None,
+                    },
+                }),
+                3 => Some(ParsedInstruction::VoteWithdraw {
+                    vote: key(0)?,
+                    to: key(1)?,
+                    lamports: data.u64()?,
+                }),
+                5 => Some(ParsedInstruction::VoteUpdateCommission {
+                    vote: key(0)?,
+                    commission: data.u8()?,
+                }),
+                _ => None,
+            }
+        }
+        id if id == MEMO_PROGRAM_ID => std::str::from_utf8(data)
+            .ok()
+            .map(|memo| ParsedInstruction::Memo(memo.to_string())),
+        _ => None,
+    }
+}
+
+/// Little-endian reader over instruction data.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn pubkey(&mut self) -> Option<Pubkey> {
+        self.take().map(Pubkey::new_from_array)
+    }
+
+    /// A `COption<Pubkey>` as the token program packs it: a one byte tag,
+    /// then the key if there is one.
+    fn option_pubkey(&mut self) -> Option<Option<Pubkey>> {
+        match self.u8()? {
+            0 => Some(None),
+            1 => self.pubkey().map(Some),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for InstructionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.parsed, self.program_name) {
+            (Some(parsed), _) => write!(f, "{}", parsed),
+            (None, Some(name)) => write!(f, "{} instruction ({} bytes)", name, self.data.len()),
+            (None, None) => write!(
+                f,
+                "Instruction to {} ({} accounts, {} bytes)",
+                self.program_id,
+                self.accounts.len(),
+                self.data.len()
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ParsedInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SystemCreateAccount {
+                from,
+                to,
+                lamports,
+                space,
+                owner,
+            } => write!(
+                f,
+                "Create account {} owned by {} with {} bytes, funded with {} SOL from {}",
+                to,
+                owner,
+                space,
+                format_amount(*lamports, SOL_DECIMALS),
+                from
+            ),
+            Self::SystemAssign { account, owner } => {
+                write!(f, "Assign {} to program {}", account, owner)
+            }
+            Self::SystemTransfer { from, to, lamports } => write!(
+                f,
+                "Send {} SOL from {} to {}",
+                format_amount(*lamports, SOL_DECIMALS),
+                from,
+                to
+            ),
+            Self::TokenTransfer {
+                source,
+                destination,
+                amount,
+                ..
+            } => write!(
+                f,
+                "Send {} base units of tokens from {} to {}",
+                amount, source, destination
+            ),
+            Self::TokenTransferChecked {
+                source,
+                mint,
+                destination,
+                amount,
+                decimals,
+                ..
+            } => write!(
+                f,
+                "Send {} of mint {} from {} to {}",
+                format_amount(*amount, *decimals),
+                mint,
+                source,
+                destination
+            ),
+            Self::TokenApprove {
+                source,
+                delegate,
+                amount,
+                ..
+            } => write!(
+                f,
+                "Approve {} to spend {} base units from {}",
+                delegate, amount, source
+            ),
+            Self::TokenApproveChecked {
+                source,
+                mint,
+                delegate,
+                amount,
+                decimals,
+                ..
+            } => write!(
+                f,
+                "Approve {} to spend {} of mint {} from {}",
+                delegate,
+                format_amount(*amount, *decimals),
+                mint,
+                source
+            ),
+            Self::TokenRevoke { source, .. } => write!(f, "Revoke the delegate of {}", source),
+            Self::TokenSetAuthority {
+                account,
+                authority_type,
+                new_authority,
+            } => match new_authority {
+                Some(new_authority) => write!(
+                    f,
+                    "Set the {} authority of {} to {}",
+                    authority_type, account, new_authority
+                ),
+                None => write!(f, "Remove the {} authority of {}", authority_type, account),
+            },
+            Self::TokenFreezeAccount { account, mint, .. } => {
+                write!(f, "Freeze token account {} of mint {}", account, mint)
+            }
+            Self::TokenThawAccount { account, mint, .. } => {
+                write!(f, "Thaw token account {} of mint {}", account, mint)
+            }
+            Self::TokenMintTo {
+                mint,
+                account,
+                amount,
+                ..
+            } => write!(f, "Mint {} base units of {} to {}", amount, mint, account),
+            Self::TokenMintToChecked {
+                mint,
+                account,
+                amount,
+                decimals,
+                ..
+            } => write!(
+                f,
+                "Mint {} of {} to {}",
+                format_amount(*amount, *decimals),
+                mint,
+                account
+            ),
+            Self::TokenBurn {
+                account,
+                mint,
+                amount,
+                ..
+            } => write!(f, "Burn {} base units of {} from {}", amount, mint, account),
+            Self::TokenCloseAccount {
+                account,
+                destination,
+                ..
+            } => write!(
+                f,
+                "Close token account {} and send its rent to {}",
+                account, destination
+            ),
+            Self::CreateAssociatedTokenAccount {
+                account,
+                owner,
+                mint,
+                ..
+            } => write!(
+                f,
+                "Create token account {} for {} holding mint {}",
+                account, owner, mint
+            ),
+            Self::StakeDelegate { stake, vote } => {
+                write!(f, "Delegate stake account {} to validator {}", stake, vote)
+            }
+            Self::StakeWithdraw {
+                stake,
+                to,
+                lamports,
+            } => write!(
+                f,
+                "Withdraw {} SOL from stake account {} to {}",
+                format_amount(*lamports, SOL_DECIMALS),
+                stake,
+                to
+            ),
+            Self::StakeDeactivate { stake } => write!(f, "Deactivate stake account {}", stake),
+            Self::StakePoolDepositSol {
+                pool,
+                from,
+                lamports,
+            } => write!(
+                f,
+                "Deposit {} SOL from {} into stake pool {}",
+                format_amount(*lamports, SOL_DECIMALS),
+                from,
+                pool
+            ),
+            Self::StakePoolWithdrawSol {
+                pool,
+                source,
+                to,
+                pool_tokens,
+            } => write!(
+                f,
+                "Redeem {} base units of pool tokens from {} in stake pool {} for SOL to {}",
+                pool_tokens, source, pool, to
+            ),
+            Self::VoteAuthorize {
+                vote,
+                new_authority,
+                authorize,
+            } => write!(
+                f,
+                "Set the {} of vote account {} to {}",
+                match authorize {
+                    VoteAuthorize::Voter => "voter",
+                    VoteAuthorize::Withdrawer => "withdrawer",
+                },
+                vote,
+                new_authority
+            ),
+            Self::VoteWithdraw { vote, to, lamports } => write!(
+                f,
+                "Withdraw {} SOL from vote account {} to {}",
+                format_amount(*lamports, SOL_DECIMALS),
+                vote,
+                to
+            ),
+            Self::VoteUpdateCommission { vote, commission } => write!(
+                f,
+                "Set the commission of vote account {} to {}%",
+                vote, commission
+            ),
+            Self::Memo(memo) => write!(f, "Memo: {}", memo),
+        }
+    }
+}
+
+/// Formats `amount` base units with `decimals` places, trimming trailing
+/// zeros. `decimals` comes from instruction data; values too large for the
+/// unit to be represented fall back to the raw base units.
+fn format_amount(amount: u64, decimals: u8) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+    let Some(unit) = 10u128.checked_pow(decimals.into()) else {
+        return format!("{} base units", amount);
+    };
+    let whole = amount as u128 / unit;
+    let fraction = amount as u128 % unit;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_amount_handles_every_decimals_value() {
+        assert_eq!(format_amount(1_500, 0), "1500");
+        assert_eq!(format_amount(1_500_000_000, 9), "1.5");
+        assert_eq!(format_amount(1, 9), "0.000000001");
+        assert_eq!(format_amount(u64::MAX, 38), format!("0.{:0>38}", u64::MAX));
+        assert_eq!(
+            format_amount(7, 38),
+            "0.00000000000000000000000000000000000007"
+        );
+        assert_eq!(format_amount(7, 39), "7 base units");
+        assert_eq!(format_amount(7, 255), "7 base units");
+    }
+}
@@ -0,0 +1,267 @@
+//! Delegates and authorities of token accounts owned by a vault.
+//!
+//! [`VaultTokenAuthority`] builds the SPL Token instructions that approve
+//! and revoke delegates, hand over or drop the close authority, and freeze
+//! and thaw accounts of a mint whose freeze authority is the vault. The
+//! vault is the only signer of each instruction, so they go into a vault
+//! transaction as they are.
+//!
+//! Delegates are approved with `ApproveChecked`, which carries the mint and
+//! its decimals, so [`crate::summary`] can show approvers the amount in
+//! whole tokens and the mint it is for. The same instructions work for
+//! Token-2022 accounts, see [`VaultTokenAuthority::with_token_program`].
+
+use std::fmt;
+
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+use crate::pda;
+use crate::smart_account::TOKEN_PROGRAM_ID;
+
+/// Indexes into the token program's instruction enum.
+const REVOKE: u8 = 5;
+const SET_AUTHORITY: u8 = 6;
+const FREEZE_ACCOUNT: u8 = 10;
+const THAW_ACCOUNT: u8 = 11;
+const APPROVE_CHECKED: u8 = 13;
+
+/// The authorities `SetAuthority` can change, as the token program numbers
+/// them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AuthorityType {
+    MintTokens = 0,
+    FreezeAccount = 1,
+    AccountOwner = 2,
+    CloseAccount = 3,
+}
+
+impl AuthorityType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::MintTokens),
+            1 => Some(Self::FreezeAccount),
+            2 => Some(Self::AccountOwner),
+            3 => Some(Self::CloseAccount),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AuthorityType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::MintTokens => "mint",
+            Self::FreezeAccount => "freeze",
+            Self::AccountOwner => "owner",
+            Self::CloseAccount => "close",
+        })
+    }
+}
+
+/// See the [module documentation](self).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VaultTokenAuthority {
+    vault: Pubkey,
+    token_program: Pubkey,
+}
+
+impl VaultTokenAuthority {
+    /// The vault of `settings` at `account_index`, under the token program.
+    pub fn new(settings: &Pubkey, account_index: u8) -> Self {
+        Self {
+            vault: pda::smart_account(settings, account_index).0,
+            token_program: TOKEN_PROGRAM_ID,
+        }
+    }
+
+    /// For accounts and mints of another token program, e.g. Token-2022.
+    pub fn with_token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        self.vault
+    }
+
+    /// Lets `delegate` transfer or burn up to `amount` base units of `mint`
+    /// from `token_account`. `decimals` must be the mint's, or the token
+    /// program fails the instruction.
+    pub fn approve(
+        &self,
+        token_account: &Pubkey,
+        mint: &Pubkey,
+        delegate: &Pubkey,
+        amount: u64,
+        decimals: u8,
+    ) -> Instruction {
+        let mut data = vec![APPROVE_CHECKED];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.push(decimals);
+        self.instruction(
+            vec![
+                AccountMeta::new(*token_account, false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new_readonly(*delegate, false),
+                AccountMeta::new_readonly(self.vault, true),
+            ],
+            data,
+        )
+    }
+
+    /// Removes the delegate of `token_account`, whatever it has left.
+    pub fn revoke(&self, token_account: &Pubkey) -> Instruction {
+        self.instruction(
+            vec![
+                AccountMeta::new(*token_account, false),
+                AccountMeta::new_readonly(self.vault, true),
+            ],
+            vec![REVOKE],
+        )
+    }
+
+    /// Lets `new_authority` close `token_account`, or gives the right back
+    /// to the vault as owner with `None`.
+    pub fn set_close_authority(
+        &self,
+        token_account: &Pubkey,
+        new_authority: Option<&Pubkey>,
+    ) -> Instruction {
+        set_authority(
+            &self.token_program,
+            token_account,
+            &self.vault,
+            AuthorityType::CloseAccount,
+            new_authority,
+        )
+    }
+
+    /// Freezes `token_account`, which must hold `mint`. The vault must be
+    /// the mint's freeze authority.
+    pub fn freeze(&self, token_account: &Pubkey, mint: &Pubkey) -> Instruction {
+        self.freeze_or_thaw(FREEZE_ACCOUNT, token_account, mint)
+    }
+
+    pub fn thaw(&self, token_account: &Pubkey, mint: &Pubkey) -> Instruction {
+        self.freeze_or_thaw(THAW_ACCOUNT, token_account, mint)
+    }
+
+    fn freeze_or_thaw(&self, index: u8, token_account: &Pubkey, mint: &Pubkey) -> Instruction {
+        self.instruction(
+            vec![
+                AccountMeta::new(*token_account, false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new_readonly(self.vault, true),
+            ],
+            vec![index],
+        )
+    }
+
+    fn instruction(&self, accounts: Vec<AccountMeta>, data: Vec<u8>) -> Instruction {
+        Instruction {
+            program_id: self.token_program,
+            accounts,
+            data,
+        }
+    }
+}
+
+/// `SetAuthority` on `account`, a mint or token account, signed by its
+/// current authority.
+pub(crate) fn set_authority(
+    token_program: &Pubkey,
+    account: &Pubkey,
+    current_authority: &Pubkey,
+    authority_type: AuthorityType,
+    new_authority: Option<&Pubkey>,
+) -> Instruction {
+    let mut data = vec![SET_AUTHORITY, authority_type as u8];
+    match new_authority {
+        Some(authority) => {
+            data.push(1);
+            data.extend_from_slice(authority.as_ref());
+        }
+        None => data.push(0),
+    }
+    Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new_readonly(*current_authority, true),
+        ],
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::summarize;
+    use crate::types::SmartAccountTransactionMessage;
+
+    #[test]
+    fn instruction_data_follows_the_token_program() {
+        let authority = VaultTokenAuthority::new(&Pubkey::new_from_array([7; 32]), 0);
+        let (account, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(
+            authority.approve(&account, &mint, &mint, 5, 6).data,
+            [13, 5, 0, 0, 0, 0, 0, 0, 0, 6]
+        );
+        assert_eq!(authority.revoke(&account).data, [5]);
+        assert_eq!(
+            authority.set_close_authority(&account, None).data,
+            [6, 3, 0]
+        );
+        let close_authority = Pubkey::new_from_array([1; 32]);
+        let data = authority
+            .set_close_authority(&account, Some(&close_authority))
+            .data;
+        assert_eq!(data[..3], [6, 3, 1]);
+        assert_eq!(data[3..], [1; 32]);
+        assert_eq!(authority.freeze(&account, &mint).data, [10]);
+        assert_eq!(authority.thaw(&account, &mint).data, [11]);
+    }
+
+    #[test]
+    fn approvers_see_the_amount_and_the_mint() {
+        let authority = VaultTokenAuthority::new(&Pubkey::new_unique(), 1);
+        let (account, mint, delegate) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let instructions = [
+            authority.approve(&account, &mint, &delegate, 1_500_000, 6),
+            authority.revoke(&account),
+            authority.set_close_authority(&account, Some(&delegate)),
+            authority.freeze(&account, &mint),
+            authority.thaw(&account, &mint),
+        ];
+        let message =
+            SmartAccountTransactionMessage::try_compile(&authority.vault(), &instructions, &[])
+                .unwrap();
+        assert_eq!(message.num_signers, 1);
+        assert_eq!(message.account_keys[0], authority.vault());
+
+        let summaries: Vec<String> = summarize(&message, &[])
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                format!(
+                    "Approve {} to spend 1.5 of mint {} from {}",
+                    delegate, mint, account
+                ),
+                format!("Revoke the delegate of {}", account),
+                format!("Set the close authority of {} to {}", account, delegate),
+                format!("Freeze token account {} of mint {}", account, mint),
+                format!("Thaw token account {} of mint {}", account, mint),
+            ]
+        );
+    }
+}
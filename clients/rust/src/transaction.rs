@@ -0,0 +1,217 @@
+//! Outer transactions in legacy or v0 format.
+//!
+//! The instruction builders in [`crate::smart_account`] return instructions;
+//! [`TransactionFormat`] decides how they are wrapped for the network. `V0`
+//! loads accounts from the given lookup tables where it can, for relayers
+//! that only accept versioned transactions or for executions that would not
+//! fit otherwise. The client, the sender and the packing and offline helpers
+//! all take a format.
+
+use solana_hash::Hash;
+use solana_instruction::Instruction;
+use solana_message::{v0, AddressLookupTableAccount, Message, VersionedMessage};
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use solana_transaction::versioned::VersionedTransaction;
+use thiserror::Error;
+
+use crate::message::MessageError;
+
+const SIGNATURE_SIZE: usize = 64;
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum BuildError {
+    #[error(transparent)]
+    Message(#[from] MessageError),
+    #[error("missing signer {0}")]
+    MissingSigner(Pubkey),
+    #[error("signing failed: {0}")]
+    Signer(String),
+}
+
+/// How instructions are wrapped into a transaction.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum TransactionFormat {
+    #[default]
+    Legacy,
+    /// A v0 transaction, loading accounts from these tables where possible.
+    /// The tables may be empty.
+    V0(Vec<AddressLookupTableAccount>),
+}
+
+impl TransactionFormat {
+    pub fn address_lookup_table_accounts(&self) -> &[AddressLookupTableAccount] {
+        match self {
+            Self::Legacy => &[],
+            Self::V0(tables) => tables,
+        }
+    }
+
+    /// The same format with `table` available as well. A legacy format
+    /// becomes v0.
+    pub fn with_table(&self, table: AddressLookupTableAccount) -> Self {
+        let mut tables = self.address_lookup_table_accounts().to_vec();
+        tables.push(table);
+        Self::V0(tables)
+    }
+
+    /// Compiles `instructions` into a message paid for by `payer`.
+    pub fn compile(
+        &self,
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        recent_blockhash: Hash,
+    ) -> Result<VersionedMessage, MessageError> {
+        match self {
+            Self::Legacy => Ok(VersionedMessage::Legacy(Message::new_with_blockhash(
+                instructions,
+                Some(payer),
+                &recent_blockhash,
+            ))),
+            Self::V0(tables) => Ok(VersionedMessage::V0(v0::Message::try_compile(
+                payer,
+                instructions,
+                tables,
+                recent_blockhash,
+            )?)),
+        }
+    }
+
+    /// Compiles `instructions` and signs with the `signers` the message
+    /// requires. `signers` may hold keys the message does not need.
+    pub fn sign(
+        &self,
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        signers: &[&dyn Signer],
+        recent_blockhash: Hash,
+    ) -> Result<VersionedTransaction, BuildError> {
+        sign_message(
+            self.compile(payer, instructions, recent_blockhash)?,
+            signers,
+        )
+    }
+}
+
+/// Signs `message` with the `signers` it requires, in the order it expects.
+pub fn sign_message(
+    message: VersionedMessage,
+    signers: &[&dyn Signer],
+) -> Result<VersionedTransaction, BuildError> {
+    let num_required_signatures = message.header().num_required_signatures as usize;
+    let signers = message
+        .static_account_keys()
+        .iter()
+        .take(num_required_signatures)
+        .map(|key| {
+            signers
+                .iter()
+                .find(|signer| signer.pubkey() == *key)
+                .copied()
+                .ok_or(BuildError::MissingSigner(*key))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    VersionedTransaction::try_new(message, &signers).map_err(|e| BuildError::Signer(e.to_string()))
+}
+
+/// Serialized size of a transaction carrying `message` and its signatures.
+pub fn wire_size(message: &VersionedMessage) -> usize {
+    let num_signatures = message.header().num_required_signatures as usize;
+    short_vec_len(num_signatures) + num_signatures * SIGNATURE_SIZE + message.serialize().len()
+}
+
+fn short_vec_len(len: usize) -> usize {
+    match len {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_instruction::AccountMeta;
+    use solana_keypair::Keypair;
+
+    use super::*;
+
+    #[test]
+    fn v0_loads_accounts_from_tables_and_legacy_keeps_them_static() {
+        let payer = Pubkey::new_unique();
+        let loaded = Pubkey::new_unique();
+        let instructions = [Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[1, 2, 3],
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(loaded, false),
+            ],
+        )];
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique(), loaded],
+        };
+        let blockhash = Hash::new_unique();
+
+        let legacy = TransactionFormat::Legacy
+            .compile(&payer, &instructions, blockhash)
+            .unwrap();
+        assert!(matches!(legacy, VersionedMessage::Legacy(_)));
+        assert!(legacy.static_account_keys().contains(&loaded));
+
+        let format = TransactionFormat::Legacy.with_table(table.clone());
+        let VersionedMessage::V0(message) =
+            format.compile(&payer, &instructions, blockhash).unwrap()
+        else {
+            panic!("a format with a table compiles v0 messages");
+        };
+        assert!(!message.account_keys.contains(&loaded));
+        assert_eq!(message.address_table_lookups.len(), 1);
+        assert_eq!(message.address_table_lookups[0].account_key, table.key);
+        assert_eq!(message.address_table_lookups[0].writable_indexes, [1]);
+        assert!(message.address_table_lookups[0].readonly_indexes.is_empty());
+        assert_eq!(message.recent_blockhash, blockhash);
+    }
+
+    #[test]
+    fn sign_orders_signatures_like_the_message() {
+        let payer = Keypair::new();
+        let cosigner = Keypair::new();
+        let unrelated = Keypair::new();
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![AccountMeta::new_readonly(cosigner.pubkey(), true)],
+        );
+        for format in [TransactionFormat::Legacy, TransactionFormat::V0(Vec::new())] {
+            let transaction = format
+                .sign(
+                    &payer.pubkey(),
+                    std::slice::from_ref(&instruction),
+                    &[&unrelated, &cosigner, &payer],
+                    Hash::new_unique(),
+                )
+                .unwrap();
+            let keys = &transaction.message.static_account_keys()[..2];
+            assert_eq!(keys, [payer.pubkey(), cosigner.pubkey()]);
+            let message = transaction.message.serialize();
+            for (key, signature) in keys.iter().zip(&transaction.signatures) {
+                assert!(signature.verify(key.as_ref(), &message));
+            }
+            assert_eq!(
+                wire_size(&transaction.message),
+                bincode::serialize(&transaction).unwrap().len()
+            );
+
+            assert_eq!(
+                format.sign(
+                    &payer.pubkey(),
+                    std::slice::from_ref(&instruction),
+                    &[&payer],
+                    Hash::default(),
+                ),
+                Err(BuildError::MissingSigner(cosigner.pubkey()))
+            );
+        }
+    }
+}
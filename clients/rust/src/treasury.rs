@@ -0,0 +1,315 @@
+//! Balances held by a smart account's vaults.
+//!
+//! [`fetch_treasury`] reads the SOL and token balances of every vault the
+//! settings account reports in use; [`fetch_vault_balances`] does the same
+//! for vault indexes chosen by the caller, e.g. to probe for funds sent to
+//! vaults that were never used. Token balances are read from each vault's
+//! associated token accounts for the given mints, under whichever token
+//! program owns the mint.
+
+use std::io;
+
+use solana_pubkey::Pubkey;
+
+use crate::decode::VersionedSettings;
+use crate::pda;
+use crate::rpc::{fetch_multiple, AccountFetcher};
+use crate::smart_account::{associated_token_address, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+/// `Mint::decimals`, after the mint authority and supply.
+const MINT_DECIMALS_OFFSET: usize = 44;
+/// `Account::amount`, after the mint and owner.
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenBalance {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub mint: Pubkey,
+    /// The associated token account holding the balance.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub token_account: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VaultBalance {
+    pub account_index: u8,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub address: Pubkey,
+    pub lamports: u64,
+    /// Balances of the mints whose associated token account exists.
+    pub tokens: Vec<TokenBalance>,
+}
+
+/// A mint's balance summed over all vaults.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenTotal {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub mint: Pubkey,
+    pub amount: u128,
+    pub decimals: u8,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreasurySnapshot {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "serde_with::As::<serde_with::DisplayFromStr>")
+    )]
+    pub settings: Pubkey,
+    pub vaults: Vec<VaultBalance>,
+    pub total_lamports: u64,
+    /// One entry per mint held by any vault, in the order of `mints`.
+    pub tokens: Vec<TokenTotal>,
+}
+
+/// Balances of vaults `0..=account_utilization` of `settings`.
+pub async fn fetch_treasury<R: AccountFetcher>(
+    rpc: &R,
+    settings: &Pubkey,
+    mints: &[Pubkey],
+) -> Result<TreasurySnapshot, io::Error> {
+    let account = fetch_multiple(rpc, &[*settings])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Account not found: {}", settings),
+            )
+        })?;
+    let account_utilization = VersionedSettings::from_bytes(&account.data)?
+        .into_current()
+        .account_utilization;
+    let account_indexes: Vec<u8> = (0..=account_utilization).collect();
+    fetch_vault_balances(rpc, settings, &account_indexes, mints).await
+}
+
+/// Balances of the vaults at `account_indexes` of `settings`. Mints that do
+/// not exist are skipped.
+pub async fn fetch_vault_balances<R: AccountFetcher>(
+    rpc: &R,
+    settings: &Pubkey,
+    account_indexes: &[u8],
+    mints: &[Pubkey],
+) -> Result<TreasurySnapshot, io::Error> {
+    let mint_accounts = fetch_multiple(rpc, mints).await?;
+    let mints: Vec<(Pubkey, Pubkey, u8)> = mints
+        .iter()
+        .zip(mint_accounts)
+        .filter_map(|(mint, account)| {
+            let account = account?;
+            let is_token_program =
+                account.owner == TOKEN_PROGRAM_ID || account.owner == TOKEN_2022_PROGRAM_ID;
+            let decimals = *account.data.get(MINT_DECIMALS_OFFSET)?;
+            is_token_program.then_some((*mint, account.owner, decimals))
+        })
+        .collect();
+
+    let vaults: Vec<Pubkey> = account_indexes
+        .iter()
+        .map(|&index| pda::smart_account(settings, index).0)
+        .collect();
+    let addresses: Vec<Pubkey> = vaults
+        .iter()
+        .flat_map(|vault| {
+            std::iter::once(*vault).chain(mints.iter().map(|(mint, token_program, _)| {
+                associated_token_address(vault, mint, token_program)
+            }))
+        })
+        .collect();
+    let accounts = fetch_multiple(rpc, &addresses).await?;
+
+    let stride = mints.len() + 1;
+    let vaults: Vec<VaultBalance> = account_indexes
+        .iter()
+        .zip(vaults)
+        .zip(addresses.chunks(stride).zip(accounts.chunks(stride)))
+        .map(|((&account_index, address), (addresses, accounts))| {
+            let tokens = mints
+                .iter()
+                .zip(addresses[1..].iter().zip(&accounts[1..]))
+                .filter_map(|(&(mint, _, decimals), (token_account, account))| {
+                    let amount = account
+                        .as_ref()?
+                        .data
+                        .get(TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8)?;
+                    Some(TokenBalance {
+                        mint,
+                        token_account: *token_account,
+                        amount: u64::from_le_bytes(amount.try_into().unwrap()),
+                        decimals,
+                    })
+                })
+                .collect();
+            VaultBalance {
+                account_index,
+                address,
+                lamports: accounts[0].as_ref().map_or(0, |account| account.lamports),
+                tokens,
+            }
+        })
+        .collect();
+
+    let tokens = mints
+        .iter()
+        .filter_map(|&(mint, _, decimals)| {
+            let balances: Vec<u64> = vaults
+                .iter()
+                .flat_map(|vault| &vault.tokens)
+                .filter(|token| token.mint == mint)
+                .map(|token| token.amount)
+                .collect();
+            (!balances.is_empty()).then(|| TokenTotal {
+                mint,
+                amount: balances.into_iter().map(u128::from).sum(),
+                decimals,
+            })
+        })
+        .collect();
+    Ok(TreasurySnapshot {
+        settings: *settings,
+        total_lamports: vaults.iter().map(|vault| vault.lamports).sum(),
+        vaults,
+        tokens,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use solana_account::Account;
+
+    use super::*;
+    use crate::accounts::Settings;
+    use crate::decode::SETTINGS_DISCRIMINATOR;
+    use crate::mock::MockRpc;
+
+    fn account(owner: Pubkey, lamports: u64, data: Vec<u8>) -> Account {
+        Account {
+            lamports,
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn mint(rpc: &MockRpc, token_program: Pubkey, decimals: u8) -> Pubkey {
+        let mut data = vec![0; 82];
+        data[MINT_DECIMALS_OFFSET] = decimals;
+        let mint = Pubkey::new_unique();
+        rpc.set_account(mint, account(token_program, 1, data));
+        mint
+    }
+
+    fn token_account(
+        rpc: &MockRpc,
+        vault: &Pubkey,
+        mint: &Pubkey,
+        token_program: Pubkey,
+        amount: u64,
+    ) {
+        let mut data = vec![0; 165];
+        data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8]
+            .copy_from_slice(&amount.to_le_bytes());
+        rpc.set_account(
+            associated_token_address(vault, mint, &token_program),
+            account(token_program, 1, data),
+        );
+    }
+
+    #[test]
+    fn balances_are_read_per_vault_and_summed() {
+        let rpc = MockRpc::new();
+        let settings = Pubkey::new_unique();
+        rpc.set_program_account_with_space(
+            settings,
+            &Settings {
+                discriminator: SETTINGS_DISCRIMINATOR,
+                seed: 0,
+                settings_authority: Pubkey::default(),
+                threshold: 1,
+                time_lock: 0,
+                transaction_index: 0,
+                stale_transaction_index: 0,
+                archival_authority: None,
+                archivable_after: 0,
+                bump: 255,
+                signers: Vec::new(),
+                restricted_signers: Vec::new(),
+                account_utilization: 1,
+                reserved1: 0,
+                reserved2: 0,
+            },
+            Settings::size(0, 0),
+        );
+        let vaults = [0, 1].map(|index| pda::smart_account(&settings, index).0);
+        rpc.set_account(vaults[0], account(Pubkey::default(), 5, Vec::new()));
+        let usdc = mint(&rpc, TOKEN_PROGRAM_ID, 6);
+        let points = mint(&rpc, TOKEN_2022_PROGRAM_ID, 0);
+        token_account(&rpc, &vaults[0], &usdc, TOKEN_PROGRAM_ID, 100);
+        token_account(&rpc, &vaults[1], &usdc, TOKEN_PROGRAM_ID, 50);
+        token_account(&rpc, &vaults[1], &points, TOKEN_2022_PROGRAM_ID, 7);
+        let missing = Pubkey::new_unique();
+
+        let snapshot = block_on(fetch_treasury(&rpc, &settings, &[missing, points, usdc])).unwrap();
+        assert_eq!(snapshot.total_lamports, 5);
+        assert_eq!(snapshot.vaults.len(), 2);
+        assert_eq!(snapshot.vaults[0].address, vaults[0]);
+        assert_eq!(
+            snapshot.vaults[1].tokens,
+            [
+                TokenBalance {
+                    mint: points,
+                    token_account: associated_token_address(
+                        &vaults[1],
+                        &points,
+                        &TOKEN_2022_PROGRAM_ID
+                    ),
+                    amount: 7,
+                    decimals: 0,
+                },
+                TokenBalance {
+                    mint: usdc,
+                    token_account: associated_token_address(&vaults[1], &usdc, &TOKEN_PROGRAM_ID),
+                    amount: 50,
+                    decimals: 6,
+                },
+            ]
+        );
+        assert_eq!(
+            snapshot.tokens,
+            [
+                TokenTotal {
+                    mint: points,
+                    amount: 7,
+                    decimals: 0,
+                },
+                TokenTotal {
+                    mint: usdc,
+                    amount: 150,
+                    decimals: 6,
+                },
+            ]
+        );
+    }
+}
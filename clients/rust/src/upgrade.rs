@@ -0,0 +1,202 @@
+//! Upgrading programs whose upgrade authority is a vault.
+//!
+//! [`VaultUpgradeAuthority`] builds the upgradeable loader instructions for
+//! a program whose upgrade authority is a vault. The vault is the only
+//! signer of each instruction, so they go into a vault transaction as they
+//! are.
+//!
+//! The new program is written to a buffer outside the smart account, e.g.
+//! with `solana program write-buffer`, and the buffer's authority is then
+//! set to the vault, since the loader only upgrades from buffers whose
+//! authority is the upgrade authority. From then on nobody but the vault
+//! can change the buffer. Approvers compare [`fetch_buffer_hash`], the
+//! SHA-256 of the program in the buffer, with the hash of the program they
+//! reviewed; it matches `sha256sum` of the `.so` the buffer was written
+//! from. [`upgrade_memo`] records the hash in the proposal's memo. The
+//! program does not read the memo, so it is a record for reviewers, not a
+//! check.
+
+use std::io;
+
+use sha2::{Digest, Sha256};
+use solana_instruction::Instruction;
+use solana_loader_v3_interface::instruction as loader;
+use solana_loader_v3_interface::state::UpgradeableLoaderState;
+use solana_pubkey::Pubkey;
+
+use crate::pda;
+use crate::rpc::{fetch_multiple, AccountFetcher};
+
+/// See the [module documentation](self).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VaultUpgradeAuthority {
+    vault: Pubkey,
+}
+
+impl VaultUpgradeAuthority {
+    /// The vault of `settings` at `account_index`.
+    pub fn new(settings: &Pubkey, account_index: u8) -> Self {
+        Self {
+            vault: pda::smart_account(settings, account_index).0,
+        }
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        self.vault
+    }
+
+    /// Replaces `program` with the program in `buffer`. The buffer is closed
+    /// and its lamports go to `spill`.
+    pub fn upgrade(&self, program: &Pubkey, buffer: &Pubkey, spill: &Pubkey) -> Instruction {
+        loader::upgrade(program, buffer, &self.vault, spill)
+    }
+
+    /// Hands the upgrade authority of `program` to `new_authority`, or makes
+    /// the program immutable with `None`.
+    pub fn set_upgrade_authority(
+        &self,
+        program: &Pubkey,
+        new_authority: Option<&Pubkey>,
+    ) -> Instruction {
+        loader::set_upgrade_authority(program, &self.vault, new_authority)
+    }
+
+    /// Hands a buffer held by the vault to `new_authority`.
+    pub fn set_buffer_authority(&self, buffer: &Pubkey, new_authority: &Pubkey) -> Instruction {
+        loader::set_buffer_authority(buffer, &self.vault, new_authority)
+    }
+
+    /// Closes a buffer held by the vault that will not be used, returning
+    /// its lamports to `recipient`.
+    pub fn close_buffer(&self, buffer: &Pubkey, recipient: &Pubkey) -> Instruction {
+        loader::close(buffer, recipient, &self.vault)
+    }
+}
+
+/// The authority of a buffer and the SHA-256 of the program it holds, from
+/// the buffer's account data.
+pub fn buffer_hash(buffer: &[u8]) -> Result<(Option<Pubkey>, [u8; 32]), io::Error> {
+    let metadata_size = UpgradeableLoaderState::size_of_buffer_metadata();
+    let state = buffer
+        .get(..metadata_size)
+        .and_then(|metadata| bincode::deserialize(metadata).ok());
+    let Some(UpgradeableLoaderState::Buffer { authority_address }) = state else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not an upgradeable loader buffer",
+        ));
+    };
+    Ok((
+        authority_address,
+        Sha256::digest(&buffer[metadata_size..]).into(),
+    ))
+}
+
+/// Fetches `buffer` and returns the SHA-256 of the program it holds. Fails
+/// unless the buffer's authority is `authority`, since anyone else could
+/// still rewrite it.
+pub async fn fetch_buffer_hash<R: AccountFetcher>(
+    rpc: &R,
+    buffer: &Pubkey,
+    authority: &Pubkey,
+) -> Result<[u8; 32], io::Error> {
+    let account = fetch_multiple(rpc, &[*buffer])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Account not found: {}", buffer),
+            )
+        })?;
+    match buffer_hash(&account.data)? {
+        (Some(buffer_authority), hash) if buffer_authority == *authority => Ok(hash),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the authority of buffer {} is not {}", buffer, authority),
+        )),
+    }
+}
+
+/// A proposal memo recording which buffer `program` is upgraded from and
+/// the hash of the program in it.
+pub fn upgrade_memo(program: &Pubkey, buffer: &Pubkey, hash: &[u8; 32]) -> String {
+    let hash: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "upgrade {} from buffer {} (sha256 {})",
+        program, buffer, hash
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::mock::MockRpc;
+    use crate::types::SmartAccountTransactionMessage;
+
+    fn buffer(authority: Option<Pubkey>, program: &[u8]) -> Vec<u8> {
+        let mut data = bincode::serialize(&UpgradeableLoaderState::Buffer {
+            authority_address: authority,
+        })
+        .unwrap();
+        data.resize(UpgradeableLoaderState::size_of_buffer_metadata(), 0);
+        data.extend_from_slice(program);
+        data
+    }
+
+    #[test]
+    fn the_vault_is_the_only_signer() {
+        let authority = VaultUpgradeAuthority::new(&Pubkey::new_unique(), 0);
+        let (program, buffer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let instructions = [
+            authority.upgrade(&program, &buffer, &authority.vault()),
+            authority.set_upgrade_authority(&program, Some(&Pubkey::new_unique())),
+            authority.set_upgrade_authority(&program, None),
+            authority.set_buffer_authority(&buffer, &Pubkey::new_unique()),
+            authority.close_buffer(&buffer, &authority.vault()),
+        ];
+        let message =
+            SmartAccountTransactionMessage::try_compile(&authority.vault(), &instructions, &[])
+                .unwrap();
+        assert_eq!(message.num_signers, 1);
+        assert_eq!(message.account_keys[0], authority.vault());
+    }
+
+    #[test]
+    fn buffer_hash_covers_the_program_and_checks_the_authority() {
+        let rpc = MockRpc::new();
+        let vault = Pubkey::new_unique();
+        let (held, foreign) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(held, loader_account(buffer(Some(vault), b"program")));
+        rpc.set_account(foreign, loader_account(buffer(None, b"program")));
+
+        let hash = block_on(fetch_buffer_hash(&rpc, &held, &vault)).unwrap();
+        assert_eq!(
+            upgrade_memo(&Pubkey::default(), &Pubkey::default(), &hash),
+            "upgrade 11111111111111111111111111111111 from buffer \
+             11111111111111111111111111111111 (sha256 \
+             1310ca2c8932dbd118668bd97442558a3e1f546b5e15c69699dfbd6024f548d8)"
+        );
+        assert!(block_on(fetch_buffer_hash(&rpc, &foreign, &vault)).is_err());
+        assert!(block_on(fetch_buffer_hash(&rpc, &held, &Pubkey::new_unique())).is_err());
+
+        let program = bincode::serialize(&UpgradeableLoaderState::Program {
+            programdata_address: Pubkey::new_unique(),
+        })
+        .unwrap();
+        assert!(buffer_hash(&program).is_err());
+    }
+
+    fn loader_account(data: Vec<u8>) -> solana_account::Account {
+        solana_account::Account {
+            lamports: 1,
+            data,
+            owner: solana_sdk_ids::bpf_loader_upgradeable::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Vote accounts whose authorized withdrawer is a vault.
+//!
+//! [`VaultVoteWithdrawer`] builds the vote program instructions the
+//! withdrawer signs: withdrawing rewards, changing the commission, and
+//! handing over the voter or withdrawer authority. The vault is the only
+//! signer of each instruction, so they go into a vault transaction as they
+//! are, and [`crate::summary`] shows approvers what each one does.
+//!
+//! A vote account has to stay rent exempt unless it is emptied, and the
+//! vote program only allows raising the commission in the first half of an
+//! epoch. Both are checked when the vault transaction executes, so a
+//! proposal that breaks them fails then.
+
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_vote_interface::instruction as vote;
+use solana_vote_interface::state::VoteAuthorize;
+
+use crate::pda;
+
+/// See the [module documentation](self).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VaultVoteWithdrawer {
+    vault: Pubkey,
+}
+
+impl VaultVoteWithdrawer {
+    /// The vault of `settings` at `account_index`.
+    pub fn new(settings: &Pubkey, account_index: u8) -> Self {
+        Self {
+            vault: pda::smart_account(settings, account_index).0,
+        }
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        self.vault
+    }
+
+    /// Withdraws `lamports` of `vote_account` to `recipient`.
+    pub fn withdraw(
+        &self,
+        vote_account: &Pubkey,
+        recipient: &Pubkey,
+        lamports: u64,
+    ) -> Instruction {
+        vote::withdraw(vote_account, &self.vault, lamports, recipient)
+    }
+
+    /// Sets the commission of `vote_account`, in percent.
+    pub fn update_commission(&self, vote_account: &Pubkey, commission: u8) -> Instruction {
+        vote::update_commission(vote_account, &self.vault, commission)
+    }
+
+    /// Makes `new_withdrawer` the authorized withdrawer of `vote_account`,
+    /// taking the account out of the smart account's control.
+    pub fn set_withdrawer(&self, vote_account: &Pubkey, new_withdrawer: &Pubkey) -> Instruction {
+        vote::authorize(
+            vote_account,
+            &self.vault,
+            new_withdrawer,
+            VoteAuthorize::Withdrawer,
+        )
+    }
+
+    /// Makes `new_voter` the authorized voter of `vote_account` from the
+    /// next epoch on.
+    pub fn set_voter(&self, vote_account: &Pubkey, new_voter: &Pubkey) -> Instruction {
+        vote::authorize(vote_account, &self.vault, new_voter, VoteAuthorize::Voter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::summarize;
+    use crate::types::SmartAccountTransactionMessage;
+
+    #[test]
+    fn approvers_see_what_the_withdrawer_does() {
+        let withdrawer = VaultVoteWithdrawer::new(&Pubkey::new_unique(), 0);
+        let (vote_account, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let instructions = [
+            withdrawer.withdraw(&vote_account, &recipient, 1_250_000_000),
+            withdrawer.update_commission(&vote_account, 5),
+            withdrawer.set_voter(&vote_account, &recipient),
+            withdrawer.set_withdrawer(&vote_account, &recipient),
+        ];
+        let message =
+            SmartAccountTransactionMessage::try_compile(&withdrawer.vault(), &instructions, &[])
+                .unwrap();
+        assert_eq!(message.num_signers, 1);
+        assert_eq!(message.account_keys[0], withdrawer.vault());
+
+        let summaries: Vec<String> = summarize(&message, &[])
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                format!(
+                    "Withdraw 1.25 SOL from vote account {} to {}",
+                    vote_account, recipient
+                ),
+                format!("Set the commission of vote account {} to 5%", vote_account),
+                format!(
+                    "Set the voter of vote account {} to {}",
+                    vote_account, recipient
+                ),
+                format!(
+                    "Set the withdrawer of vote account {} to {}",
+                    vote_account, recipient
+                ),
+            ]
+        );
+    }
+}
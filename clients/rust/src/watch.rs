@@ -0,0 +1,535 @@
+//! Change events for one smart account, for alerting bots.
+//!
+//! [`SettingsWatcher`] turns account updates of a settings account and its
+//! proposals into [`WatchEvent`]s: new transactions, proposal status
+//! changes, proposals whose time lock has run out, and changes to the
+//! threshold, time lock or signers. It does no I/O, so updates can come
+//! from any source; with the `fetch` feature, [`subscribe`] feeds it from
+//! websocket account, program and log subscriptions and delivers the
+//! events over a channel.
+//!
+//! Time locks end on the cluster clock, which the watcher is told about
+//! through `now`; [`SettingsWatcher::tick`] reports proposals that became
+//! executable without an account update.
+
+use std::collections::HashMap;
+use std::io;
+
+use solana_pubkey::Pubkey;
+
+use crate::accounts::Settings;
+use crate::decode::{VersionedProposal, VersionedSettings};
+use crate::events::Event;
+use crate::types::ProposalStatus;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WatchEvent {
+    /// The settings' transaction index moved past `transaction_index`.
+    NewTransactionCreated {
+        transaction_index: u64,
+    },
+    ProposalStatusChanged {
+        transaction_index: u64,
+        status: ProposalStatus,
+    },
+    /// The proposal is approved and its time lock has passed.
+    ProposalNowExecutable {
+        transaction_index: u64,
+    },
+    ThresholdChanged {
+        before: u16,
+        after: u16,
+    },
+    TimeLockChanged {
+        before: u32,
+        after: u32,
+    },
+    SignersChanged {
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "serde_with::As::<Vec<serde_with::DisplayFromStr>>")
+        )]
+        added: Vec<Pubkey>,
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "serde_with::As::<Vec<serde_with::DisplayFromStr>>")
+        )]
+        removed: Vec<Pubkey>,
+    },
+    /// Program activity in a transaction that mentions the settings.
+    Program(Event),
+}
+
+/// See the [module documentation](self).
+pub struct SettingsWatcher {
+    settings: Pubkey,
+    current: Option<Settings>,
+    statuses: HashMap<u64, ProposalStatus>,
+    /// Approval timestamps of approved proposals not yet reported
+    /// executable.
+    approved: HashMap<u64, i64>,
+}
+
+impl SettingsWatcher {
+    pub fn new(settings: Pubkey) -> Self {
+        Self {
+            settings,
+            current: None,
+            statuses: HashMap::new(),
+            approved: HashMap::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &Pubkey {
+        &self.settings
+    }
+
+    /// Applies the new data of `address`, which changed at unix time `now`.
+    /// The first settings update only records the baseline. Accounts that
+    /// are neither the settings nor one of its proposals are ignored.
+    ///
+    /// Fails if the settings data does not decode; the watcher keeps the
+    /// last settings it decoded.
+    pub fn apply_account(
+        &mut self,
+        address: &Pubkey,
+        data: &[u8],
+        now: i64,
+    ) -> Result<Vec<WatchEvent>, io::Error> {
+        let mut events = Vec::new();
+        if *address == self.settings {
+            let settings = VersionedSettings::from_bytes(data)?.into_current();
+            if let Some(before) = self.current.replace(settings.clone()) {
+                diff_settings(&before, &settings, &mut events);
+            }
+        } else if let Ok(proposal) = VersionedProposal::from_bytes(data) {
+            let proposal = proposal.into_current();
+            if proposal.settings != self.settings {
+                return Ok(events);
+            }
+            let index = proposal.transaction_index;
+            if self.statuses.get(&index) != Some(&proposal.status) {
+                if let ProposalStatus::Approved { timestamp } = proposal.status {
+                    self.approved.insert(index, timestamp);
+                } else {
+                    self.approved.remove(&index);
+                }
+                self.statuses.insert(index, proposal.status.clone());
+                events.push(WatchEvent::ProposalStatusChanged {
+                    transaction_index: index,
+                    status: proposal.status,
+                });
+            }
+        }
+        events.extend(self.tick(now));
+        Ok(events)
+    }
+
+    /// Reports approved proposals whose time lock has passed at unix time
+    /// `now`. Each proposal is reported once.
+    pub fn tick(&mut self, now: i64) -> Vec<WatchEvent> {
+        let time_lock = self.current.as_ref().map_or(0, |s| s.time_lock) as i64;
+        let mut ready: Vec<u64> = self
+            .approved
+            .iter()
+            .filter(|(_, &approved_at)| approved_at + time_lock <= now)
+            .map(|(&index, _)| index)
+            .collect();
+        ready.sort_unstable();
+        ready
+            .into_iter()
+            .map(|index| {
+                self.approved.remove(&index);
+                WatchEvent::ProposalNowExecutable {
+                    transaction_index: index,
+                }
+            })
+            .collect()
+    }
+}
+
+fn diff_settings(before: &Settings, after: &Settings, events: &mut Vec<WatchEvent>) {
+    events.extend(
+        (before.transaction_index + 1..=after.transaction_index)
+            .map(|transaction_index| WatchEvent::NewTransactionCreated { transaction_index }),
+    );
+    if before.threshold != after.threshold {
+        events.push(WatchEvent::ThresholdChanged {
+            before: before.threshold,
+            after: after.threshold,
+        });
+    }
+    if before.time_lock != after.time_lock {
+        events.push(WatchEvent::TimeLockChanged {
+            before: before.time_lock,
+            after: after.time_lock,
+        });
+    }
+    let keys = |settings: &Settings| -> Vec<Pubkey> {
+        settings.signers.iter().map(|signer| signer.key).collect()
+    };
+    let (before_keys, after_keys) = (keys(before), keys(after));
+    let added: Vec<Pubkey> = after_keys
+        .iter()
+        .filter(|key| !before_keys.contains(key))
+        .copied()
+        .collect();
+    let removed: Vec<Pubkey> = before_keys
+        .iter()
+        .filter(|key| !after_keys.contains(key))
+        .copied()
+        .collect();
+    if !added.is_empty() || !removed.is_empty() {
+        events.push(WatchEvent::SignersChanged { added, removed });
+    }
+}
+
+/// Watches `settings` until `events` is closed or a subscription ends.
+/// Fails if an update of the settings account does not decode.
+///
+/// Subscribes to the settings account, the program's proposals of
+/// `settings` and the logs of transactions that mention it, and sends
+/// every [`WatchEvent`] they produce. Time locks are checked every second
+/// against the local clock.
+#[cfg(feature = "fetch")]
+pub async fn subscribe(
+    pubsub: &solana_client::nonblocking::pubsub_client::PubsubClient,
+    settings: Pubkey,
+    events: futures::channel::mpsc::UnboundedSender<WatchEvent>,
+) -> Result<(), io::Error> {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use futures::stream::{self, StreamExt};
+    use solana_account_decoder_client_types::UiAccountEncoding;
+    use solana_client::rpc_config::{
+        RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionLogsConfig,
+        RpcTransactionLogsFilter,
+    };
+    use solana_commitment_config::CommitmentConfig;
+
+    use crate::events::parse_logs;
+    use crate::filters;
+
+    enum Update {
+        Account(Pubkey, Option<Vec<u8>>),
+        Logs(u64, String, Vec<String>),
+        Tick,
+    }
+
+    let now = || {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64)
+    };
+    let account_config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        ..Default::default()
+    };
+    let (settings_updates, settings_unsubscribe) = pubsub
+        .account_subscribe(&settings, Some(account_config.clone()))
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let (proposal_updates, proposals_unsubscribe) = pubsub
+        .program_subscribe(
+            &crate::ID,
+            Some(RpcProgramAccountsConfig {
+                filters: Some(
+                    filters::proposals(&settings)
+                        .into_iter()
+                        .map(Into::into)
+                        .collect(),
+                ),
+                account_config,
+                ..Default::default()
+            }),
+        )
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let (log_updates, logs_unsubscribe) = pubsub
+        .logs_subscribe(
+            RpcTransactionLogsFilter::Mentions(vec![settings.to_string()]),
+            RpcTransactionLogsConfig {
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?;
+
+    let ticks = stream::unfold(
+        tokio::time::interval(Duration::from_secs(1)),
+        |mut interval| async move {
+            interval.tick().await;
+            Some((Update::Tick, interval))
+        },
+    );
+    let mut updates = stream::select_all([
+        settings_updates
+            .map(move |response| {
+                Update::Account(
+                    settings,
+                    response
+                        .value
+                        .decode()
+                        .map(|account: solana_account::Account| account.data),
+                )
+            })
+            .boxed(),
+        proposal_updates
+            .filter_map(|response| async move {
+                let address = response.value.pubkey.parse().ok()?;
+                let account: solana_account::Account = response.value.account.decode()?;
+                Some(Update::Account(address, Some(account.data)))
+            })
+            .boxed(),
+        log_updates
+            .map(|response| {
+                Update::Logs(
+                    response.context.slot,
+                    response.value.signature,
+                    response.value.logs,
+                )
+            })
+            .boxed(),
+        ticks.boxed(),
+    ]);
+
+    let mut watcher = SettingsWatcher::new(settings);
+    let mut result = Ok(());
+    while let Some(update) = updates.next().await {
+        let batch = match update {
+            Update::Account(address, Some(data)) => {
+                match watcher.apply_account(&address, &data, now()) {
+                    Ok(batch) => batch,
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            }
+            Update::Account(..) => Vec::new(),
+            Update::Logs(slot, signature, logs) => {
+                let Ok(signature) = signature.parse() else {
+                    continue;
+                };
+                parse_logs(&logs)
+                    .into_iter()
+                    .map(|event| {
+                        WatchEvent::Program(Event {
+                            signature,
+                            slot,
+                            event,
+                        })
+                    })
+                    .collect()
+            }
+            Update::Tick => watcher.tick(now()),
+        };
+        if batch
+            .into_iter()
+            .any(|event| events.unbounded_send(event).is_err())
+        {
+            break;
+        }
+    }
+    drop(updates);
+    settings_unsubscribe().await;
+    proposals_unsubscribe().await;
+    logs_unsubscribe().await;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::Proposal;
+    use crate::decode::{PROPOSAL_DISCRIMINATOR, SETTINGS_DISCRIMINATOR};
+    use crate::types::{Permissions, SmartAccountSigner};
+
+    fn settings_account(
+        transaction_index: u64,
+        threshold: u16,
+        time_lock: u32,
+        keys: &[Pubkey],
+    ) -> Settings {
+        Settings {
+            discriminator: SETTINGS_DISCRIMINATOR,
+            seed: 0,
+            settings_authority: Pubkey::default(),
+            threshold,
+            time_lock,
+            transaction_index,
+            stale_transaction_index: 0,
+            archival_authority: None,
+            archivable_after: 0,
+            bump: 255,
+            signers: keys
+                .iter()
+                .map(|key| SmartAccountSigner {
+                    key: *key,
+                    permissions: Permissions {
+                        mask: Permissions::VOTE,
+                    },
+                })
+                .collect(),
+            restricted_signers: Vec::new(),
+            account_utilization: 0,
+            reserved1: 0,
+            reserved2: 0,
+        }
+    }
+
+    fn settings_data(
+        transaction_index: u64,
+        threshold: u16,
+        time_lock: u32,
+        keys: &[Pubkey],
+    ) -> Vec<u8> {
+        let mut data = borsh::to_vec(&settings_account(
+            transaction_index,
+            threshold,
+            time_lock,
+            keys,
+        ))
+        .unwrap();
+        data.resize(Settings::size(keys.len(), 0), 0);
+        data
+    }
+
+    fn proposal_data(settings: Pubkey, transaction_index: u64, status: ProposalStatus) -> Vec<u8> {
+        let proposal = Proposal {
+            discriminator: PROPOSAL_DISCRIMINATOR,
+            settings,
+            transaction_index,
+            rent_collector: Pubkey::default(),
+            status,
+            bump: 255,
+            approved: Vec::new(),
+            rejected: Vec::new(),
+            cancelled: Vec::new(),
+        };
+        let mut data = borsh::to_vec(&proposal).unwrap();
+        data.resize(Proposal::size(2), 0);
+        data
+    }
+
+    #[test]
+    fn settings_changes_are_reported_after_the_baseline() {
+        let settings = Pubkey::new_unique();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut watcher = SettingsWatcher::new(settings);
+
+        assert_eq!(
+            watcher
+                .apply_account(&settings, &settings_data(1, 1, 0, &[alice]), 0)
+                .unwrap(),
+            []
+        );
+        assert_eq!(
+            watcher
+                .apply_account(&settings, &settings_data(3, 2, 60, &[bob]), 0)
+                .unwrap(),
+            [
+                WatchEvent::NewTransactionCreated {
+                    transaction_index: 2
+                },
+                WatchEvent::NewTransactionCreated {
+                    transaction_index: 3
+                },
+                WatchEvent::ThresholdChanged {
+                    before: 1,
+                    after: 2
+                },
+                WatchEvent::TimeLockChanged {
+                    before: 0,
+                    after: 60
+                },
+                WatchEvent::SignersChanged {
+                    added: vec![bob],
+                    removed: vec![alice],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn removing_a_signer_is_reported_from_the_allocated_account() {
+        let settings = Pubkey::new_unique();
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let mut watcher = SettingsWatcher::new(settings);
+        let mut data = settings_data(1, 1, 0, &keys);
+        watcher.apply_account(&settings, &data, 0).unwrap();
+
+        // The account keeps its size and the removed signer's bytes.
+        let shrunk = borsh::to_vec(&settings_account(1, 1, 0, &keys[..2])).unwrap();
+        data[..shrunk.len()].copy_from_slice(&shrunk);
+        assert_eq!(
+            watcher.apply_account(&settings, &data, 0).unwrap(),
+            [WatchEvent::SignersChanged {
+                added: Vec::new(),
+                removed: vec![keys[2]],
+            }]
+        );
+    }
+
+    #[test]
+    fn undecodable_settings_are_an_error() {
+        let settings = Pubkey::new_unique();
+        let mut watcher = SettingsWatcher::new(settings);
+        watcher
+            .apply_account(&settings, &settings_data(1, 1, 0, &[]), 0)
+            .unwrap();
+        assert!(watcher.apply_account(&settings, &[0; 8], 0).is_err());
+        assert_eq!(
+            watcher
+                .apply_account(&settings, &settings_data(2, 1, 0, &[]), 0)
+                .unwrap(),
+            [WatchEvent::NewTransactionCreated {
+                transaction_index: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn approved_proposals_become_executable_once_the_time_lock_passes() {
+        let settings = Pubkey::new_unique();
+        let mut watcher = SettingsWatcher::new(settings);
+        watcher
+            .apply_account(&settings, &settings_data(2, 1, 60, &[]), 0)
+            .unwrap();
+
+        let proposal = Pubkey::new_unique();
+        let approved = ProposalStatus::Approved { timestamp: 100 };
+        let data = proposal_data(settings, 2, approved.clone());
+        assert_eq!(
+            watcher.apply_account(&proposal, &data, 120).unwrap(),
+            [WatchEvent::ProposalStatusChanged {
+                transaction_index: 2,
+                status: approved,
+            }]
+        );
+        // The same status again is not a change.
+        assert_eq!(watcher.apply_account(&proposal, &data, 130).unwrap(), []);
+        assert_eq!(watcher.tick(159), []);
+        assert_eq!(
+            watcher.tick(160),
+            [WatchEvent::ProposalNowExecutable {
+                transaction_index: 2
+            }]
+        );
+        assert_eq!(watcher.tick(200), []);
+
+        // Proposals of other smart accounts are not watched.
+        let other = proposal_data(
+            Pubkey::new_unique(),
+            2,
+            ProposalStatus::Cancelled { timestamp: 0 },
+        );
+        assert_eq!(watcher.apply_account(&proposal, &other, 200).unwrap(), []);
+    }
+}
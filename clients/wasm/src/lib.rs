@@ -0,0 +1,408 @@
+//! WebAssembly bindings for `astrolabe-client`.
+//!
+//! Addresses cross the boundary as base58 strings and raw bytes as
+//! `Uint8Array`. Decoded accounts and messages are plain JS objects with
+//! 64- and 128-bit integers as `BigInt`, matching the serde representation
+//! of the Rust types.
+
+use std::str::FromStr;
+
+use astrolabe_client::accounts::{ProgramConfig, SpendingLimit};
+use astrolabe_client::decode::{TransactionAccount, VersionedProposal, VersionedSettings};
+use astrolabe_client::types::SmartAccountTransactionMessage;
+use astrolabe_client::{pda, SmartAccount as Client};
+use borsh::BorshDeserialize;
+use serde::{Deserialize, Serialize};
+use solana_message::AddressLookupTableAccount;
+use solana_pubkey::Pubkey;
+use wasm_bindgen::prelude::*;
+
+/// A derived address and its bump seed.
+#[wasm_bindgen(getter_with_clone)]
+pub struct Pda {
+    pub address: String,
+    pub bump: u8,
+}
+
+impl From<(Pubkey, u8)> for Pda {
+    fn from((address, bump): (Pubkey, u8)) -> Self {
+        Self {
+            address: address.to_string(),
+            bump,
+        }
+    }
+}
+
+#[wasm_bindgen(js_name = programConfigPda)]
+pub fn program_config_pda() -> Pda {
+    pda::program_config().into()
+}
+
+/// `seed` is the decimal `u128` settings seed.
+#[wasm_bindgen(js_name = settingsPda)]
+pub fn settings_pda(seed: &str) -> Result<Pda, JsError> {
+    Ok(pda::settings(u128::from_str(seed)?).into())
+}
+
+#[wasm_bindgen(js_name = smartAccountPda)]
+pub fn smart_account_pda(settings: &str, account_index: u8) -> Result<Pda, JsError> {
+    Ok(pda::smart_account(&pubkey(settings)?, account_index).into())
+}
+
+#[wasm_bindgen(js_name = transactionPda)]
+pub fn transaction_pda(settings: &str, transaction_index: u64) -> Result<Pda, JsError> {
+    Ok(pda::transaction(&pubkey(settings)?, transaction_index).into())
+}
+
+#[wasm_bindgen(js_name = proposalPda)]
+pub fn proposal_pda(settings: &str, transaction_index: u64) -> Result<Pda, JsError> {
+    Ok(pda::proposal(&pubkey(settings)?, transaction_index).into())
+}
+
+#[wasm_bindgen(js_name = batchTransactionPda)]
+pub fn batch_transaction_pda(
+    settings: &str,
+    batch_index: u64,
+    transaction_index: u32,
+) -> Result<Pda, JsError> {
+    Ok(pda::batch_transaction(&pubkey(settings)?, batch_index, transaction_index).into())
+}
+
+#[wasm_bindgen(js_name = spendingLimitPda)]
+pub fn spending_limit_pda(settings: &str, seed: &str) -> Result<Pda, JsError> {
+    Ok(pda::spending_limit(&pubkey(settings)?, &pubkey(seed)?).into())
+}
+
+#[wasm_bindgen(js_name = transactionBufferPda)]
+pub fn transaction_buffer_pda(
+    settings: &str,
+    creator: &str,
+    buffer_index: u8,
+) -> Result<Pda, JsError> {
+    Ok(pda::transaction_buffer(&pubkey(settings)?, &pubkey(creator)?, buffer_index).into())
+}
+
+#[wasm_bindgen(js_name = ephemeralSignerPda)]
+pub fn ephemeral_signer_pda(transaction: &str, ephemeral_signer_index: u8) -> Result<Pda, JsError> {
+    Ok(pda::ephemeral_signer(&pubkey(transaction)?, ephemeral_signer_index).into())
+}
+
+/// Decodes a settings account in any supported layout.
+#[wasm_bindgen(js_name = decodeSettings)]
+pub fn decode_settings(data: &[u8]) -> Result<JsValue, JsError> {
+    to_js(&VersionedSettings::from_bytes(data)?.into_current())
+}
+
+/// Decodes a proposal account in any supported layout.
+#[wasm_bindgen(js_name = decodeProposal)]
+pub fn decode_proposal(data: &[u8]) -> Result<JsValue, JsError> {
+    to_js(&VersionedProposal::from_bytes(data)?.into_current())
+}
+
+/// Decodes a transaction, settings transaction or batch account into
+/// `{ Transaction: ... }`, `{ SettingsTransaction: ... }` or `{ Batch: ... }`.
+#[wasm_bindgen(js_name = decodeTransactionAccount)]
+pub fn decode_transaction_account(data: &[u8]) -> Result<JsValue, JsError> {
+    to_js(&TransactionAccount::from_bytes(data)?)
+}
+
+#[wasm_bindgen(js_name = decodeProgramConfig)]
+pub fn decode_program_config(data: &[u8]) -> Result<JsValue, JsError> {
+    to_js(&ProgramConfig::from_bytes(data)?)
+}
+
+#[wasm_bindgen(js_name = decodeSpendingLimit)]
+pub fn decode_spending_limit(data: &[u8]) -> Result<JsValue, JsError> {
+    to_js(&SpendingLimit::from_bytes(data)?)
+}
+
+/// Decodes the borsh-encoded message passed to `create_transaction`.
+#[wasm_bindgen(js_name = decodeTransactionMessage)]
+pub fn decode_transaction_message(data: &[u8]) -> Result<JsValue, JsError> {
+    to_js(&SmartAccountTransactionMessage::try_from_slice(data)?)
+}
+
+/// An instruction as `{ programId, accounts: [{ pubkey, isSigner, isWritable }], data }`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Instruction {
+    program_id: String,
+    accounts: Vec<AccountMeta>,
+    #[serde(with = "serde_bytes_as_uint8array")]
+    data: Vec<u8>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountMeta {
+    pubkey: String,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+impl From<solana_instruction::Instruction> for Instruction {
+    fn from(instruction: solana_instruction::Instruction) -> Self {
+        Self {
+            program_id: instruction.program_id.to_string(),
+            accounts: instruction
+                .accounts
+                .into_iter()
+                .map(|meta| AccountMeta {
+                    pubkey: meta.pubkey.to_string(),
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: instruction.data,
+        }
+    }
+}
+
+/// `{ key, addresses }` as returned by `@solana/kit` lookup table fetchers.
+#[derive(Deserialize)]
+struct LookupTable {
+    key: String,
+    addresses: Vec<String>,
+}
+
+/// Instruction builders bound to one smart account.
+#[wasm_bindgen]
+pub struct SmartAccount(Client);
+
+#[wasm_bindgen]
+impl SmartAccount {
+    #[wasm_bindgen(constructor)]
+    pub fn new(settings: &str) -> Result<SmartAccount, JsError> {
+        Ok(Self(Client::new(pubkey(settings)?)))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn settings(&self) -> String {
+        self.0.settings().to_string()
+    }
+
+    /// `message` is a decoded `SmartAccountTransactionMessage` object. Every
+    /// signer after the smart account itself is taken to be an ephemeral
+    /// signer.
+    #[wasm_bindgen(js_name = createTransaction)]
+    pub fn create_transaction(
+        &self,
+        transaction_index: u64,
+        creator: &str,
+        rent_payer: &str,
+        account_index: u8,
+        message: JsValue,
+        memo: Option<String>,
+    ) -> Result<JsValue, JsError> {
+        let message: SmartAccountTransactionMessage = serde_wasm_bindgen::from_value(message)?;
+        let ephemeral_signers = message.num_signers.saturating_sub(1);
+        let args = self
+            .0
+            .transaction_args(account_index, ephemeral_signers, &message, memo);
+        instruction(self.0.create_transaction(
+            transaction_index,
+            pubkey(creator)?,
+            pubkey(rent_payer)?,
+            args,
+        ))
+    }
+
+    #[wasm_bindgen(js_name = createProposal)]
+    pub fn create_proposal(
+        &self,
+        transaction_index: u64,
+        creator: &str,
+        rent_payer: &str,
+        draft: bool,
+    ) -> Result<JsValue, JsError> {
+        instruction(self.0.create_proposal(
+            transaction_index,
+            pubkey(creator)?,
+            pubkey(rent_payer)?,
+            draft,
+        ))
+    }
+
+    #[wasm_bindgen(js_name = activateProposal)]
+    pub fn activate_proposal(
+        &self,
+        transaction_index: u64,
+        signer: &str,
+    ) -> Result<JsValue, JsError> {
+        instruction(self.0.activate_proposal(transaction_index, pubkey(signer)?))
+    }
+
+    #[wasm_bindgen(js_name = approveProposal)]
+    pub fn approve_proposal(
+        &self,
+        transaction_index: u64,
+        signer: &str,
+        memo: Option<String>,
+    ) -> Result<JsValue, JsError> {
+        instruction(
+            self.0
+                .approve_proposal(transaction_index, pubkey(signer)?, memo),
+        )
+    }
+
+    #[wasm_bindgen(js_name = rejectProposal)]
+    pub fn reject_proposal(
+        &self,
+        transaction_index: u64,
+        signer: &str,
+        memo: Option<String>,
+    ) -> Result<JsValue, JsError> {
+        instruction(
+            self.0
+                .reject_proposal(transaction_index, pubkey(signer)?, memo),
+        )
+    }
+
+    #[wasm_bindgen(js_name = cancelProposal)]
+    pub fn cancel_proposal(
+        &self,
+        transaction_index: u64,
+        signer: &str,
+        memo: Option<String>,
+    ) -> Result<JsValue, JsError> {
+        instruction(
+            self.0
+                .cancel_proposal(transaction_index, pubkey(signer)?, memo),
+        )
+    }
+
+    /// `message` is the stored `SmartAccountTransactionMessage` object and
+    /// `addressLookupTables` an array of `{ key, addresses }` covering every
+    /// table it references.
+    #[wasm_bindgen(js_name = executeTransaction)]
+    pub fn execute_transaction(
+        &self,
+        transaction_index: u64,
+        signer: &str,
+        message: JsValue,
+        address_lookup_tables: JsValue,
+    ) -> Result<JsValue, JsError> {
+        let message: SmartAccountTransactionMessage = serde_wasm_bindgen::from_value(message)?;
+        let tables =
+            serde_wasm_bindgen::from_value::<Option<Vec<LookupTable>>>(address_lookup_tables)?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|table| {
+                    Ok(AddressLookupTableAccount {
+                        key: pubkey(&table.key)?,
+                        addresses: table
+                            .addresses
+                            .iter()
+                            .map(|address| pubkey(address))
+                            .collect::<Result<_, _>>()?,
+                    })
+                })
+                .collect::<Result<Vec<_>, JsError>>()?;
+        let accounts = message.execution_accounts(&tables)?;
+        instruction(
+            self.0
+                .execute_transaction(transaction_index, pubkey(signer)?, &accounts),
+        )
+    }
+
+    #[wasm_bindgen(js_name = closeTransaction)]
+    pub fn close_transaction(
+        &self,
+        transaction_index: u64,
+        proposal_rent_collector: &str,
+        transaction_rent_collector: &str,
+    ) -> Result<JsValue, JsError> {
+        instruction(self.0.close_transaction(
+            transaction_index,
+            pubkey(proposal_rent_collector)?,
+            pubkey(transaction_rent_collector)?,
+        ))
+    }
+}
+
+fn pubkey(address: &str) -> Result<Pubkey, JsError> {
+    Pubkey::from_str(address).map_err(|e| JsError::new(&format!("{}: {}", address, e)))
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    Ok(value.serialize(
+        &serde_wasm_bindgen::Serializer::new().serialize_large_number_types_as_bigints(true),
+    )?)
+}
+
+fn instruction(instruction: solana_instruction::Instruction) -> Result<JsValue, JsError> {
+    to_js(&Instruction::from(instruction))
+}
+
+/// Serializes bytes as a `Uint8Array` rather than an array of numbers.
+mod serde_bytes_as_uint8array {
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+}
+
+/// Only what stays on the Rust side runs natively: building a `JsValue` or
+/// `JsError` needs a JS host.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdas_are_derived_from_base58_inputs() {
+        let settings = Pubkey::new_unique();
+        let creator = Pubkey::new_unique();
+        let address = settings.to_string();
+        let cases = [
+            (
+                settings_pda("340282366920938463463374607431768211455").unwrap(),
+                pda::settings(u128::MAX),
+            ),
+            (
+                smart_account_pda(&address, 3).unwrap(),
+                pda::smart_account(&settings, 3),
+            ),
+            (
+                proposal_pda(&address, 9).unwrap(),
+                pda::proposal(&settings, 9),
+            ),
+            (
+                batch_transaction_pda(&address, 9, 2).unwrap(),
+                pda::batch_transaction(&settings, 9, 2),
+            ),
+            (
+                transaction_buffer_pda(&address, &creator.to_string(), 1).unwrap(),
+                pda::transaction_buffer(&settings, &creator, 1),
+            ),
+        ];
+        for (binding, (expected, bump)) in cases {
+            assert_eq!(binding.address, expected.to_string());
+            assert_eq!(binding.bump, bump);
+        }
+        assert_eq!(SmartAccount::new(&address).unwrap().settings(), address);
+    }
+
+    #[test]
+    fn instructions_keep_their_account_order_and_flags() {
+        let smart_account = Client::new(Pubkey::new_unique());
+        let signer = Pubkey::new_unique();
+        let native = smart_account.approve_proposal(1, signer, None);
+        let converted = Instruction::from(native.clone());
+
+        assert_eq!(converted.program_id, native.program_id.to_string());
+        assert_eq!(converted.data, native.data);
+        let flags: Vec<(String, bool, bool)> = converted
+            .accounts
+            .into_iter()
+            .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+            .collect();
+        let expected: Vec<(String, bool, bool)> = native
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey.to_string(), meta.is_signer, meta.is_writable))
+            .collect();
+        assert_eq!(flags, expected);
+        assert!(flags.contains(&(signer.to_string(), true, true)));
+    }
+}
@@ -0,0 +1,483 @@
+//! One pass over a smart account: execute what is ready, close what is done.
+
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use astrolabe_client::accounts::{Proposal, Settings};
+use astrolabe_client::decode::{TransactionAccount, VersionedProposal, VersionedSettings};
+use astrolabe_client::lifecycle::{is_closable, ProposalLifecycle, TransactionKind};
+use astrolabe_client::rpc::{
+    fetch_address_lookup_tables, fetch_multiple, fetch_transaction_page, fetch_unix_timestamp,
+    index_pages, AccountFetcher, TransactionSender,
+};
+use astrolabe_client::types::ProposalStatus;
+use astrolabe_client::SmartAccount;
+use solana_account::Account;
+use solana_instruction::Instruction;
+use solana_keypair::Keypair;
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction::Transaction;
+use tokio::time::{sleep_until, Instant};
+
+use crate::metrics::Metrics;
+
+/// Spaces transactions out so that no more than `max` are sent per minute.
+pub struct RateLimiter {
+    interval: Duration,
+    next: Instant,
+}
+
+impl RateLimiter {
+    pub fn per_minute(max: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(60) / max.max(1),
+            next: Instant::now(),
+        }
+    }
+
+    /// Resolves once the next transaction may be sent.
+    pub async fn wait(&mut self) {
+        sleep_until(self.next).await;
+        self.next = self.next.max(Instant::now()) + self.interval;
+    }
+}
+
+/// The transaction and proposal accounts at one index; either may be closed.
+type AccountPair = (u64, Option<Account>, Option<Account>);
+
+pub struct Keeper<'a, R> {
+    rpc: &'a R,
+    payer: &'a Keypair,
+    metrics: &'a Metrics,
+    limiter: RateLimiter,
+    /// Per smart account, the lowest transaction index that may still be
+    /// executed.
+    execute_from: HashMap<Pubkey, u64>,
+    /// Per smart account, the lowest transaction index that may still have
+    /// open accounts.
+    open_from: HashMap<Pubkey, u64>,
+}
+
+impl<'a, R: AccountFetcher + TransactionSender + Sync> Keeper<'a, R> {
+    pub fn new(rpc: &'a R, payer: &'a Keypair, metrics: &'a Metrics, limiter: RateLimiter) -> Self {
+        Self {
+            rpc,
+            payer,
+            metrics,
+            limiter,
+            execute_from: HashMap::new(),
+            open_from: HashMap::new(),
+        }
+    }
+
+    /// Executes every proposal of `settings` the keeper may execute now,
+    /// then closes every transaction the program lets anyone close.
+    pub async fn crank(&mut self, settings: &Pubkey) -> Result<()> {
+        self.execute_ready(settings)
+            .await
+            .context("executing ready proposals")?;
+        self.close_reclaimable(settings)
+            .await
+            .context("closing reclaimable transactions")
+    }
+
+    async fn execute_ready(&mut self, settings: &Pubkey) -> Result<()> {
+        let keeper = self.payer.pubkey();
+        let now = fetch_unix_timestamp(self.rpc).await?;
+        let smart_account = SmartAccount::new(*settings);
+        let first = self.execute_from.get(settings).copied().unwrap_or(1);
+        let (settings_data, pairs) = self.fetch_from(settings, first).await?;
+
+        let mut execute_from = first;
+        let mut all_finished = true;
+        for (index, transaction, proposal) in pairs {
+            let decoded = decode(transaction.as_ref(), proposal.as_ref());
+            let Some((account, proposal)) = self.skip_undecodable(decoded, settings, index) else {
+                all_finished = false;
+                continue;
+            };
+            let finished = is_finished(
+                account.as_ref().map(TransactionKind::from),
+                proposal.as_ref().map(|proposal| &proposal.status),
+                index <= settings_data.stale_transaction_index,
+            );
+            if all_finished && finished {
+                execute_from = index + 1;
+            } else {
+                all_finished = false;
+            }
+
+            let (Some(account), Some(proposal)) = (&account, &proposal) else {
+                continue;
+            };
+            if !ProposalLifecycle::new(proposal, &settings_data, account.into())
+                .can_execute(&keeper, now)
+            {
+                continue;
+            }
+            let instruction = match account {
+                TransactionAccount::Transaction(transaction) => {
+                    let tables =
+                        fetch_address_lookup_tables(self.rpc, &transaction.message).await?;
+                    let accounts = transaction.message.execution_accounts(&tables)?;
+                    smart_account.execute_transaction(index, keeper, &accounts)
+                }
+                TransactionAccount::SettingsTransaction(transaction) => smart_account
+                    .execute_settings_transaction(
+                        index,
+                        keeper,
+                        Some(keeper),
+                        &transaction.actions,
+                    ),
+                TransactionAccount::Batch(_) => {
+                    println!(
+                        "skip {} #{}: the keeper leaves batches alone",
+                        settings, index
+                    );
+                    continue;
+                }
+            };
+            if self.send(&[instruction], settings, index, "execute").await {
+                Metrics::add(&self.metrics.executed, 1);
+            }
+        }
+        self.execute_from.insert(*settings, execute_from);
+        Ok(())
+    }
+
+    async fn close_reclaimable(&mut self, settings: &Pubkey) -> Result<()> {
+        // Executing a settings transaction may have moved the stale index,
+        // so the settings are fetched again.
+        let smart_account = SmartAccount::new(*settings);
+        let first = self.open_from.get(settings).copied().unwrap_or(1);
+        let (settings_data, pairs) = self.fetch_from(settings, first).await?;
+
+        let mut open_from = first;
+        let mut all_closed = true;
+        for (index, transaction, proposal_account) in pairs {
+            let Some(transaction) = transaction else {
+                if all_closed && proposal_account.is_none() {
+                    open_from = index + 1;
+                }
+                continue;
+            };
+            all_closed = false;
+            let decoded = decode(Some(&transaction), proposal_account.as_ref());
+            let Some((Some(account), proposal)) = self.skip_undecodable(decoded, settings, index)
+            else {
+                continue;
+            };
+            if !is_closable(
+                TransactionKind::from(&account),
+                proposal.as_ref().map(|proposal| &proposal.status),
+                index <= settings_data.stale_transaction_index,
+            ) {
+                continue;
+            }
+            let instruction = match account {
+                TransactionAccount::Transaction(account) => smart_account.close_transaction(
+                    index,
+                    proposal
+                        .as_ref()
+                        .map_or(account.rent_collector, |p| p.rent_collector),
+                    account.rent_collector,
+                ),
+                TransactionAccount::SettingsTransaction(account) => smart_account
+                    .close_settings_transaction(
+                        index,
+                        proposal
+                            .as_ref()
+                            .map_or(account.rent_collector, |p| p.rent_collector),
+                        account.rent_collector,
+                    ),
+                TransactionAccount::Batch(_) => {
+                    println!(
+                        "skip {} #{}: the keeper leaves batches alone",
+                        settings, index
+                    );
+                    continue;
+                }
+            };
+            if self.send(&[instruction], settings, index, "close").await {
+                let lamports = transaction.lamports
+                    + proposal_account
+                        .as_ref()
+                        .map_or(0, |account| account.lamports);
+                Metrics::add(&self.metrics.closed, 1);
+                Metrics::add(&self.metrics.lamports_reclaimed, lamports);
+            }
+        }
+        self.open_from.insert(*settings, open_from);
+        Ok(())
+    }
+
+    /// Fetches `settings` and the accounts at every transaction index from
+    /// `first` on, a page of indexes per request.
+    async fn fetch_from(
+        &self,
+        settings: &Pubkey,
+        first: u64,
+    ) -> Result<(Settings, Vec<AccountPair>)> {
+        let account = fetch_multiple(self.rpc, &[*settings])
+            .await?
+            .pop()
+            .flatten()
+            .ok_or_else(|| anyhow!("account {} not found", settings))?;
+        let settings_data = VersionedSettings::from_bytes(&account.data)?.into_current();
+
+        let mut pairs = Vec::new();
+        for page in index_pages(first, settings_data.transaction_index) {
+            pairs.extend(fetch_transaction_page(self.rpc, settings, page).await?);
+        }
+        Ok((settings_data, pairs))
+    }
+
+    /// Passes decoded accounts through. An index whose accounts do not
+    /// decode is logged, counted and skipped, so that it does not hold up
+    /// the others; it is tried again on the next round.
+    fn skip_undecodable<T>(
+        &self,
+        decoded: Result<T, io::Error>,
+        settings: &Pubkey,
+        index: u64,
+    ) -> Option<T> {
+        decoded
+            .inspect_err(|e| {
+                eprintln!("skip {} #{}: {}", settings, index, e);
+                Metrics::add(&self.metrics.undecodable, 1);
+            })
+            .ok()
+    }
+
+    /// Sends `instructions` once the rate limit allows. Failures are logged
+    /// and counted, and retried on the next round.
+    async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        settings: &Pubkey,
+        index: u64,
+        action: &str,
+    ) -> bool {
+        self.limiter.wait().await;
+        let result = async {
+            let blockhash = TransactionSender::get_latest_blockhash(self.rpc).await?;
+            let transaction = VersionedTransaction::from(Transaction::new_signed_with_payer(
+                instructions,
+                Some(&self.payer.pubkey()),
+                &[self.payer],
+                blockhash,
+            ));
+            Ok::<_, anyhow::Error>(
+                TransactionSender::send_and_confirm_transaction(self.rpc, &transaction).await?,
+            )
+        }
+        .await;
+        match result {
+            Ok(signature) => {
+                println!("{} {} #{}: {}", action, settings, index, signature);
+                true
+            }
+            Err(e) => {
+                eprintln!("{} {} #{} failed: {:#}", action, settings, index, e);
+                Metrics::add(&self.metrics.failed_transactions, 1);
+                false
+            }
+        }
+    }
+}
+
+/// Decodes the transaction and proposal accounts at one index.
+fn decode(
+    transaction: Option<&Account>,
+    proposal: Option<&Account>,
+) -> Result<(Option<TransactionAccount>, Option<Proposal>), io::Error> {
+    let transaction = transaction
+        .map(|account| TransactionAccount::from_bytes(&account.data))
+        .transpose()?;
+    let proposal = proposal
+        .map(|account| VersionedProposal::from_bytes(&account.data))
+        .transpose()?
+        .map(VersionedProposal::into_current);
+    Ok((transaction, proposal))
+}
+
+/// Whether the transaction at an index can no longer be executed, whatever
+/// happens next: it was closed, its proposal is done, or it went stale
+/// without being approved. Stale approved settings transactions cannot be
+/// executed either.
+fn is_finished(
+    kind: Option<TransactionKind>,
+    status: Option<&ProposalStatus>,
+    is_stale: bool,
+) -> bool {
+    match (kind, status) {
+        (None, _) => true,
+        (
+            _,
+            Some(
+                ProposalStatus::Executed { .. }
+                | ProposalStatus::Rejected { .. }
+                | ProposalStatus::Cancelled { .. },
+            ),
+        ) => true,
+        (Some(TransactionKind::SettingsTransaction), _) => is_stale,
+        (_, Some(ProposalStatus::Approved { .. } | ProposalStatus::Executing)) => false,
+        (_, Some(ProposalStatus::Draft { .. } | ProposalStatus::Active { .. }) | None) => is_stale,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use astrolabe_client::accounts::SettingsTransaction;
+    use astrolabe_client::decode::{
+        PROPOSAL_DISCRIMINATOR, SETTINGS_DISCRIMINATOR, SETTINGS_TRANSACTION_DISCRIMINATOR,
+    };
+    use astrolabe_client::mock::MockRpc;
+    use astrolabe_client::rpc::INDEXES_PER_PAGE;
+    use astrolabe_client::types::{Permissions, SmartAccountSigner};
+
+    use super::*;
+
+    const SETTINGS: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    fn set_settings(rpc: &MockRpc, keeper: &Pubkey, transaction_index: u64) {
+        rpc.set_program_account_with_space(
+            SETTINGS,
+            &Settings {
+                discriminator: SETTINGS_DISCRIMINATOR,
+                seed: 0,
+                settings_authority: Pubkey::default(),
+                threshold: 1,
+                time_lock: 0,
+                transaction_index,
+                stale_transaction_index: 0,
+                archival_authority: None,
+                archivable_after: 0,
+                bump: 255,
+                signers: vec![SmartAccountSigner {
+                    key: *keeper,
+                    permissions: Permissions {
+                        mask: Permissions::EXECUTE,
+                    },
+                }],
+                restricted_signers: Vec::new(),
+                account_utilization: 0,
+                reserved1: 0,
+                reserved2: 0,
+            },
+            Settings::size(1, 0),
+        );
+    }
+
+    fn set_settings_transaction(rpc: &MockRpc, index: u64, status: ProposalStatus) {
+        let smart_account = SmartAccount::new(SETTINGS);
+        rpc.set_program_account(
+            smart_account.transaction_pda(index).0,
+            &SettingsTransaction {
+                discriminator: SETTINGS_TRANSACTION_DISCRIMINATOR,
+                settings: SETTINGS,
+                creator: Pubkey::default(),
+                rent_collector: Pubkey::default(),
+                index,
+                bump: 255,
+                actions: Vec::new(),
+            },
+        );
+        rpc.set_program_account_with_space(
+            smart_account.proposal_pda(index).0,
+            &Proposal {
+                discriminator: PROPOSAL_DISCRIMINATOR,
+                settings: SETTINGS,
+                transaction_index: index,
+                rent_collector: Pubkey::default(),
+                status,
+                bump: 255,
+                approved: Vec::new(),
+                rejected: Vec::new(),
+                cancelled: Vec::new(),
+            },
+            Proposal::size(1),
+        );
+    }
+
+    #[tokio::test]
+    async fn crank_skips_undecodable_indexes_and_pages_through_the_rest() {
+        let rpc = MockRpc::new();
+        let payer = Keypair::new();
+        let metrics = Metrics::default();
+        let last = 2 * INDEXES_PER_PAGE + 1;
+        rpc.set_clock(1, 1_000);
+        set_settings(&rpc, &payer.pubkey(), last);
+        set_settings_transaction(&rpc, 1, ProposalStatus::Approved { timestamp: 0 });
+        set_settings_transaction(&rpc, 2, ProposalStatus::Approved { timestamp: 0 });
+        rpc.set_account(
+            SmartAccount::new(SETTINGS).proposal_pda(2).0,
+            Account {
+                lamports: 1,
+                data: vec![1; 16],
+                owner: astrolabe_client::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        set_settings_transaction(&rpc, last, ProposalStatus::Executed { timestamp: 0 });
+
+        let mut keeper = Keeper::new(&rpc, &payer, &metrics, RateLimiter::per_minute(u32::MAX));
+        keeper.crank(&SETTINGS).await.unwrap();
+        assert_eq!(metrics.executed.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.closed.load(Ordering::Relaxed), 1);
+        // Once by each pass.
+        assert_eq!(metrics.undecodable.load(Ordering::Relaxed), 2);
+        assert_eq!(rpc.sent_transactions().len(), 2);
+        // The executed transaction is still approved in the mock, so
+        // nothing may be skipped on the next round.
+        assert_eq!(keeper.execute_from[&SETTINGS], 1);
+        assert_eq!(keeper.open_from[&SETTINGS], 1);
+    }
+
+    #[test]
+    fn is_finished_agrees_with_is_closable() {
+        let statuses = [
+            None,
+            Some(ProposalStatus::Draft { timestamp: 0 }),
+            Some(ProposalStatus::Active { timestamp: 0 }),
+            Some(ProposalStatus::Approved { timestamp: 0 }),
+            Some(ProposalStatus::Executing),
+            Some(ProposalStatus::Executed { timestamp: 0 }),
+            Some(ProposalStatus::Rejected { timestamp: 0 }),
+            Some(ProposalStatus::Cancelled { timestamp: 0 }),
+        ];
+        for kind in [
+            TransactionKind::Transaction,
+            TransactionKind::SettingsTransaction,
+            TransactionKind::Batch,
+        ] {
+            for status in &statuses {
+                for is_stale in [false, true] {
+                    // A stale settings transaction that is executing can no
+                    // longer be executed, but the program will not close it
+                    // either.
+                    let executing_stale_settings = kind == TransactionKind::SettingsTransaction
+                        && status == &Some(ProposalStatus::Executing)
+                        && is_stale;
+                    assert_eq!(
+                        is_finished(Some(kind), status.as_ref(), is_stale),
+                        is_closable(kind, status.as_ref(), is_stale) || executing_stale_settings,
+                        "{:?} {:?} stale: {}",
+                        kind,
+                        status,
+                        is_stale
+                    );
+                }
+                // Closed transactions are finished, whatever the proposal.
+                assert!(is_finished(None, status.as_ref(), false));
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! `keeper`: execute ready proposals and reclaim rent for smart accounts.
+//!
+//! Every round the keeper goes through the configured smart accounts. It
+//! executes the proposals that are approved and past their time lock, and
+//! closes the transactions the program lets anyone close, returning their
+//! rent to the rent collectors. Executing needs the keeper's key to be a
+//! signer with execute permission on the smart account; closing does not.
+//! Batches are left alone and logged.
+
+mod keeper;
+mod metrics;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use clap::Parser;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_commitment_config::CommitmentConfig;
+use solana_keypair::{read_keypair_file, Keypair};
+use solana_pubkey::Pubkey;
+
+use crate::keeper::{Keeper, RateLimiter};
+use crate::metrics::Metrics;
+
+#[derive(Parser)]
+#[command(
+    name = "keeper",
+    version,
+    about = "Execute ready proposals and reclaim rent for Astrolabe smart accounts"
+)]
+struct Args {
+    /// JSON RPC URL of the cluster.
+    #[arg(short, long, default_value = "https://api.mainnet-beta.solana.com")]
+    url: String,
+    /// Keypair file that executes and pays. Defaults to the Solana CLI keypair.
+    #[arg(short, long)]
+    keypair: Option<PathBuf>,
+    /// Settings accounts of the smart accounts to keep.
+    #[arg(long, value_delimiter = ',', required = true)]
+    settings: Vec<Pubkey>,
+    /// Seconds between rounds.
+    #[arg(long, default_value_t = 30)]
+    interval: u64,
+    /// Most transactions sent per minute, across all smart accounts.
+    #[arg(long, default_value_t = 30)]
+    max_transactions_per_minute: u32,
+    /// Address to serve Prometheus metrics on, e.g. 127.0.0.1:9100.
+    #[arg(long)]
+    metrics: Option<SocketAddr>,
+    /// Run a single round and exit.
+    #[arg(long)]
+    once: bool,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let rpc = RpcClient::new_with_commitment(args.url, CommitmentConfig::confirmed());
+    let payer = load_keypair(args.keypair)?;
+    let metrics = Arc::new(Metrics::default());
+    if let Some(address) = args.metrics {
+        metrics::serve(metrics.clone(), address)?;
+    }
+
+    let mut keeper = Keeper::new(
+        &rpc,
+        &payer,
+        &metrics,
+        RateLimiter::per_minute(args.max_transactions_per_minute),
+    );
+    loop {
+        for settings in &args.settings {
+            if let Err(e) = keeper.crank(settings).await {
+                eprintln!("{}: {:#}", settings, e);
+                Metrics::add(&metrics.errors, 1);
+            }
+        }
+        Metrics::add(&metrics.rounds, 1);
+        if args.once {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(args.interval)).await;
+    }
+}
+
+fn load_keypair(path: Option<PathBuf>) -> Result<Keypair> {
+    let path = match path {
+        Some(path) => path,
+        None => dirs::home_dir()
+            .ok_or_else(|| anyhow!("cannot locate home directory"))?
+            .join(".config/solana/id.json"),
+    };
+    if path.to_string_lossy().starts_with("usb://") {
+        bail!("hardware wallets cannot sign unattended; pass a keypair file");
+    }
+    read_keypair_file(&path)
+        .map_err(|e| anyhow!("failed to read keypair {}: {}", path.display(), e))
+}
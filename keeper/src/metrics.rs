@@ -0,0 +1,104 @@
+//! Counters in the Prometheus text format, served over plain HTTP.
+
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+/// How long a scrape may take to send its request or read the response.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+pub struct Metrics {
+    pub rounds: AtomicU64,
+    pub executed: AtomicU64,
+    pub closed: AtomicU64,
+    pub lamports_reclaimed: AtomicU64,
+    pub failed_transactions: AtomicU64,
+    pub undecodable: AtomicU64,
+    pub errors: AtomicU64,
+}
+
+impl Metrics {
+    pub fn add(counter: &AtomicU64, value: u64) {
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for (name, help, counter) in [
+            (
+                "keeper_rounds_total",
+                "Passes over all smart accounts.",
+                &self.rounds,
+            ),
+            (
+                "keeper_executed_total",
+                "Transactions executed.",
+                &self.executed,
+            ),
+            (
+                "keeper_closed_total",
+                "Transactions closed to reclaim rent.",
+                &self.closed,
+            ),
+            (
+                "keeper_lamports_reclaimed_total",
+                "Rent returned by closed accounts.",
+                &self.lamports_reclaimed,
+            ),
+            (
+                "keeper_failed_transactions_total",
+                "Transactions that failed to land.",
+                &self.failed_transactions,
+            ),
+            (
+                "keeper_undecodable_total",
+                "Times a transaction index was skipped because its accounts did not decode.",
+                &self.undecodable,
+            ),
+            (
+                "keeper_errors_total",
+                "Smart account passes that ended in an error.",
+                &self.errors,
+            ),
+        ] {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} counter", name);
+            let _ = writeln!(text, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+        text
+    }
+}
+
+/// Answers every request on `address` with the current metrics, from a
+/// background thread. Connections that stall are dropped after `TIMEOUT`
+/// so that they cannot block the scrapes behind them.
+pub fn serve(metrics: Arc<Metrics>, address: SocketAddr) -> Result<()> {
+    let listener =
+        TcpListener::bind(address).with_context(|| format!("cannot listen on {}", address))?;
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            if stream.set_read_timeout(Some(TIMEOUT)).is_err()
+                || stream.set_write_timeout(Some(TIMEOUT)).is_err()
+            {
+                continue;
+            }
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let body = metrics.render();
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+    Ok(())
+}